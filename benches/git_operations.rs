@@ -90,6 +90,33 @@ fn setup_large_modified_repo() -> BenchRepo {
     repo
 }
 
+/// Create a repo with many committed files spread over directories, a tenth of them
+/// modified, as a stand-in for a large working tree.
+fn setup_many_files_repo() -> BenchRepo {
+    let repo = BenchRepo::new();
+    for dir in 0..20 {
+        fs::create_dir_all(repo.path.join(format!("dir{}", dir)))
+            .expect("failed to create directory");
+        for file in 0..100 {
+            let mut content = String::new();
+            for line in 0..50 {
+                content.push_str(&format!("file {} line {}\n", file, line));
+            }
+            repo.write_file(&format!("dir{}/file{}.txt", dir, file), &content);
+        }
+    }
+    repo.commit_all("initial");
+    for dir in 0..20 {
+        for file in (0..100).step_by(10) {
+            let rel_path = format!("dir{}/file{}.txt", dir, file);
+            let mut content = fs::read_to_string(repo.path.join(&rel_path)).expect("read file");
+            content.push_str("appended line\n");
+            repo.write_file(&rel_path, &content);
+        }
+    }
+    repo
+}
+
 fn bench_get_diff_snapshot(c: &mut Criterion) {
     let repo = setup_modified_repo();
     let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
@@ -159,7 +186,7 @@ fn bench_stage_hunk(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -186,7 +213,7 @@ fn bench_unstage_hunk(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk").clone();
 
@@ -210,7 +237,7 @@ fn bench_stage_single_line(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
     let line_index = hunk
@@ -241,7 +268,7 @@ fn bench_unstage_single_line(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk").clone();
     let line_index = hunk
@@ -272,7 +299,7 @@ fn bench_detect_staged_lines(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -295,7 +322,7 @@ fn bench_toggle_hunk_staging(c: &mut Criterion) {
     let file_change = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -309,6 +336,72 @@ fn bench_toggle_hunk_staging(c: &mut Criterion) {
     });
 }
 
+/// A single edit in a large working tree: full snapshot versus re-diffing the one path
+fn bench_snapshot_after_one_edit_many_files(c: &mut Criterion) {
+    let repo = setup_many_files_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let previous = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    repo.write_file("dir7/file42.txt", "rewritten\n");
+    let changed = vec![PathBuf::from("dir7/file42.txt")];
+
+    let mut group = c.benchmark_group("snapshot_after_one_edit_many_files");
+    group.bench_function("full", |b| {
+        b.iter(|| {
+            git_repo
+                .get_diff_snapshot()
+                .expect("failed to get diff snapshot");
+        });
+    });
+    group.bench_function("incremental", |b| {
+        b.iter(|| {
+            git_repo
+                .update_snapshot(&previous, &changed)
+                .expect("failed to update snapshot");
+        });
+    });
+    group.finish();
+}
+
+/// Staged line detection for every hunk of a large snapshot: per hunk versus batched
+fn bench_detect_staged_lines_many_files(c: &mut Criterion) {
+    let repo = setup_many_files_repo();
+    run_git(&repo.path, &["add", "dir0", "dir1"]);
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let paths: Vec<&Path> = snapshot.files.iter().map(|f| f.path.as_path()).collect();
+
+    let mut group = c.benchmark_group("detect_staged_lines_many_files");
+    group.sample_size(10);
+    group.bench_function("per_hunk", |b| {
+        b.iter(|| {
+            for file in &snapshot.files {
+                for hunk in &file.hunks {
+                    git_repo
+                        .detect_staged_lines(hunk, &file.path)
+                        .expect("failed to detect staged lines");
+                }
+            }
+        });
+    });
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            let staged = git_repo
+                .staged_changes(&paths)
+                .expect("failed to read staged changes");
+            for file in &snapshot.files {
+                for hunk in &file.hunks {
+                    staged.staged_lines(hunk, &file.path);
+                }
+            }
+        });
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_get_diff_snapshot,
@@ -321,5 +414,7 @@ criterion_group!(
     bench_unstage_single_line,
     bench_detect_staged_lines,
     bench_toggle_hunk_staging,
+    bench_snapshot_after_one_edit_many_files,
+    bench_detect_staged_lines_many_files,
 );
 criterion_main!(benches);
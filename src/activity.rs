@@ -0,0 +1,204 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Width of one sparkline column
+pub const BUCKET_WIDTH: Duration = Duration::from_secs(10);
+
+/// How many buckets of history each file keeps: ten minutes
+pub const BUCKET_COUNT: usize = 60;
+
+/// Files tracked before the least recently touched ones are forgotten
+const FILE_LIMIT: usize = 1000;
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What a file event did to a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl TouchKind {
+    pub fn from_event(kind: &notify::EventKind) -> Option<Self> {
+        match kind {
+            notify::EventKind::Create(_) => Some(Self::Created),
+            notify::EventKind::Modify(_) => Some(Self::Modified),
+            notify::EventKind::Remove(_) => Some(Self::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// A working tree file the watcher saw an event for
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTouch {
+    /// Relative to the repository root
+    pub path: PathBuf,
+    pub kind: TouchKind,
+    pub at: SystemTime,
+}
+
+/// Event counts and a bucketed history for one file
+#[derive(Debug, Clone)]
+pub struct FileActivity {
+    pub created: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    // (bucket number, events in it), oldest first
+    buckets: VecDeque<(u64, u32)>,
+}
+
+impl FileActivity {
+    fn new(at: SystemTime) -> Self {
+        Self {
+            created: 0,
+            modified: 0,
+            removed: 0,
+            first_seen: at,
+            last_seen: at,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.created + self.modified + self.removed
+    }
+
+    fn record(&mut self, kind: TouchKind, at: SystemTime) {
+        match kind {
+            TouchKind::Created => self.created += 1,
+            TouchKind::Modified => self.modified += 1,
+            TouchKind::Removed => self.removed += 1,
+        }
+        self.first_seen = self.first_seen.min(at);
+        self.last_seen = self.last_seen.max(at);
+
+        let bucket = bucket_of(at);
+        match self.buckets.iter_mut().rev().find(|(b, _)| *b == bucket) {
+            Some((_, count)) => *count += 1,
+            None => {
+                let position = self.buckets.partition_point(|(b, _)| *b < bucket);
+                self.buckets.insert(position, (bucket, 1));
+            }
+        }
+        let newest = self.buckets.back().map(|(b, _)| *b).unwrap_or(bucket);
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(b, _)| newest - b >= BUCKET_COUNT as u64)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Events per bucket for the `width` buckets ending with the one containing `now`,
+    /// oldest first
+    pub fn counts(&self, now: SystemTime, width: usize) -> Vec<u32> {
+        let last = bucket_of(now);
+        let first = (last + 1).saturating_sub(width as u64);
+        let mut counts = vec![0; width];
+        for &(bucket, count) in &self.buckets {
+            if bucket >= first && bucket <= last {
+                counts[width - 1 - (last - bucket) as usize] = count;
+            }
+        }
+        counts
+    }
+}
+
+/// Which files the watcher saw events for, how often and when
+#[derive(Debug, Clone, Default)]
+pub struct ActivityStats {
+    files: HashMap<PathBuf, FileActivity>,
+}
+
+impl ActivityStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, touch: FileTouch) {
+        if !self.files.contains_key(&touch.path) && self.files.len() >= FILE_LIMIT {
+            if let Some(stalest) = self
+                .files
+                .iter()
+                .min_by_key(|(_, activity)| activity.last_seen)
+                .map(|(path, _)| path.clone())
+            {
+                self.files.remove(&stalest);
+            }
+        }
+        self.files
+            .entry(touch.path)
+            .or_insert_with(|| FileActivity::new(touch.at))
+            .record(touch.kind, touch.at);
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, path: &Path) -> Option<&FileActivity> {
+        self.files.get(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Files ordered hottest first: most events, then most recently touched
+    pub fn files(&self) -> Vec<(&Path, &FileActivity)> {
+        let mut files: Vec<_> = self
+            .files
+            .iter()
+            .map(|(path, activity)| (path.as_path(), activity))
+            .collect();
+        files.sort_by(|(a_path, a), (b_path, b)| {
+            b.total()
+                .cmp(&a.total())
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then(a_path.cmp(b_path))
+        });
+        files
+    }
+
+    /// Events per bucket across all files, like `FileActivity::counts`
+    pub fn counts(&self, now: SystemTime, width: usize) -> Vec<u32> {
+        let mut counts = vec![0; width];
+        for activity in self.files.values() {
+            for (total, count) in counts.iter_mut().zip(activity.counts(now, width)) {
+                *total += count;
+            }
+        }
+        counts
+    }
+}
+
+/// Render counts as block characters scaled to `peak`; idle buckets are blank
+pub fn sparkline(counts: &[u32], peak: u32) -> String {
+    counts
+        .iter()
+        .map(|&count| {
+            if count == 0 || peak == 0 {
+                ' '
+            } else {
+                let level = (count.min(peak) as usize * SPARK_LEVELS.len()).div_ceil(peak as usize);
+                SPARK_LEVELS[level.clamp(1, SPARK_LEVELS.len()) - 1]
+            }
+        })
+        .collect()
+}
+
+fn bucket_of(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / BUCKET_WIDTH.as_secs()
+}
+
+#[cfg(test)]
+#[path = "../tests/activity.rs"]
+mod tests;
@@ -8,15 +8,23 @@ use ratatui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io::{self};
+use std::path::Path;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
-use crate::diff::{CommitInfo, DiffSnapshot, FileChange};
+use crate::activity::ActivityStats;
+use crate::archive;
+use crate::composer::CommitComposer;
+use crate::diff::{
+    CleanReason, CommitInfo, ConflictChoice, ConflictedFile, DiffSnapshot, FileChange, HeadMove,
+    HeadMoveKind, Hunk, HunkId, IgnoreTarget, RepoStatus, StashInfo, UntrackedMode,
+};
 use crate::git::GitRepo;
+use crate::history::{SnapshotHistory, HISTORY_LIMIT};
 use crate::ui::UI;
-use crate::watcher::FileWatcher;
+use crate::watcher::{FileWatcher, WatchConfig, WatchEvent};
 
 // Debug logging helper
 fn debug_log(msg: String) {
@@ -43,6 +51,21 @@ pub enum Mode {
     Review,                   // Review hunks in a specific commit
 }
 
+/// One-line message shown in the header until the next key press
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusMessage {
+    pub text: String,
+    pub is_error: bool,
+}
+
+/// An action that only runs when its key is pressed a second time
+#[derive(Debug, Clone, PartialEq)]
+enum PendingConfirmation {
+    Autosquash { target_sha: String },
+    DropStash { sha: String },
+    Discard { hunk: HunkId, lines: Vec<usize> },
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FocusPane {
     FileList,
@@ -63,7 +86,8 @@ impl StreamSpeed {
 }
 
 pub struct App {
-    git_repo: GitRepo,
+    // None only for saved snapshots opened with --open, which need no repository
+    git_repo: Option<GitRepo>,
     snapshots: Vec<DiffSnapshot>,
     current_snapshot_index: usize,
     current_file_index: usize,
@@ -76,9 +100,13 @@ pub struct App {
     focus: FocusPane,
     line_selection_mode: bool,
     selected_line_index: usize,
+    // Anchor line of a visual range selection in line mode; the range runs to selected_line_index
+    selection_anchor: Option<usize>,
     // Track last selected line per hunk (file_index, hunk_index) -> line_index
     hunk_line_memory: HashMap<(usize, usize), usize>,
-    snapshot_receiver: mpsc::UnboundedReceiver<DiffSnapshot>,
+    // Split points (line indices) requested for original hunks, reapplied on every refresh
+    hunk_splits: HashMap<HunkId, BTreeSet<usize>>,
+    watch_receiver: mpsc::UnboundedReceiver<WatchEvent>,
     last_auto_advance: Instant,
     scroll_offset: u16,
     help_scroll_offset: u16,
@@ -90,29 +118,252 @@ pub struct App {
     last_diff_viewport_height: u16,
     last_help_viewport_height: u16,
     needs_full_redraw: bool,
-    _watcher: FileWatcher,
+    status_message: Option<StatusMessage>,
+    // Branch/HEAD state shown in the header
+    repo_status: RepoStatus,
+    // Commit HEAD moved to outside hunky, offered for review with `g`
+    announced_commit: Option<CommitInfo>,
+    // Why the working tree last went clean, shown until changes appear again
+    clean_reason: Option<CleanReason>,
+    // Which files the watcher saw events for, shown in the activity panel
+    activity: ActivityStats,
+    activity_panel_open: bool,
+    activity_cursor: usize,
+    // Working tree states seen so far; while the timeline is open, the one on screen and
+    // the file and hunk to return to afterwards
+    history: SnapshotHistory,
+    timeline_cursor: Option<usize>,
+    timeline_return: (usize, usize),
+    // Timeline point to compare the selected one with, instead of the one before it
+    timeline_mark: Option<usize>,
+    // History position of the last "what changed since I looked", and the delta on screen
+    // with its label
+    delta_checkpoint: usize,
+    delta_view: Option<(DiffSnapshot, String)>,
+    // Name of the saved snapshots file opened with --open; its snapshots replace the
+    // history and the timeline stays open on them
+    archive: Option<String>,
+    pending_confirmation: Option<PendingConfirmation>,
+    // In-TUI commit panel, open while composing a commit message
+    commit_composer: Option<CommitComposer>,
+    _watcher: Option<FileWatcher>,
     // Review mode state
     review_commits: Vec<CommitInfo>,
     review_commit_cursor: usize,
     review_selecting_commit: bool,
     review_snapshot: Option<DiffSnapshot>,
+    // Stash panel state
+    stash_panel_open: bool,
+    stashes: Vec<StashInfo>,
+    stash_cursor: usize,
+    stash_preview: Option<DiffSnapshot>,
+    // Hunks marked to go into the next "stash selected hunks"
+    stash_marks: HashSet<HunkId>,
+    // Unmerged paths of an in-progress merge, rebase, cherry-pick or revert
+    conflicts: Vec<ConflictedFile>,
+    conflict_view_open: bool,
+    conflict_file_cursor: usize,
+    conflict_region_cursor: usize,
+    // Every watched repository or worktree; the slot of the one on screen is empty because
+    // its state lives in the fields above
+    repo_sessions: Vec<Option<RepoSession>>,
+    active_repo: usize,
 }
 
-impl App {
-    pub async fn new(repo_path: &str) -> Result<Self> {
+/// A watched repository or worktree that is not on screen. Its watcher keeps running and
+/// its snapshot history keeps growing until it is switched back in.
+struct RepoSession {
+    git_repo: GitRepo,
+    snapshots: Vec<DiffSnapshot>,
+    current_snapshot_index: usize,
+    current_file_index: usize,
+    current_hunk_index: usize,
+    streaming_start_snapshot: Option<usize>,
+    hunk_splits: HashMap<HunkId, BTreeSet<usize>>,
+    stash_marks: HashSet<HunkId>,
+    repo_status: RepoStatus,
+    conflicts: Vec<ConflictedFile>,
+    announced_commit: Option<CommitInfo>,
+    clean_reason: Option<CleanReason>,
+    activity: ActivityStats,
+    history: SnapshotHistory,
+    delta_checkpoint: usize,
+    watch_receiver: mpsc::UnboundedReceiver<WatchEvent>,
+    watcher: FileWatcher,
+    // Watcher events received while in the background
+    new_events: usize,
+}
+
+impl RepoSession {
+    fn open(repo_path: &str, watch_config: WatchConfig) -> Result<Self> {
         let git_repo = GitRepo::new(repo_path)?;
 
         // Get initial snapshot
         let mut initial_snapshot = git_repo.get_diff_snapshot()?;
+        detect_snapshot_staged_lines(&git_repo, &mut initial_snapshot);
+        let mut history = SnapshotHistory::new();
+        history.record(&initial_snapshot);
+
+        let repo_status = git_repo.repo_status().unwrap_or_default();
+        let conflicts = git_repo.conflicted_files().unwrap_or_default();
+
+        // Set up file watcher
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher = FileWatcher::with_config(git_repo.clone(), tx, watch_config)?;
+
+        Ok(Self {
+            git_repo,
+            snapshots: vec![initial_snapshot],
+            current_snapshot_index: 0,
+            current_file_index: 0,
+            current_hunk_index: 0,
+            streaming_start_snapshot: None,
+            hunk_splits: HashMap::new(),
+            stash_marks: HashSet::new(),
+            repo_status,
+            conflicts,
+            announced_commit: None,
+            clean_reason: None,
+            activity: ActivityStats::new(),
+            history,
+            delta_checkpoint: 0,
+            watch_receiver: rx,
+            watcher,
+            new_events: 0,
+        })
+    }
+
+    /// Take in watcher events while in the background: snapshots join the history the same
+    /// way the app would take them for the current mode. Returns the watcher's warnings,
+    /// which can't wait until the repository is switched to.
+    fn drain_events(&mut self, mode: Mode) -> Vec<String> {
+        let mut warnings = Vec::new();
+        while let Ok(event) = self.watch_receiver.try_recv() {
+            let mut snapshot = match event {
+                WatchEvent::Snapshot(snapshot) => snapshot,
+                WatchEvent::HeadMoved(head_move) => {
+                    self.new_events += 1;
+                    if head_move.kind == HeadMoveKind::Commit {
+                        self.announced_commit = Some(head_move.commit);
+                    }
+                    continue;
+                }
+                WatchEvent::TreeClean(reason) => {
+                    self.clean_reason = Some(reason);
+                    continue;
+                }
+                WatchEvent::Warning(warning) => {
+                    warnings.push(warning);
+                    continue;
+                }
+                WatchEvent::Activity(touches) => {
+                    for touch in touches {
+                        self.activity.record(touch);
+                    }
+                    continue;
+                }
+            };
+            self.new_events += 1;
+            if !snapshot.files.is_empty() {
+                self.clean_reason = None;
+            }
+            detect_snapshot_staged_lines(&self.git_repo, &mut snapshot);
+            let dropped = self.history.record(&snapshot);
+            self.delta_checkpoint = self.delta_checkpoint.saturating_sub(dropped);
+            match mode {
+                Mode::View => {
+                    if self.current_file_index >= snapshot.files.len() {
+                        self.current_file_index = 0;
+                        self.current_hunk_index = 0;
+                    }
+                    self.snapshots[self.current_snapshot_index] = snapshot;
+                }
+                Mode::Streaming(_) if !snapshot.files.is_empty() => {
+                    self.snapshots.push(snapshot);
+                    if trim_streamed_snapshots(
+                        &mut self.snapshots,
+                        &mut self.current_snapshot_index,
+                        &mut self.streaming_start_snapshot,
+                    ) {
+                        self.current_file_index = 0;
+                        self.current_hunk_index = 0;
+                    }
+                }
+                Mode::Streaming(_) | Mode::Review => {}
+            }
+        }
+        warnings
+    }
+}
+
+/// Drop the oldest streamed snapshots past the history limit, keeping the indices on the
+/// same snapshots. Returns whether the snapshot being shown was dropped, in which case the
+/// oldest one kept is shown instead.
+fn trim_streamed_snapshots(
+    snapshots: &mut Vec<DiffSnapshot>,
+    current: &mut usize,
+    streaming_start: &mut Option<usize>,
+) -> bool {
+    let excess = snapshots.len().saturating_sub(HISTORY_LIMIT);
+    if excess == 0 {
+        return false;
+    }
+    snapshots.drain(..excess);
+    *streaming_start = streaming_start.and_then(|start| start.checked_sub(excess));
+    match current.checked_sub(excess) {
+        Some(index) => {
+            *current = index;
+            false
+        }
+        None => {
+            *current = 0;
+            true
+        }
+    }
+}
 
-        // Detect staged lines for initial snapshot
-        for file in &mut initial_snapshot.files {
-            for hunk in &mut file.hunks {
-                // Detect which lines are actually staged in git's index
-                if let Ok(staged_indices) = git_repo.detect_staged_lines(hunk, &file.path) {
+/// Name of a watched repository: its working directory's folder name
+fn repo_display_name(git_repo: &GitRepo) -> String {
+    git_repo
+        .repo_path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| git_repo.repo_path().display().to_string())
+}
+
+/// Like [`App::repo`], for when other fields of the app are borrowed
+fn repo_of(git_repo: &Option<GitRepo>) -> Result<&GitRepo> {
+    git_repo
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Saved snapshots have no repository to work on"))
+}
+
+/// Mark which lines of each hunk are already staged in git's index
+fn detect_snapshot_staged_lines(git_repo: &GitRepo, snapshot: &mut DiffSnapshot) {
+    // One pair of index diffs per repository for the whole snapshot rather than per hunk
+    let paths: Vec<&Path> = snapshot
+        .files
+        .iter()
+        .map(|file| file.path.as_path())
+        .collect();
+    let staged_changes = git_repo.staged_changes(&paths);
+    if let Err(e) = &staged_changes {
+        debug_log(format!("Failed to read staged changes: {}", e));
+    }
+    for file in &mut snapshot.files {
+        for hunk in &mut file.hunks {
+            let staged = match staged_changes
+                .as_ref()
+                .ok()
+                .and_then(|staged| staged.staged_lines(hunk, &file.path))
+            {
+                Some(staged) => Ok(staged),
+                None => git_repo.detect_staged_lines(hunk, &file.path),
+            };
+            match staged {
+                Ok(staged_indices) => {
                     hunk.staged_line_indices = staged_indices;
 
-                    // Check if all change lines are staged
                     let total_change_lines = hunk
                         .lines
                         .iter()
@@ -125,16 +376,87 @@ impl App {
                     hunk.staged = hunk.staged_line_indices.len() == total_change_lines
                         && total_change_lines > 0;
                 }
+                Err(e) => {
+                    debug_log(format!("Failed to detect staged lines: {}", e));
+                }
             }
         }
+    }
+}
 
-        // Set up file watcher
-        let (tx, rx) = mpsc::unbounded_channel();
-        let watcher = FileWatcher::new(git_repo.clone(), tx)?;
+impl App {
+    /// Watch a single repository
+    #[cfg(test)]
+    pub async fn new(repo_path: &str) -> Result<Self> {
+        Self::with_repos(&[repo_path.to_string()], WatchConfig::default()).await
+    }
 
-        let app = Self {
+    /// Watch several repositories or worktrees in one session, each with its own watcher
+    /// and snapshot history. The first one is shown; `[` and `]` switch between them.
+    pub async fn with_repos(repo_paths: &[String], watch_config: WatchConfig) -> Result<Self> {
+        let mut sessions: Vec<RepoSession> = Vec::new();
+        for repo_path in repo_paths {
+            let session = RepoSession::open(repo_path, watch_config)?;
+            // The same worktree reached through two paths is watched once
+            if sessions
+                .iter()
+                .all(|known| known.git_repo.repo_path() != session.git_repo.repo_path())
+            {
+                sessions.push(session);
+            }
+        }
+        if sessions.is_empty() {
+            return Err(anyhow::anyhow!("No repository to watch"));
+        }
+        let RepoSession {
             git_repo,
-            snapshots: vec![initial_snapshot],
+            snapshots,
+            repo_status,
+            conflicts,
+            history,
+            watch_receiver: rx,
+            watcher,
+            ..
+        } = sessions.remove(0);
+        let mut app = Self::with_state(Some(git_repo), snapshots, history, rx, Some(watcher));
+        app.repo_status = repo_status;
+        app.conflicts = conflicts;
+        app.repo_sessions.extend(sessions.into_iter().map(Some));
+        Ok(app)
+    }
+
+    /// Show saved snapshots read-only, without a repository or watcher, so any file opened
+    /// with --open works wherever hunky runs
+    pub fn from_archive(path: &Path, snapshots: Vec<DiffSnapshot>) -> Self {
+        // Nothing ever sends on this channel
+        let (_, rx) = mpsc::unbounded_channel();
+        let current = snapshots.last().cloned().unwrap_or_else(|| DiffSnapshot {
+            timestamp: std::time::SystemTime::now(),
+            head: None,
+            files: Vec::new(),
+            untracked_overflow: 0,
+        });
+        let mut app = Self::with_state(None, vec![current], SnapshotHistory::new(), rx, None);
+        app.open_archive(path, snapshots);
+        app
+    }
+
+    /// The repository on screen, or an error while viewing saved snapshots without one
+    fn repo(&self) -> Result<&GitRepo> {
+        repo_of(&self.git_repo)
+    }
+
+    /// An app on `snapshots` with every view closed and nothing selected yet
+    fn with_state(
+        git_repo: Option<GitRepo>,
+        snapshots: Vec<DiffSnapshot>,
+        history: SnapshotHistory,
+        rx: mpsc::UnboundedReceiver<WatchEvent>,
+        watcher: Option<FileWatcher>,
+    ) -> Self {
+        Self {
+            git_repo,
+            snapshots,
             current_snapshot_index: 0,
             current_file_index: 0,
             current_hunk_index: 0,
@@ -146,8 +468,10 @@ impl App {
             focus: FocusPane::HunkView,
             line_selection_mode: false,
             selected_line_index: 0,
+            selection_anchor: None,
             hunk_line_memory: HashMap::new(),
-            snapshot_receiver: rx,
+            hunk_splits: HashMap::new(),
+            watch_receiver: rx,
             last_auto_advance: Instant::now(),
             scroll_offset: 0,
             help_scroll_offset: 0,
@@ -157,14 +481,39 @@ impl App {
             last_diff_viewport_height: 20, // Reasonable default
             last_help_viewport_height: 20, // Reasonable default
             needs_full_redraw: true,
+            status_message: None,
+            repo_status: RepoStatus::default(),
+            announced_commit: None,
+            clean_reason: None,
+            activity: ActivityStats::new(),
+            activity_panel_open: false,
+            activity_cursor: 0,
+            history,
+            timeline_cursor: None,
+            timeline_return: (0, 0),
+            timeline_mark: None,
+            delta_checkpoint: 0,
+            delta_view: None,
+            archive: None,
+            pending_confirmation: None,
+            commit_composer: None,
             _watcher: watcher,
             review_commits: Vec::new(),
             review_commit_cursor: 0,
             review_selecting_commit: false,
             review_snapshot: None,
-        };
-
-        Ok(app)
+            stash_panel_open: false,
+            stashes: Vec::new(),
+            stash_cursor: 0,
+            stash_preview: None,
+            stash_marks: HashSet::new(),
+            conflicts: Vec::new(),
+            conflict_view_open: false,
+            conflict_file_cursor: 0,
+            conflict_region_cursor: 0,
+            repo_sessions: vec![None],
+            active_repo: 0,
+        }
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -189,85 +538,127 @@ impl App {
         result
     }
 
-    async fn run_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
-        loop {
-            // Check for new snapshots
-            while let Ok(mut snapshot) = self.snapshot_receiver.try_recv() {
-                debug_log(format!(
-                    "Received snapshot with {} files",
-                    snapshot.files.len()
-                ));
+    /// Take in everything the watchers sent since the last loop iteration
+    fn take_watch_events(&mut self) {
+        let mut warnings = Vec::new();
+        for session in self.repo_sessions.iter_mut().flatten() {
+            warnings.extend(session.drain_events(self.mode));
+        }
+        if let Some(warning) = warnings.pop() {
+            self.set_status(warning, true);
+        }
 
-                // Detect staged lines for all hunks
-                for file in &mut snapshot.files {
-                    for hunk in &mut file.hunks {
-                        // Detect which lines are actually staged in git's index
-                        match self.git_repo.detect_staged_lines(hunk, &file.path) {
-                            Ok(staged_indices) => {
-                                hunk.staged_line_indices = staged_indices;
-
-                                // Check if all change lines are staged
-                                let total_change_lines = hunk
-                                    .lines
-                                    .iter()
-                                    .filter(|line| {
-                                        (line.starts_with('+') && !line.starts_with("+++"))
-                                            || (line.starts_with('-') && !line.starts_with("---"))
-                                    })
-                                    .count();
-
-                                hunk.staged = hunk.staged_line_indices.len() == total_change_lines
-                                    && total_change_lines > 0;
-
-                                if !hunk.staged_line_indices.is_empty() {
-                                    debug_log(format!("Detected {} staged lines in hunk (total: {}, fully staged: {})", 
-                                        hunk.staged_line_indices.len(), total_change_lines, hunk.staged));
-                                }
-                            }
-                            Err(e) => {
-                                debug_log(format!("Failed to detect staged lines: {}", e));
-                            }
-                        }
+        // Check for new snapshots
+        let mut received_snapshot = false;
+        while let Ok(event) = self.watch_receiver.try_recv() {
+            let mut snapshot = match event {
+                WatchEvent::Snapshot(snapshot) => snapshot,
+                WatchEvent::HeadMoved(head_move) => {
+                    self.announce_head_move(head_move);
+                    continue;
+                }
+                WatchEvent::TreeClean(reason) => {
+                    self.clean_reason = Some(reason);
+                    // A commit or reset was already announced with its HEAD move
+                    if reason == CleanReason::Cleared {
+                        self.set_status(reason.description().to_string(), false);
                     }
+                    continue;
                 }
-
-                match self.mode {
-                    Mode::View => {
-                        // In View mode, update the current snapshot with new staged line info
-                        // Replace the current snapshot entirely with the new one
-                        if !self.snapshots.is_empty() {
-                            self.snapshots[self.current_snapshot_index] = snapshot;
-                            debug_log("Updated current snapshot in View mode".to_string());
+                WatchEvent::Warning(warning) => {
+                    self.set_status(warning, true);
+                    continue;
+                }
+                WatchEvent::Activity(touches) => {
+                    for touch in touches {
+                        self.activity.record(touch);
+                    }
+                    continue;
+                }
+            };
+            debug_log(format!(
+                "Received snapshot with {} files",
+                snapshot.files.len()
+            ));
+            received_snapshot = true;
+            // The history keeps hunks as git reported them, to rebuild file contents from
+            self.record_history(&snapshot);
+            self.apply_hunk_splits(&mut snapshot);
+
+            // Detect staged lines for all hunks
+            self.annotate_staged_lines(&mut snapshot);
+
+            match self.mode {
+                Mode::View => {
+                    // In View mode, update the current snapshot with new staged line info
+                    // Replace the current snapshot entirely with the new one
+                    if !self.snapshots.is_empty() {
+                        if snapshot.files.is_empty() {
+                            // Nothing stale stays on screen once the tree is clean
+                            self.current_file_index = 0;
+                            self.current_hunk_index = 0;
+                            self.selected_line_index = 0;
+                            self.selection_anchor = None;
+                            self.scroll_offset = 0;
+                        } else {
+                            self.clean_reason = None;
                         }
+                        self.snapshots[self.current_snapshot_index] = snapshot;
+                        debug_log("Updated current snapshot in View mode".to_string());
                     }
-                    Mode::Review => {
-                        // In Review mode, ignore live snapshot updates (reviewing a commit)
-                        debug_log("Ignoring snapshot update in Review mode".to_string());
+                }
+                Mode::Review => {
+                    // In Review mode, ignore live snapshot updates (reviewing a commit)
+                    debug_log("Ignoring snapshot update in Review mode".to_string());
+                }
+                Mode::Streaming(_) if snapshot.files.is_empty() => {
+                    // Sent because HEAD moved; there is nothing new to stream
+                    debug_log("Ignoring empty snapshot in Streaming mode".to_string());
+                }
+                Mode::Streaming(_) => {
+                    // In Streaming mode, only add snapshots that arrived after we entered streaming
+                    // These are "new" changes to stream
+                    self.snapshots.push(snapshot);
+                    if trim_streamed_snapshots(
+                        &mut self.snapshots,
+                        &mut self.current_snapshot_index,
+                        &mut self.streaming_start_snapshot,
+                    ) {
+                        self.current_file_index = 0;
+                        self.current_hunk_index = 0;
                     }
-                    Mode::Streaming(_) => {
-                        // In Streaming mode, only add snapshots that arrived after we entered streaming
-                        // These are "new" changes to stream
-                        self.snapshots.push(snapshot);
-                        debug_log(format!(
-                            "Added new snapshot in Streaming mode. Total snapshots: {}",
-                            self.snapshots.len()
-                        ));
-
-                        // If we're on an empty/old snapshot, advance to the new one
-                        if let Some(start_idx) = self.streaming_start_snapshot {
-                            if self.current_snapshot_index <= start_idx {
-                                self.current_snapshot_index = self.snapshots.len() - 1;
-                                self.current_file_index = 0;
-                                self.current_hunk_index = 0;
-                                debug_log("Advanced to new snapshot in Streaming mode".to_string());
-                            }
+                    debug_log(format!(
+                        "Added new snapshot in Streaming mode. Total snapshots: {}",
+                        self.snapshots.len()
+                    ));
+
+                    // If we're on an empty/old snapshot, advance to the new one
+                    if let Some(start_idx) = self.streaming_start_snapshot {
+                        if self.current_snapshot_index <= start_idx {
+                            self.current_snapshot_index = self.snapshots.len() - 1;
+                            self.current_file_index = 0;
+                            self.current_hunk_index = 0;
+                            debug_log("Advanced to new snapshot in Streaming mode".to_string());
                         }
                     }
                 }
             }
+        }
+
+        if received_snapshot {
+            self.refresh_repo_status();
+        }
+    }
+
+    async fn run_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        loop {
+            self.take_watch_events();
 
             // Auto-advance in Streaming Auto mode
-            if let Mode::Streaming(StreamingType::Auto(speed)) = self.mode {
+            // Scrubbing the timeline pauses streaming
+            if let (Mode::Streaming(StreamingType::Auto(speed)), None) =
+                (self.mode, self.timeline_cursor)
+            {
                 let elapsed = self.last_auto_advance.elapsed();
                 // Get current hunk change count (not including context lines) for duration calculation
                 let change_count = self
@@ -312,6 +703,10 @@ impl App {
             // Handle input (non-blocking)
             if event::poll(Duration::from_millis(50))? {
                 if let Event::Key(key) = event::read()? {
+                    self.status_message = None;
+                    // Any key other than the confirming one cancels a pending action
+                    let pending_confirmation = self.pending_confirmation.take();
+
                     // If the commit picker overlay is active, handle its keys first
                     if self.review_selecting_commit {
                         match key.code {
@@ -319,12 +714,12 @@ impl App {
                             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                                 break
                             }
-                            KeyCode::Char('j') | KeyCode::Down => {
+                            KeyCode::Char('j') | KeyCode::Down
                                 if !self.review_commits.is_empty()
-                                    && self.review_commit_cursor + 1 < self.review_commits.len()
-                                {
-                                    self.review_commit_cursor += 1;
-                                }
+                                    && self.review_commit_cursor + 1
+                                        < self.review_commits.len() =>
+                            {
+                                self.review_commit_cursor += 1;
                             }
                             KeyCode::Char('k') | KeyCode::Up => {
                                 self.review_commit_cursor =
@@ -345,6 +740,102 @@ impl App {
                         continue;
                     }
 
+                    if self.activity_panel_open {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Char('Q') => break,
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                break
+                            }
+                            KeyCode::Char('j') | KeyCode::Down => self.move_activity_cursor(1),
+                            KeyCode::Char('k') | KeyCode::Up => self.move_activity_cursor(-1),
+                            KeyCode::Enter => self.jump_to_activity_file(),
+                            KeyCode::Esc | KeyCode::Char('W') => {
+                                self.activity_panel_open = false;
+                                debug_log("Closed activity panel".to_string());
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    if self.stash_panel_open {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Char('Q') => break,
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                break
+                            }
+                            KeyCode::Char('j') | KeyCode::Down
+                                if self.stash_cursor + 1 < self.stashes.len() =>
+                            {
+                                self.stash_cursor += 1;
+                            }
+                            KeyCode::Char('k') | KeyCode::Up => {
+                                self.stash_cursor = self.stash_cursor.saturating_sub(1);
+                            }
+                            KeyCode::Enter => self.preview_selected_stash(),
+                            KeyCode::Char('a') => self.apply_selected_stash(false),
+                            KeyCode::Char('p') => self.apply_selected_stash(true),
+                            KeyCode::Char('d') => self.drop_selected_stash(pending_confirmation),
+                            KeyCode::Esc => {
+                                self.stash_panel_open = false;
+                                debug_log("Closed stash panel".to_string());
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    if self.conflict_view_open {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Char('Q') => break,
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                break
+                            }
+                            KeyCode::Char('j') | KeyCode::Down => self.move_conflict_region(1),
+                            KeyCode::Char('k') | KeyCode::Up => self.move_conflict_region(-1),
+                            KeyCode::Char('n') | KeyCode::Tab => self.move_conflict_file(1),
+                            KeyCode::Char('p') | KeyCode::BackTab => self.move_conflict_file(-1),
+                            KeyCode::Char('o') => {
+                                self.resolve_current_conflict(ConflictChoice::Ours)
+                            }
+                            KeyCode::Char('t') => {
+                                self.resolve_current_conflict(ConflictChoice::Theirs)
+                            }
+                            KeyCode::Char('b') => {
+                                self.resolve_current_conflict(ConflictChoice::Both)
+                            }
+                            KeyCode::Enter => self.mark_current_conflict_resolved(),
+                            KeyCode::Esc => {
+                                self.conflict_view_open = false;
+                                debug_log("Closed conflict view".to_string());
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // The commit composer captures all typing while it is open
+                    if let Some(composer) = self.commit_composer.as_mut() {
+                        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                        match key.code {
+                            KeyCode::Char('c') if ctrl => break,
+                            KeyCode::Char('s') if ctrl => self.submit_commit_composer(),
+                            KeyCode::Char('a') if ctrl => composer.toggle_amend(),
+                            KeyCode::Char('o') if ctrl => composer.toggle_signoff(),
+                            KeyCode::Char('t') if ctrl => composer.cycle_template(),
+                            KeyCode::Char(ch) if !ctrl => composer.insert_char(ch),
+                            KeyCode::Enter => composer.newline(),
+                            KeyCode::Backspace => composer.backspace(),
+                            KeyCode::Tab | KeyCode::BackTab => composer.toggle_field(),
+                            KeyCode::Esc => {
+                                self.commit_composer = None;
+                                debug_log("Closed commit composer".to_string());
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
                     match key.code {
                         KeyCode::Char('q') | KeyCode::Char('Q') => break,
                         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -367,23 +858,104 @@ impl App {
                                 }
                             }
                         }
-                        KeyCode::Char('r') | KeyCode::Char('R') => {
-                            if self.mode != Mode::Review {
-                                self.enter_review_mode();
-                            }
+                        KeyCode::Char('F') if self.mode == Mode::Review => {
+                            self.create_fixup_for_reviewed_commit();
                         }
-                        KeyCode::Char('c') => {
-                            if self.mode != Mode::Review {
-                                if let Err(e) = self.open_commit_mode() {
-                                    debug_log(format!("Failed to open commit mode: {}", e));
-                                }
+                        KeyCode::Char('A') if self.mode == Mode::Review => {
+                            self.amend_head_from_review();
+                        }
+                        KeyCode::Char('Z') if self.mode == Mode::Review => {
+                            self.autosquash_reviewed_commit(pending_confirmation);
+                        }
+                        KeyCode::Char('Z') if !self.read_only_view() => {
+                            self.stash_marked_hunks();
+                        }
+                        KeyCode::Char('t') if !self.read_only_view() => {
+                            self.toggle_stash_mark();
+                        }
+                        KeyCode::Char('g')
+                            if self.announced_commit.is_some()
+                                && self.stash_preview.is_none()
+                                && self.timeline_cursor.is_none() =>
+                        {
+                            self.review_announced_commit();
+                        }
+                        KeyCode::Char('O') if !self.read_only_view() => {
+                            self.toggle_recurse_submodules();
+                        }
+                        KeyCode::Char('u') if !self.read_only_view() => {
+                            self.cycle_untracked_mode();
+                        }
+                        KeyCode::Char('i') if !self.read_only_view() => {
+                            self.ignore_current_file(IgnoreTarget::GitIgnore);
+                        }
+                        KeyCode::Char('I') if !self.read_only_view() => {
+                            self.ignore_current_file(IgnoreTarget::InfoExclude);
+                        }
+                        KeyCode::Char(']') if !self.read_only_view() => {
+                            self.switch_repo(1);
+                        }
+                        KeyCode::Char('[') if !self.read_only_view() => {
+                            self.switch_repo(-1);
+                        }
+                        KeyCode::Char('N') if !self.read_only_view() => {
+                            self.intent_to_add_current_file();
+                        }
+                        KeyCode::Char('M') if !self.read_only_view() => {
+                            self.open_conflict_view();
+                        }
+                        KeyCode::Char('z')
+                            if self.stash_preview.is_none() && self.timeline_cursor.is_none() =>
+                        {
+                            self.open_stash_panel();
+                        }
+                        KeyCode::Char('W') => self.open_activity_panel(),
+                        KeyCode::Char('E') => self.export_snapshots(),
+                        KeyCode::Char('T') if self.timeline_cursor.is_some() => {
+                            self.close_timeline();
+                        }
+                        KeyCode::Char('T') if !self.read_only_view() => self.open_timeline(),
+                        KeyCode::Left | KeyCode::Char(',') if self.timeline_cursor.is_some() => {
+                            self.step_timeline(-1);
+                        }
+                        KeyCode::Right | KeyCode::Char('.') if self.timeline_cursor.is_some() => {
+                            self.step_timeline(1);
+                        }
+                        KeyCode::Enter if self.timeline_cursor.is_some() => {
+                            self.toggle_timeline_mark();
+                        }
+                        KeyCode::Char('D') if self.delta_view.is_some() => self.close_delta_view(),
+                        KeyCode::Char('D')
+                            if self.stash_preview.is_none() && self.mode != Mode::Review =>
+                        {
+                            self.open_delta_view();
+                        }
+                        KeyCode::Home if self.timeline_cursor.is_some() => {
+                            self.step_timeline(isize::MIN);
+                        }
+                        KeyCode::End if self.timeline_cursor.is_some() => {
+                            self.step_timeline(isize::MAX);
+                        }
+                        KeyCode::Char('r') | KeyCode::Char('R') if !self.read_only_view() => {
+                            self.enter_review_mode();
+                        }
+                        KeyCode::Char('c') if !self.read_only_view() => {
+                            self.open_commit_composer();
+                        }
+                        KeyCode::Char('C') if !self.read_only_view() => {
+                            if let Err(e) = self.open_commit_mode() {
+                                debug_log(format!("Failed to open commit mode: {}", e));
                             }
                         }
-                        KeyCode::Char('m') => {
-                            if self.mode != Mode::Review {
-                                self.cycle_mode();
+                        KeyCode::Char('e') if !self.read_only_view() => {
+                            if let Err(e) = self.edit_current_hunk() {
+                                debug_log(format!("Failed to edit hunk: {}", e));
+                                self.set_status(format!("Failed to edit hunk: {}", e), true);
                             }
                         }
+                        KeyCode::Char('m') if !self.read_only_view() => {
+                            self.cycle_mode();
+                        }
                         KeyCode::Char(' ') => {
                             // Advance to next hunk
                             self.advance_hunk();
@@ -507,6 +1079,8 @@ impl App {
                             if self.mode == Mode::Review {
                                 // In review mode, toggle acceptance of the current hunk (in-memory)
                                 self.toggle_review_acceptance();
+                            } else if self.stash_preview.is_some() {
+                                // Stash previews are read-only
                             } else {
                                 // Stage/unstage current selection (smart toggle)
                                 self.stage_current_selection();
@@ -523,6 +1097,16 @@ impl App {
                         KeyCode::Char('l') | KeyCode::Char('L') => {
                             self.toggle_line_selection_mode()
                         }
+                        KeyCode::Char('v') | KeyCode::Char('V') => self.toggle_range_selection(),
+                        KeyCode::Char('d') if !self.read_only_view() => {
+                            self.discard_current_selection(pending_confirmation);
+                        }
+                        KeyCode::Char('x') if !self.read_only_view() => {
+                            self.split_current_hunk();
+                        }
+                        KeyCode::Char('X') if !self.read_only_view() => {
+                            self.unsplit_current_hunk();
+                        }
                         KeyCode::Char('h') => {
                             // Toggle help sidebar
                             self.show_help = !self.show_help;
@@ -538,7 +1122,19 @@ impl App {
                             self.extended_help_scroll_offset = 0;
                         }
                         KeyCode::Esc => {
-                            if self.mode == Mode::Review {
+                            if self.selection_anchor.is_some() {
+                                // Leave range selection but stay in line mode
+                                self.selection_anchor = None;
+                            } else if self.stash_preview.is_some() {
+                                // Back from the preview to the stash list
+                                self.close_stash_preview();
+                            } else if self.delta_view.is_some() {
+                                // Back from the comparison to where it was opened
+                                self.close_delta_view();
+                            } else if self.timeline_cursor.is_some() {
+                                // Back from the history to the live working tree
+                                self.close_timeline();
+                            } else if self.mode == Mode::Review {
                                 // Exit review mode, go back to View
                                 self.exit_review_mode();
                             } else {
@@ -547,6 +1143,7 @@ impl App {
                                 self.extended_help_scroll_offset = 0;
                                 self.mode = Mode::View;
                                 self.line_selection_mode = false;
+                                self.selection_anchor = None;
                                 self.focus = FocusPane::HunkView;
                                 self.show_help = false;
                                 self.help_scroll_offset = 0;
@@ -578,6 +1175,7 @@ impl App {
         // Clear line memory for current hunk before moving
         let old_hunk_key = (self.current_file_index, self.current_hunk_index);
         self.hunk_line_memory.remove(&old_hunk_key);
+        self.selection_anchor = None;
 
         // Advance to next hunk
         self.current_hunk_index += 1;
@@ -630,6 +1228,7 @@ impl App {
         // Clear line memory for current hunk before moving
         let old_hunk_key = (self.current_file_index, self.current_hunk_index);
         self.hunk_line_memory.remove(&old_hunk_key);
+        self.selection_anchor = None;
 
         // Reset scroll when moving to a different hunk
         self.scroll_offset = 0;
@@ -805,12 +1404,16 @@ impl App {
         // Remove all entries for this file
         self.hunk_line_memory
             .retain(|(f_idx, _), _| *f_idx != file_index);
+        self.selection_anchor = None;
     }
 
     fn cycle_mode(&mut self) {
         self.mode = match self.mode {
             Mode::View => {
                 self.streaming_start_snapshot = Some(self.current_snapshot_index);
+                for session in self.repo_sessions.iter_mut().flatten() {
+                    session.streaming_start_snapshot = Some(session.current_snapshot_index);
+                }
                 debug_log(format!(
                     "Entering Streaming mode, baseline snapshot: {}",
                     self.current_snapshot_index
@@ -831,6 +1434,12 @@ impl App {
                 self.current_snapshot_index = self.snapshots.len() - 1;
                 self.current_file_index = 0;
                 self.current_hunk_index = 0;
+                for session in self.repo_sessions.iter_mut().flatten() {
+                    session.streaming_start_snapshot = None;
+                    session.current_snapshot_index = session.snapshots.len() - 1;
+                    session.current_file_index = 0;
+                    session.current_hunk_index = 0;
+                }
                 debug_log("Exiting Streaming mode, back to View".to_string());
                 Mode::View
             }
@@ -864,6 +1473,7 @@ impl App {
             self.hunk_line_memory
                 .insert(hunk_key, self.selected_line_index);
             self.line_selection_mode = false;
+            self.selection_anchor = None;
         }
     }
 
@@ -889,6 +1499,7 @@ impl App {
             self.hunk_line_memory
                 .insert(hunk_key, self.selected_line_index);
             self.line_selection_mode = false;
+            self.selection_anchor = None;
         }
     }
 
@@ -899,6 +1510,7 @@ impl App {
                 self.hunk_line_memory
                     .insert(hunk_key, self.selected_line_index);
                 self.line_selection_mode = false;
+                self.selection_anchor = None;
             } else {
                 self.line_selection_mode = true;
                 let hunk_key = (self.current_file_index, self.current_hunk_index);
@@ -912,61 +1524,233 @@ impl App {
         }
     }
 
-    fn stage_current_selection(&mut self) {
-        let mut refresh_needed = false;
+    fn toggle_range_selection(&mut self) {
+        if self.focus != FocusPane::HunkView {
+            return;
+        }
 
-        match self.focus {
-            FocusPane::HunkView => {
-                // Check if we're in line selection mode
-                if self.line_selection_mode {
-                    // Stage/unstage a single line
-                    if let Some(snapshot) = self.snapshots.get_mut(self.current_snapshot_index) {
-                        if let Some(file) = snapshot.files.get_mut(self.current_file_index) {
-                            if let Some(hunk) = file.hunks.get_mut(self.current_hunk_index) {
-                                // Get the selected line
-                                if let Some(selected_line) =
-                                    hunk.lines.get(self.selected_line_index)
-                                {
-                                    // Only stage change lines (+ or -)
-                                    if (selected_line.starts_with('+')
-                                        && !selected_line.starts_with("+++"))
-                                        || (selected_line.starts_with('-')
-                                            && !selected_line.starts_with("---"))
-                                    {
-                                        // Check if line is already staged
-                                        let is_staged = hunk
-                                            .staged_line_indices
-                                            .contains(&self.selected_line_index);
+        if self.selection_anchor.is_some() {
+            self.selection_anchor = None;
+            return;
+        }
 
-                                        if is_staged {
-                                            // Unstage the single line
-                                            match self.git_repo.unstage_single_line(
-                                                hunk,
-                                                self.selected_line_index,
-                                                &file.path,
-                                            ) {
-                                                Ok(_) => {
-                                                    // Remove this line from staged indices
-                                                    hunk.staged_line_indices
-                                                        .remove(&self.selected_line_index);
-                                                    debug_log(format!(
-                                                        "Unstaged line {} in {}",
-                                                        self.selected_line_index,
-                                                        file.path.display()
-                                                    ));
-                                                    refresh_needed = true;
+        // Starting a range implies line mode
+        if !self.line_selection_mode {
+            self.toggle_line_selection_mode();
+        }
+        self.selection_anchor = Some(self.selected_line_index);
+    }
+
+    /// Inclusive line range of the visual selection, if one is active
+    pub fn selected_line_range(&self) -> Option<(usize, usize)> {
+        let anchor = self.selection_anchor?;
+        if !self.line_selection_mode {
+            return None;
+        }
+        Some((
+            anchor.min(self.selected_line_index),
+            anchor.max(self.selected_line_index),
+        ))
+    }
+
+    /// Change lines of the given hunk covered by the current line-mode selection
+    fn selected_change_indices(&self, hunk: &crate::diff::Hunk) -> HashSet<usize> {
+        let (start, end) = self
+            .selected_line_range()
+            .unwrap_or((self.selected_line_index, self.selected_line_index));
+        hunk.lines
+            .iter()
+            .enumerate()
+            .filter(|(idx, line)| {
+                *idx >= start
+                    && *idx <= end
+                    && ((line.starts_with('+') && !line.starts_with("+++"))
+                        || (line.starts_with('-') && !line.starts_with("---")))
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    fn stage_selected_range(&mut self) {
+        let Some(file) = self.current_file() else {
+            return;
+        };
+        let Some(hunk) = file.hunks.get(self.current_hunk_index) else {
+            return;
+        };
+
+        let selected = self.selected_change_indices(hunk);
+        if selected.is_empty() {
+            return;
+        }
+
+        // Unstage the range if it is already fully staged, otherwise stage all of it
+        let desired: HashSet<usize> = if selected.is_subset(&hunk.staged_line_indices) {
+            hunk.staged_line_indices
+                .difference(&selected)
+                .copied()
+                .collect()
+        } else {
+            hunk.staged_line_indices.union(&selected).copied().collect()
+        };
+
+        match self
+            .repo()
+            .and_then(|repo| repo.set_hunk_staged_lines(hunk, &file.path, &desired))
+        {
+            Ok(_) => {
+                debug_log(format!(
+                    "Updated staging for {} selected lines in {}",
+                    selected.len(),
+                    file.path.display()
+                ));
+                self.selection_anchor = None;
+                self.refresh_current_snapshot_from_git();
+            }
+            Err(e) => {
+                debug_log(format!("Failed to stage selected lines: {}", e));
+                self.set_status(format!("Failed to stage selected lines: {}", e), true);
+            }
+        }
+    }
+
+    /// Discard the selected lines, or the whole hunk outside line mode, after a second `d`
+    /// press; the worktree and index changes are gone for good
+    fn discard_current_selection(&mut self, pending: Option<PendingConfirmation>) {
+        if self.focus != FocusPane::HunkView {
+            return;
+        }
+        let Some(file) = self.current_file() else {
+            return;
+        };
+        let Some(hunk) = file.hunks.get(self.current_hunk_index) else {
+            return;
+        };
+
+        // Line mode discards the selected line(s); hunk mode discards the whole hunk
+        let selected = if self.line_selection_mode {
+            self.selected_change_indices(hunk)
+        } else {
+            hunk.lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| Hunk::is_change_line(line))
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+        if selected.is_empty() {
+            return;
+        }
+
+        let mut lines: Vec<usize> = selected.iter().copied().collect();
+        lines.sort_unstable();
+        let confirmation = PendingConfirmation::Discard {
+            hunk: hunk.id.clone(),
+            lines,
+        };
+        if pending.as_ref() != Some(&confirmation) {
+            let what = if self.line_selection_mode {
+                let count = selected.len();
+                format!("{} line{}", count, if count == 1 { "" } else { "s" })
+            } else {
+                "this hunk".to_string()
+            };
+            self.pending_confirmation = Some(confirmation);
+            self.set_status(
+                format!("Press d again to discard {}; this cannot be undone", what),
+                false,
+            );
+            return;
+        }
+
+        match self
+            .repo()
+            .and_then(|repo| repo.discard_hunk_lines(hunk, &file.path, &selected))
+        {
+            Ok(_) => {
+                debug_log(format!(
+                    "Discarded {} lines in {}",
+                    selected.len(),
+                    file.path.display()
+                ));
+                self.selection_anchor = None;
+                self.refresh_current_snapshot_from_git();
+            }
+            Err(e) => {
+                debug_log(format!("Failed to discard selection: {}", e));
+                self.set_status(format!("Failed to discard: {}", e), true);
+            }
+        }
+    }
+
+    fn stage_current_selection(&mut self) {
+        let mut refresh_needed = false;
+        let mut failure = None;
+
+        if self.focus == FocusPane::HunkView && self.selected_line_range().is_some() {
+            self.stage_selected_range();
+            return;
+        }
+
+        match self.focus {
+            FocusPane::HunkView => {
+                // Check if we're in line selection mode
+                if self.line_selection_mode {
+                    // Stage/unstage a single line
+                    if let Some(snapshot) = self.snapshots.get_mut(self.current_snapshot_index) {
+                        if let Some(file) = snapshot.files.get_mut(self.current_file_index) {
+                            if let Some(hunk) = file.hunks.get_mut(self.current_hunk_index) {
+                                // Get the selected line
+                                if let Some(selected_line) =
+                                    hunk.lines.get(self.selected_line_index)
+                                {
+                                    // Only stage change lines (+ or -)
+                                    if (selected_line.starts_with('+')
+                                        && !selected_line.starts_with("+++"))
+                                        || (selected_line.starts_with('-')
+                                            && !selected_line.starts_with("---"))
+                                    {
+                                        // Check if line is already staged
+                                        let is_staged = hunk
+                                            .staged_line_indices
+                                            .contains(&self.selected_line_index);
+
+                                        if is_staged {
+                                            // Unstage the single line
+                                            match repo_of(&self.git_repo).and_then(|repo| {
+                                                repo.unstage_single_line(
+                                                    hunk,
+                                                    self.selected_line_index,
+                                                    &file.path,
+                                                )
+                                            }) {
+                                                Ok(_) => {
+                                                    // Remove this line from staged indices
+                                                    hunk.staged_line_indices
+                                                        .remove(&self.selected_line_index);
+                                                    debug_log(format!(
+                                                        "Unstaged line {} in {}",
+                                                        self.selected_line_index,
+                                                        file.path.display()
+                                                    ));
+                                                    refresh_needed = true;
                                                 }
                                                 Err(e) => {
-                                                    debug_log(format!("Failed to unstage line: {}. Note: Line-level unstaging is experimental and may not work for all hunks. Consider unstaging the entire hunk with Shift+U instead.", e));
+                                                    let message =
+                                                        format!("Failed to unstage line: {}", e);
+                                                    debug_log(message.clone());
+                                                    failure = Some(message);
                                                 }
                                             }
                                         } else {
                                             // Stage the single line
-                                            match self.git_repo.stage_single_line(
-                                                hunk,
-                                                self.selected_line_index,
-                                                &file.path,
-                                            ) {
+                                            match repo_of(&self.git_repo).and_then(|repo| {
+                                                repo.stage_single_line(
+                                                    hunk,
+                                                    self.selected_line_index,
+                                                    &file.path,
+                                                )
+                                            }) {
                                                 Ok(_) => {
                                                     // Mark this line as staged
                                                     hunk.staged_line_indices
@@ -979,7 +1763,10 @@ impl App {
                                                     refresh_needed = true;
                                                 }
                                                 Err(e) => {
-                                                    debug_log(format!("Failed to stage line: {}. Note: Line-level staging is experimental and may not work for all hunks. Consider staging the entire hunk with Shift+S instead.", e));
+                                                    let message =
+                                                        format!("Failed to stage line: {}", e);
+                                                    debug_log(message.clone());
+                                                    failure = Some(message);
                                                 }
                                             }
                                         }
@@ -993,7 +1780,9 @@ impl App {
                     if let Some(snapshot) = self.snapshots.get_mut(self.current_snapshot_index) {
                         if let Some(file) = snapshot.files.get_mut(self.current_file_index) {
                             if let Some(hunk) = file.hunks.get_mut(self.current_hunk_index) {
-                                match self.git_repo.toggle_hunk_staging(hunk, &file.path) {
+                                match repo_of(&self.git_repo)
+                                    .and_then(|repo| repo.toggle_hunk_staging(hunk, &file.path))
+                                {
                                     Ok(is_staged_now) => {
                                         if is_staged_now {
                                             debug_log(format!(
@@ -1026,7 +1815,9 @@ impl App {
 
                         if any_staged {
                             // Unstage the file
-                            match self.git_repo.unstage_file(&file.path) {
+                            match repo_of(&self.git_repo)
+                                .and_then(|repo| repo.unstage_file(&file.path))
+                            {
                                 Ok(_) => {
                                     // Mark all hunks as unstaged
                                     for hunk in &mut file.hunks {
@@ -1042,7 +1833,9 @@ impl App {
                             }
                         } else {
                             // Stage the file
-                            match self.git_repo.stage_file(&file.path) {
+                            match repo_of(&self.git_repo)
+                                .and_then(|repo| repo.stage_file(&file.path))
+                            {
                                 Ok(_) => {
                                     // Mark all hunks as staged
                                     for hunk in &mut file.hunks {
@@ -1074,23 +1867,33 @@ impl App {
             }
         }
 
+        if let Some(message) = failure {
+            self.set_status(message, true);
+        }
         if refresh_needed {
             self.refresh_current_snapshot_from_git();
         }
     }
 
-    fn open_commit_mode(&mut self) -> Result<()> {
-        // Temporarily suspend the TUI so git/editor can take over the terminal.
+    /// Temporarily suspend the TUI so git or an editor can take over the terminal.
+    fn with_suspended_terminal<T>(&mut self, run: impl FnOnce(&GitRepo) -> T) -> Result<T> {
+        let git_repo = self.repo()?.clone();
         disable_raw_mode()?;
         execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
 
-        let commit_result = self.git_repo.commit_with_editor();
+        let result = run(&git_repo);
 
         // Always restore TUI state before returning.
         enable_raw_mode()?;
         execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
 
-        let status = commit_result?;
+        self.last_auto_advance = Instant::now();
+        self.needs_full_redraw = true;
+        Ok(result)
+    }
+
+    fn open_commit_mode(&mut self) -> Result<()> {
+        let status = self.with_suspended_terminal(|git_repo| git_repo.commit_with_editor())??;
         if !status.success() {
             debug_log(format!(
                 "git commit exited with status {:?} (possibly canceled or nothing to commit)",
@@ -1099,175 +1902,824 @@ impl App {
         }
 
         self.refresh_current_snapshot_from_git();
-        self.last_auto_advance = Instant::now();
-        self.needs_full_redraw = true;
         Ok(())
     }
 
-    fn annotate_staged_lines(&self, snapshot: &mut DiffSnapshot) {
-        for file in &mut snapshot.files {
-            for hunk in &mut file.hunks {
-                match self.git_repo.detect_staged_lines(hunk, &file.path) {
-                    Ok(staged_indices) => {
-                        hunk.staged_line_indices = staged_indices;
-
-                        let total_change_lines = hunk
-                            .lines
-                            .iter()
-                            .filter(|line| {
-                                (line.starts_with('+') && !line.starts_with("+++"))
-                                    || (line.starts_with('-') && !line.starts_with("---"))
-                            })
-                            .count();
-
-                        hunk.staged = hunk.staged_line_indices.len() == total_change_lines
-                            && total_change_lines > 0;
-                    }
-                    Err(e) => {
-                        debug_log(format!("Failed to detect staged lines: {}", e));
-                    }
-                }
+    /// Open the in-TUI commit panel for the currently staged changes
+    fn open_commit_composer(&mut self) {
+        let staged_files = match self.repo().and_then(|repo| repo.staged_files()) {
+            Ok(files) => files,
+            Err(e) => {
+                self.set_status(format!("Failed to read staged files: {}", e), true);
+                return;
             }
-        }
+        };
+        let template = self
+            .repo()
+            .and_then(|repo| repo.commit_template())
+            .unwrap_or_else(|e| {
+                debug_log(format!("Ignoring commit template: {}", e));
+                None
+            });
+        let head_message = self
+            .repo()
+            .and_then(|repo| repo.head_commit_message())
+            .unwrap_or(None);
+        let signoff_trailer = self.repo().and_then(|repo| repo.signoff_trailer()).ok();
+
+        self.commit_composer = Some(CommitComposer::new(
+            staged_files,
+            template,
+            head_message,
+            signoff_trailer,
+        ));
+        debug_log("Opened commit composer".to_string());
     }
 
-    fn refresh_current_snapshot_from_git(&mut self) {
-        let previous_selected_line = self.selected_line_index;
-
-        match self.git_repo.get_diff_snapshot() {
-            Ok(mut snapshot) => {
-                self.annotate_staged_lines(&mut snapshot);
+    /// Create the commit described by the composer, keeping the panel open on failure
+    fn submit_commit_composer(&mut self) {
+        let Some(composer) = self.commit_composer.as_mut() else {
+            return;
+        };
+        if !composer.confirm_commit() {
+            return;
+        }
 
-                if self.snapshots.is_empty() {
-                    self.snapshots.push(snapshot);
-                    self.current_snapshot_index = 0;
+        let amend = composer.amend;
+        match repo_of(&self.git_repo)
+            .and_then(|repo| repo.create_commit(&composer.message(), amend))
+        {
+            Ok(oid) => {
+                let short_sha: String = oid.to_string().chars().take(7).collect();
+                let text = if amend {
+                    format!("Amended HEAD as {}", short_sha)
                 } else {
-                    self.snapshots[self.current_snapshot_index] = snapshot;
-                }
+                    format!("Created commit {}", short_sha)
+                };
+                self.commit_composer = None;
+                self.set_status(text, false);
+                self.refresh_current_snapshot_from_git();
+            }
+            Err(e) => {
+                debug_log(format!("Failed to create commit: {}", e));
+                self.set_status(format!("Commit failed: {}", e), true);
+            }
+        }
+    }
 
-                // Clamp indices after snapshot replacement
-                if let Some(current_snapshot) = self.snapshots.get(self.current_snapshot_index) {
-                    if current_snapshot.files.is_empty() {
-                        self.current_file_index = 0;
-                        self.current_hunk_index = 0;
-                        self.selected_line_index = 0;
-                        return;
-                    }
+    /// Open the current hunk as a patch in the user's editor and stage the edited result
+    fn edit_current_hunk(&mut self) -> Result<()> {
+        let Some((file_path, status, hunk)) = self.current_file().and_then(|file| {
+            file.hunks
+                .get(self.current_hunk_index)
+                .map(|hunk| (file.path.clone(), file.status.clone(), hunk.clone()))
+        }) else {
+            return Ok(());
+        };
 
-                    if self.current_file_index >= current_snapshot.files.len() {
-                        self.current_file_index = current_snapshot.files.len().saturating_sub(1);
-                    }
+        let patch_path = self.repo().and_then(|repo| repo.hunk_edit_file())?;
+        std::fs::write(
+            &patch_path,
+            GitRepo::hunk_edit_patch(&hunk, &file_path, &status),
+        )?;
 
-                    if let Some(file) = current_snapshot.files.get(self.current_file_index) {
-                        if file.hunks.is_empty() {
-                            self.current_hunk_index = 0;
-                            self.selected_line_index = 0;
-                            return;
-                        }
+        let status =
+            self.with_suspended_terminal(|git_repo| git_repo.edit_file_with_editor(&patch_path))?;
+        let edited = std::fs::read_to_string(&patch_path);
+        let _ = std::fs::remove_file(&patch_path);
 
-                        if self.current_hunk_index >= file.hunks.len() {
-                            self.current_hunk_index = file.hunks.len().saturating_sub(1);
-                        }
+        let status = status?;
+        if !status.success() {
+            self.set_status(
+                format!(
+                    "Editor exited with status {:?}; hunk left unchanged",
+                    status.code()
+                ),
+                true,
+            );
+            return Ok(());
+        }
 
-                        if self.line_selection_mode {
-                            self.select_nearest_change_line(previous_selected_line);
-                        } else {
-                            self.selected_line_index = 0;
-                        }
-                    }
-                }
+        self.apply_edited_hunk(&hunk, &file_path, &edited?);
+        Ok(())
+    }
+
+    /// Stage an edited hunk patch, reporting the outcome in the header
+    fn apply_edited_hunk(&mut self, hunk: &Hunk, file_path: &Path, edited: &str) {
+        match self
+            .repo()
+            .and_then(|repo| repo.apply_edited_hunk(hunk, file_path, edited))
+        {
+            Ok(()) => {
+                self.selection_anchor = None;
+                self.set_status("Staged edited hunk".to_string(), false);
             }
             Err(e) => {
-                debug_log(format!(
-                    "Failed to refresh snapshot after staging action: {}",
-                    e
-                ));
+                debug_log(format!("Failed to apply edited hunk: {}", e));
+                self.set_status(e.to_string(), true);
             }
         }
+        self.refresh_current_snapshot_from_git();
     }
 
-    fn select_nearest_change_line(&mut self, preferred_index: usize) {
-        if let Some(snapshot) = self.current_snapshot() {
-            if let Some(file) = snapshot.files.get(self.current_file_index) {
-                if let Some(hunk) = file.hunks.get(self.current_hunk_index) {
-                    let change_indices: Vec<usize> = hunk
-                        .lines
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(idx, line)| {
-                            ((line.starts_with('+') && !line.starts_with("+++"))
-                                || (line.starts_with('-') && !line.starts_with("---")))
-                            .then_some(idx)
-                        })
-                        .collect();
+    fn set_status(&mut self, text: String, is_error: bool) {
+        self.status_message = Some(StatusMessage { text, is_error });
+    }
 
-                    if change_indices.is_empty() {
-                        self.selected_line_index = 0;
-                        return;
-                    }
+    /// Replace hunks that the user has split with their sub-hunks
+    fn apply_hunk_splits(&self, snapshot: &mut DiffSnapshot) {
+        if self.hunk_splits.is_empty() {
+            return;
+        }
 
-                    let mut best_idx = change_indices[0];
-                    let mut best_dist = usize::abs_diff(best_idx, preferred_index);
+        for file in &mut snapshot.files {
+            if !file
+                .hunks
+                .iter()
+                .any(|h| self.hunk_splits.contains_key(&h.id))
+            {
+                continue;
+            }
 
-                    for &idx in change_indices.iter().skip(1) {
-                        let dist = usize::abs_diff(idx, preferred_index);
-                        if dist < best_dist || (dist == best_dist && idx < best_idx) {
-                            best_idx = idx;
-                            best_dist = dist;
-                        }
+            let hunks = std::mem::take(&mut file.hunks);
+            for hunk in hunks {
+                match self.hunk_splits.get(&hunk.id) {
+                    Some(points) => {
+                        let points: Vec<usize> = points.iter().copied().collect();
+                        file.hunks.extend(hunk.split_at(&points, &file.path));
                     }
-
-                    self.selected_line_index = best_idx;
-                    return;
+                    None => file.hunks.push(hunk),
                 }
             }
         }
-
-        self.selected_line_index = 0;
     }
 
-    /// Get the active snapshot for navigation — works in both normal and review modes.
-    fn active_snapshot(&self) -> Option<&DiffSnapshot> {
-        if self.mode == Mode::Review {
-            self.review_snapshot.as_ref()
-        } else {
-            self.snapshots.get(self.current_snapshot_index)
-        }
-    }
+    /// Split the current hunk: at the selected line in line mode, otherwise at every context
+    /// boundary between groups of changes.
+    fn split_current_hunk(&mut self) {
+        let Some(hunk) = self
+            .current_file()
+            .and_then(|file| file.hunks.get(self.current_hunk_index))
+        else {
+            return;
+        };
 
-    fn enter_review_mode(&mut self) {
-        match self.git_repo.get_recent_commits(20) {
-            Ok(commits) => {
-                if commits.is_empty() {
-                    debug_log("No commits found for review".to_string());
-                    return;
-                }
-                self.review_commits = commits;
-                self.review_commit_cursor = 0;
-                self.review_selecting_commit = true;
-                self.mode = Mode::Review;
-                debug_log("Entered review mode, showing commit picker".to_string());
-            }
-            Err(e) => {
-                debug_log(format!("Failed to get commits for review: {}", e));
+        let points = if self.line_selection_mode {
+            if hunk.can_split_at(self.selected_line_index) {
+                vec![self.selected_line_index]
+            } else {
+                Vec::new()
             }
-        }
-    }
+        } else {
+            hunk.context_split_points()
+        };
 
-    fn select_review_commit(&mut self) {
-        if self.review_commit_cursor >= self.review_commits.len() {
+        if points.is_empty() {
+            debug_log("Nothing to split in current hunk".to_string());
             return;
         }
-        let sha = self.review_commits[self.review_commit_cursor].sha.clone();
-        debug_log(format!(
-            "Loading commit diff for {}",
-            &sha[..7.min(sha.len())]
-        ));
 
-        match self.git_repo.get_commit_diff(&sha) {
-            Ok(snapshot) => {
-                self.review_snapshot = Some(snapshot);
-                self.review_selecting_commit = false;
+        // Record split points against the original hunk so they survive refreshes
+        let (root_id, offset) = hunk
+            .split_from
+            .clone()
+            .unwrap_or_else(|| (hunk.id.clone(), 0));
+        let split_count = points.len();
+        self.hunk_splits
+            .entry(root_id)
+            .or_default()
+            .extend(points.into_iter().map(|point| point + offset));
+
+        debug_log(format!("Split current hunk at {} point(s)", split_count));
+        self.selection_anchor = None;
+        self.scroll_offset = 0;
+        self.refresh_current_snapshot_from_git();
+    }
+
+    /// Merge the sub-hunks of the current hunk back into the original hunk
+    fn unsplit_current_hunk(&mut self) {
+        let Some(hunk) = self
+            .current_file()
+            .and_then(|file| file.hunks.get(self.current_hunk_index))
+        else {
+            return;
+        };
+        let Some((root_id, _)) = hunk.split_from.clone() else {
+            return;
+        };
+
+        // Land on the merged hunk, which sits where its first piece was
+        let first_piece_index = self
+            .current_file()
+            .and_then(|file| {
+                file.hunks
+                    .iter()
+                    .position(|h| h.split_from.as_ref().is_some_and(|(id, _)| *id == root_id))
+            })
+            .unwrap_or(self.current_hunk_index);
+
+        self.hunk_splits.remove(&root_id);
+        self.current_hunk_index = first_piece_index;
+        self.selection_anchor = None;
+        self.scroll_offset = 0;
+        self.refresh_current_snapshot_from_git();
+    }
+
+    fn annotate_staged_lines(&self, snapshot: &mut DiffSnapshot) {
+        if let Ok(git_repo) = self.repo() {
+            detect_snapshot_staged_lines(git_repo, snapshot);
+        }
+    }
+
+    fn refresh_repo_status(&mut self) {
+        match self.repo().and_then(|repo| repo.repo_status()) {
+            Ok(status) => self.repo_status = status,
+            Err(e) => debug_log(format!("Failed to read repository status: {}", e)),
+        }
+        self.refresh_conflicts();
+    }
+
+    /// Cycle untracked files between shown, collapsed by directory and hidden
+    fn cycle_untracked_mode(&mut self) {
+        let Ok(git_repo) = self.repo() else {
+            return;
+        };
+        let mode = git_repo.untracked_mode().next();
+        git_repo.set_untracked_mode(mode);
+        self.set_status(format!("Untracked files: {}", mode.label()), false);
+        self.refresh_current_snapshot_from_git();
+    }
+
+    /// Include or leave out changes inside submodules, for every watched repository
+    pub fn set_recurse_submodules(&mut self, recurse: bool) {
+        if let Ok(git_repo) = self.repo() {
+            git_repo.set_recurse_submodules(recurse);
+        }
+        for session in self.repo_sessions.iter().flatten() {
+            session.git_repo.set_recurse_submodules(recurse);
+        }
+        self.refresh_current_snapshot_from_git();
+    }
+
+    fn toggle_recurse_submodules(&mut self) {
+        let recurse = !self.recurses_submodules();
+        self.set_recurse_submodules(recurse);
+        let label = if recurse { "shown" } else { "hidden" };
+        self.set_status(format!("Submodule changes {}", label), false);
+    }
+
+    pub fn recurses_submodules(&self) -> bool {
+        self.repo().is_ok_and(GitRepo::recurses_submodules)
+    }
+
+    /// Ignore the selected untracked file or directory
+    fn ignore_current_file(&mut self, target: IgnoreTarget) {
+        let Some(file) = self.current_file() else {
+            return;
+        };
+        if file.status != "Untracked" {
+            self.set_status("Only untracked files can be ignored".to_string(), true);
+            return;
+        }
+        let path = file.path.clone();
+        let ignore_file = match target {
+            IgnoreTarget::GitIgnore => ".gitignore",
+            IgnoreTarget::InfoExclude => ".git/info/exclude",
+        };
+
+        match self.repo().and_then(|repo| repo.ignore_path(&path, target)) {
+            Ok(()) => self.set_status(
+                format!("Added {} to {}", path.display(), ignore_file),
+                false,
+            ),
+            Err(e) => {
+                debug_log(format!("Failed to ignore {}: {}", path.display(), e));
+                self.set_status(e.to_string(), true);
+            }
+        }
+        self.refresh_current_snapshot_from_git();
+    }
+
+    /// Bring the next (or previous) watched repository on screen. The one being left keeps
+    /// its watcher, history and position.
+    fn switch_repo(&mut self, offset: isize) {
+        let count = self.repo_sessions.len();
+        if count < 2 {
+            self.set_status("Only one repository is being watched".to_string(), false);
+            return;
+        }
+        let target = (self.active_repo as isize + offset).rem_euclid(count as isize) as usize;
+        let Some(mut session) = self.repo_sessions[target].take() else {
+            return;
+        };
+        session.drain_events(self.mode);
+        self.swap_session(&mut session);
+        session.new_events = 0;
+        self.repo_sessions[self.active_repo] = Some(session);
+        self.active_repo = target;
+
+        // Line selections and scroll positions belong to the other repository's hunks
+        self.line_selection_mode = false;
+        self.selection_anchor = None;
+        self.selected_line_index = 0;
+        self.hunk_line_memory.clear();
+        self.scroll_offset = 0;
+        self.refresh_current_snapshot_from_git();
+        let repo_path = self
+            .repo()
+            .map(|repo| repo.repo_path().display().to_string())
+            .unwrap_or_default();
+        debug_log(format!("Switched to repository {}", repo_path));
+        self.set_status(
+            format!("Watching {} ({}/{})", repo_path, target + 1, count),
+            false,
+        );
+    }
+
+    /// Sessions only exist next to a repository on screen, so both sides have one
+    fn swap_session(&mut self, session: &mut RepoSession) {
+        if let Some(git_repo) = self.git_repo.as_mut() {
+            std::mem::swap(git_repo, &mut session.git_repo);
+        }
+        std::mem::swap(&mut self.snapshots, &mut session.snapshots);
+        std::mem::swap(
+            &mut self.current_snapshot_index,
+            &mut session.current_snapshot_index,
+        );
+        std::mem::swap(
+            &mut self.current_file_index,
+            &mut session.current_file_index,
+        );
+        std::mem::swap(
+            &mut self.current_hunk_index,
+            &mut session.current_hunk_index,
+        );
+        std::mem::swap(
+            &mut self.streaming_start_snapshot,
+            &mut session.streaming_start_snapshot,
+        );
+        std::mem::swap(&mut self.hunk_splits, &mut session.hunk_splits);
+        std::mem::swap(&mut self.stash_marks, &mut session.stash_marks);
+        std::mem::swap(&mut self.repo_status, &mut session.repo_status);
+        std::mem::swap(&mut self.conflicts, &mut session.conflicts);
+        std::mem::swap(&mut self.announced_commit, &mut session.announced_commit);
+        std::mem::swap(&mut self.clean_reason, &mut session.clean_reason);
+        std::mem::swap(&mut self.activity, &mut session.activity);
+        std::mem::swap(&mut self.history, &mut session.history);
+        std::mem::swap(&mut self.delta_checkpoint, &mut session.delta_checkpoint);
+        std::mem::swap(&mut self.watch_receiver, &mut session.watch_receiver);
+        if let Some(watcher) = self._watcher.as_mut() {
+            std::mem::swap(watcher, &mut session.watcher);
+        }
+    }
+
+    /// Why the working tree went clean, while it still is
+    pub fn clean_reason(&self) -> Option<CleanReason> {
+        self.clean_reason
+    }
+
+    /// Name and position of the repository on screen, when more than one is watched
+    pub fn repo_label(&self) -> Option<String> {
+        if self.repo_sessions.len() < 2 {
+            return None;
+        }
+        Some(format!(
+            "{} [{}/{}]",
+            repo_display_name(self.repo().ok()?),
+            self.active_repo + 1,
+            self.repo_sessions.len()
+        ))
+    }
+
+    /// Names of the background repositories that changed since they were last on screen
+    pub fn repos_with_new_events(&self) -> Vec<String> {
+        self.repo_sessions
+            .iter()
+            .flatten()
+            .filter(|session| session.new_events > 0)
+            .map(|session| repo_display_name(&session.git_repo))
+            .collect()
+    }
+
+    /// Mark the selected untracked file as intent-to-add so its lines can be staged
+    fn intent_to_add_current_file(&mut self) {
+        let Some(file) = self.current_file() else {
+            return;
+        };
+        if file.status != "Untracked" || file.path.to_string_lossy().ends_with('/') {
+            self.set_status(
+                "Only untracked files can be marked intent-to-add".to_string(),
+                true,
+            );
+            return;
+        }
+        let path = file.path.clone();
+
+        match self.repo().and_then(|repo| repo.intent_to_add(&path)) {
+            Ok(()) => self.set_status(format!("Marked {} intent-to-add", path.display()), false),
+            Err(e) => {
+                debug_log(format!(
+                    "Failed to mark {} intent-to-add: {}",
+                    path.display(),
+                    e
+                ));
+                self.set_status(e.to_string(), true);
+            }
+        }
+        self.refresh_current_snapshot_from_git();
+    }
+
+    pub fn untracked_mode(&self) -> UntrackedMode {
+        self.repo().map(GitRepo::untracked_mode).unwrap_or_default()
+    }
+
+    fn refresh_conflicts(&mut self) {
+        match self.repo().and_then(|repo| repo.conflicted_files()) {
+            Ok(conflicts) => self.conflicts = conflicts,
+            Err(e) => {
+                debug_log(format!("Failed to read conflicts: {}", e));
+                return;
+            }
+        }
+
+        if self.conflicts.is_empty() {
+            self.conflict_view_open = false;
+            self.conflict_file_cursor = 0;
+            self.conflict_region_cursor = 0;
+            return;
+        }
+        self.conflict_file_cursor = self.conflict_file_cursor.min(self.conflicts.len() - 1);
+        let region_count = self.conflicts[self.conflict_file_cursor].regions.len();
+        self.conflict_region_cursor = self
+            .conflict_region_cursor
+            .min(region_count.saturating_sub(1));
+    }
+
+    fn open_conflict_view(&mut self) {
+        self.refresh_conflicts();
+        if self.conflicts.is_empty() {
+            self.set_status("No conflicted files".to_string(), false);
+            return;
+        }
+        self.conflict_view_open = true;
+        debug_log(format!(
+            "Opened conflict view with {} files",
+            self.conflicts.len()
+        ));
+    }
+
+    fn open_activity_panel(&mut self) {
+        if self.activity.is_empty() {
+            self.set_status("No file activity yet".to_string(), false);
+            return;
+        }
+        self.activity_cursor = self.activity_cursor.min(self.activity.len() - 1);
+        self.activity_panel_open = true;
+        debug_log(format!(
+            "Opened activity panel with {} files",
+            self.activity.len()
+        ));
+    }
+
+    fn move_activity_cursor(&mut self, delta: isize) {
+        let last = self.activity.len().saturating_sub(1);
+        self.activity_cursor = self.activity_cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Close the activity panel and show the selected file's changes, if it has any
+    fn jump_to_activity_file(&mut self) {
+        let Some(path) = self
+            .activity
+            .files()
+            .get(self.activity_cursor)
+            .map(|(path, _)| path.to_path_buf())
+        else {
+            return;
+        };
+        self.activity_panel_open = false;
+        let index = self
+            .active_snapshot()
+            .and_then(|snapshot| snapshot.files.iter().position(|file| file.path == path));
+        match index {
+            Some(index) => {
+                let old_file_index = self.current_file_index;
+                self.current_file_index = index;
+                self.current_hunk_index = 0;
+                self.scroll_offset = 0;
+                self.clear_line_memory_for_file(old_file_index);
+            }
+            None => self.set_status(format!("{} has no changes", path.display()), false),
+        }
+    }
+
+    fn move_conflict_region(&mut self, delta: isize) {
+        let Some(file) = self.conflicts.get(self.conflict_file_cursor) else {
+            return;
+        };
+        let last = file.regions.len().saturating_sub(1);
+        self.conflict_region_cursor = self
+            .conflict_region_cursor
+            .saturating_add_signed(delta)
+            .min(last);
+    }
+
+    fn move_conflict_file(&mut self, delta: isize) {
+        if self.conflicts.is_empty() {
+            return;
+        }
+        let count = self.conflicts.len() as isize;
+        self.conflict_file_cursor =
+            (self.conflict_file_cursor as isize + delta).rem_euclid(count) as usize;
+        self.conflict_region_cursor = 0;
+    }
+
+    /// Resolve the selected conflict region with the chosen side(s). Files without regions,
+    /// such as modify/delete conflicts, take the chosen side as a whole.
+    fn resolve_current_conflict(&mut self, choice: ConflictChoice) {
+        let Some(file) = self.conflicts.get(self.conflict_file_cursor) else {
+            return;
+        };
+        let path = file.path.clone();
+        let side = match choice {
+            ConflictChoice::Ours => "ours",
+            ConflictChoice::Theirs => "theirs",
+            ConflictChoice::Both => "both",
+        };
+
+        let result = if file.regions.is_empty() {
+            self.repo()
+                .and_then(|repo| repo.take_conflict_side(&path, choice))
+                .map(|()| format!("Resolved {} with {}", path.display(), side))
+        } else {
+            let remaining = file.regions.len() - 1;
+            self.repo()
+                .and_then(|repo| {
+                    repo.resolve_conflict_region(&path, self.conflict_region_cursor, choice)
+                })
+                .map(|()| match remaining {
+                    0 => format!(
+                        "Took {} in {}; no conflicts left, Enter marks it resolved",
+                        side,
+                        path.display()
+                    ),
+                    _ => format!(
+                        "Took {} in {}; {} conflict{} left",
+                        side,
+                        path.display(),
+                        remaining,
+                        if remaining == 1 { "" } else { "s" }
+                    ),
+                })
+        };
+
+        match result {
+            Ok(message) => self.set_status(message, false),
+            Err(e) => {
+                debug_log(format!("Failed to resolve conflict: {}", e));
+                self.set_status(e.to_string(), true);
+            }
+        }
+        // Resolved files rejoin the regular hunks
+        self.refresh_current_snapshot_from_git();
+    }
+
+    fn mark_current_conflict_resolved(&mut self) {
+        let Some(file) = self.conflicts.get(self.conflict_file_cursor) else {
+            return;
+        };
+        let path = file.path.clone();
+        match self
+            .repo()
+            .and_then(|repo| repo.mark_conflict_resolved(&path))
+        {
+            Ok(()) => self.set_status(format!("Marked {} resolved", path.display()), false),
+            Err(e) => {
+                debug_log(format!("Failed to mark conflict resolved: {}", e));
+                self.set_status(e.to_string(), true);
+            }
+        }
+        self.refresh_current_snapshot_from_git();
+    }
+
+    pub fn conflicts(&self) -> &[ConflictedFile] {
+        &self.conflicts
+    }
+
+    pub fn conflict_view_open(&self) -> bool {
+        self.conflict_view_open
+    }
+
+    pub fn conflict_file_cursor(&self) -> usize {
+        self.conflict_file_cursor
+    }
+
+    pub fn conflict_region_cursor(&self) -> usize {
+        self.conflict_region_cursor
+    }
+
+    /// Tell the user that HEAD moved outside hunky and offer the new commit for review.
+    /// Moves hunky made itself are already reflected in `repo_status` and stay quiet.
+    fn announce_head_move(&mut self, head_move: HeadMove) {
+        let commit = head_move.commit;
+        if self.repo_status.head_short_sha.as_deref() == Some(commit.short_sha.as_str())
+            && self.repo_status.branch == head_move.branch
+        {
+            debug_log(format!("Ignoring own HEAD move to {}", commit.short_sha));
+            return;
+        }
+
+        let text = match head_move.kind {
+            HeadMoveKind::Commit => format!(
+                "new commit {} by {}: {}",
+                commit.short_sha, commit.author, commit.summary
+            ),
+            HeadMoveKind::Checkout => match &head_move.branch {
+                Some(branch) => format!("switched to {} at {}", branch, commit.short_sha),
+                None => format!("HEAD detached at {}", commit.short_sha),
+            },
+            HeadMoveKind::Reset => {
+                format!("HEAD reset to {}: {}", commit.short_sha, commit.summary)
+            }
+        };
+        self.set_status(format!("{} (g to review)", text), false);
+        self.announced_commit = Some(commit);
+    }
+
+    /// Jump into review mode on the commit announced by the last HEAD move
+    fn review_announced_commit(&mut self) {
+        let Some(commit) = self.announced_commit.take() else {
+            return;
+        };
+        if self.mode == Mode::Review {
+            self.exit_review_mode();
+        }
+        self.commit_composer = None;
+        self.stash_panel_open = false;
+
+        self.enter_review_mode();
+        if self.mode != Mode::Review {
+            return;
+        }
+        match self.review_commits.iter().position(|c| c.sha == commit.sha) {
+            Some(idx) => self.review_commit_cursor = idx,
+            // HEAD may have moved again since; review the announced commit anyway
+            None => self.review_commits.insert(0, commit),
+        }
+        self.select_review_commit();
+    }
+
+    fn refresh_current_snapshot_from_git(&mut self) {
+        let previous_selected_line = self.selected_line_index;
+        self.refresh_repo_status();
+
+        match self.repo().and_then(|repo| repo.get_diff_snapshot()) {
+            Ok(mut snapshot) => {
+                if !snapshot.files.is_empty() {
+                    self.clean_reason = None;
+                }
+                self.record_history(&snapshot);
+                self.apply_hunk_splits(&mut snapshot);
+                self.annotate_staged_lines(&mut snapshot);
+
+                if self.snapshots.is_empty() {
+                    self.snapshots.push(snapshot);
+                    self.current_snapshot_index = 0;
+                } else {
+                    self.snapshots[self.current_snapshot_index] = snapshot;
+                }
+
+                // Clamp indices after snapshot replacement
+                if let Some(current_snapshot) = self.snapshots.get(self.current_snapshot_index) {
+                    if current_snapshot.files.is_empty() {
+                        self.current_file_index = 0;
+                        self.current_hunk_index = 0;
+                        self.selected_line_index = 0;
+                        return;
+                    }
+
+                    if self.current_file_index >= current_snapshot.files.len() {
+                        self.current_file_index = current_snapshot.files.len().saturating_sub(1);
+                    }
+
+                    if let Some(file) = current_snapshot.files.get(self.current_file_index) {
+                        if file.hunks.is_empty() {
+                            self.current_hunk_index = 0;
+                            self.selected_line_index = 0;
+                            return;
+                        }
+
+                        if self.current_hunk_index >= file.hunks.len() {
+                            self.current_hunk_index = file.hunks.len().saturating_sub(1);
+                        }
+
+                        if self.line_selection_mode {
+                            self.select_nearest_change_line(previous_selected_line);
+                        } else {
+                            self.selected_line_index = 0;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                debug_log(format!(
+                    "Failed to refresh snapshot after staging action: {}",
+                    e
+                ));
+            }
+        }
+    }
+
+    fn select_nearest_change_line(&mut self, preferred_index: usize) {
+        if let Some(snapshot) = self.current_snapshot() {
+            if let Some(file) = snapshot.files.get(self.current_file_index) {
+                if let Some(hunk) = file.hunks.get(self.current_hunk_index) {
+                    let change_indices: Vec<usize> = hunk
+                        .lines
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(idx, line)| {
+                            ((line.starts_with('+') && !line.starts_with("+++"))
+                                || (line.starts_with('-') && !line.starts_with("---")))
+                            .then_some(idx)
+                        })
+                        .collect();
+
+                    if change_indices.is_empty() {
+                        self.selected_line_index = 0;
+                        return;
+                    }
+
+                    let mut best_idx = change_indices[0];
+                    let mut best_dist = usize::abs_diff(best_idx, preferred_index);
+
+                    for &idx in change_indices.iter().skip(1) {
+                        let dist = usize::abs_diff(idx, preferred_index);
+                        if dist < best_dist || (dist == best_dist && idx < best_idx) {
+                            best_idx = idx;
+                            best_dist = dist;
+                        }
+                    }
+
+                    self.selected_line_index = best_idx;
+                    return;
+                }
+            }
+        }
+
+        self.selected_line_index = 0;
+    }
+
+    /// Get the active snapshot for navigation — works in both normal and review modes.
+    fn active_snapshot(&self) -> Option<&DiffSnapshot> {
+        if let Some((delta, _)) = &self.delta_view {
+            return Some(delta);
+        }
+        if let Some(cursor) = self.timeline_cursor {
+            return self.history.get(cursor);
+        }
+        if self.mode == Mode::Review {
+            self.review_snapshot.as_ref()
+        } else {
+            self.snapshots.get(self.current_snapshot_index)
+        }
+    }
+
+    fn enter_review_mode(&mut self) {
+        match self.repo().and_then(|repo| repo.get_recent_commits(20)) {
+            Ok(commits) => {
+                if commits.is_empty() {
+                    debug_log("No commits found for review".to_string());
+                    let branch = self.repo_status.branch.as_deref().unwrap_or("HEAD");
+                    self.set_status(format!("No commits to review on {} yet", branch), false);
+                    return;
+                }
+                self.review_commits = commits;
+                self.review_commit_cursor = 0;
+                self.review_selecting_commit = true;
+                self.mode = Mode::Review;
+                debug_log("Entered review mode, showing commit picker".to_string());
+            }
+            Err(e) => {
+                debug_log(format!("Failed to get commits for review: {}", e));
+                self.set_status(format!("Failed to list commits: {}", e), true);
+            }
+        }
+    }
+
+    fn select_review_commit(&mut self) {
+        if self.review_commit_cursor >= self.review_commits.len() {
+            return;
+        }
+        let sha = self.review_commits[self.review_commit_cursor].sha.clone();
+        debug_log(format!(
+            "Loading commit diff for {}",
+            &sha[..7.min(sha.len())]
+        ));
+
+        match self.repo().and_then(|repo| repo.get_commit_diff(&sha)) {
+            Ok(snapshot) => {
+                self.review_snapshot = Some(snapshot);
+                self.review_selecting_commit = false;
                 self.current_file_index = 0;
                 self.current_hunk_index = 0;
                 self.scroll_offset = 0;
@@ -1276,10 +2728,613 @@ impl App {
                 debug_log("Loaded commit diff for review".to_string());
             }
             Err(e) => {
+                // Stay in the picker so another commit can be chosen
                 debug_log(format!("Failed to load commit diff: {}", e));
-                self.review_selecting_commit = false;
-                self.review_commits.clear();
-                self.mode = Mode::View;
+                self.set_status(e.to_string(), true);
+                self.review_selecting_commit = true;
+            }
+        }
+    }
+
+    /// Whether the diff on screen is history (a reviewed commit, a stash or an earlier
+    /// working tree state) rather than the working tree, so staging actions do not apply
+    fn read_only_view(&self) -> bool {
+        self.mode == Mode::Review
+            || self.stash_preview.is_some()
+            || self.timeline_cursor.is_some()
+            || self.delta_view.is_some()
+    }
+
+    /// Add a snapshot to the history, keeping the timeline on the state it shows
+    fn record_history(&mut self, snapshot: &DiffSnapshot) {
+        if self.archive.is_some() {
+            return;
+        }
+        let dropped = self.history.record(snapshot);
+        if let Some(cursor) = self.timeline_cursor.as_mut() {
+            *cursor = cursor.saturating_sub(dropped);
+        }
+        if let Some(mark) = self.timeline_mark.as_mut() {
+            *mark = mark.saturating_sub(dropped);
+        }
+        self.delta_checkpoint = self.delta_checkpoint.saturating_sub(dropped);
+    }
+
+    /// Show the latest working tree state in the history, read-only, to step back from
+    fn open_timeline(&mut self) {
+        if self.history.is_empty() {
+            self.set_status("No snapshot history yet".to_string(), false);
+            return;
+        }
+        self.timeline_return = (self.current_file_index, self.current_hunk_index);
+        self.timeline_cursor = Some(self.history.len() - 1);
+        self.current_file_index = 0;
+        self.current_hunk_index = 0;
+        self.scroll_offset = 0;
+        self.line_selection_mode = false;
+        self.selection_anchor = None;
+        self.focus = FocusPane::HunkView;
+        debug_log(format!(
+            "Opened timeline with {} snapshots",
+            self.history.len()
+        ));
+    }
+
+    /// Move the timeline by `delta` snapshots, staying on the same file when it still has
+    /// changes at the new point
+    fn step_timeline(&mut self, delta: isize) {
+        let (Some(cursor), None) = (self.timeline_cursor, &self.delta_view) else {
+            return;
+        };
+        let last = self.history.len().saturating_sub(1);
+        let next = cursor.saturating_add_signed(delta).min(last);
+        if next == cursor {
+            return;
+        }
+        let path = self.current_file().map(|file| file.path.clone());
+        self.timeline_cursor = Some(next);
+        let index = self.history.get(next).and_then(|snapshot| {
+            snapshot
+                .files
+                .iter()
+                .position(|file| Some(&file.path) == path.as_ref())
+        });
+        if index != Some(self.current_file_index) {
+            self.current_file_index = index.unwrap_or(0);
+            self.current_hunk_index = 0;
+        } else {
+            let hunks = self
+                .current_file()
+                .map(|file| file.hunks.len())
+                .unwrap_or(0);
+            self.current_hunk_index = self.current_hunk_index.min(hunks.saturating_sub(1));
+        }
+        self.scroll_offset = 0;
+        self.hunk_line_memory.clear();
+    }
+
+    fn toggle_timeline_mark(&mut self) {
+        if self.delta_view.is_some() {
+            return;
+        }
+        self.timeline_mark = match self.timeline_mark {
+            Some(mark) if Some(mark) == self.timeline_cursor => None,
+            _ => self.timeline_cursor,
+        };
+    }
+
+    /// Show what changed in the working tree between two points in the history. In the
+    /// timeline that is from the marked point, or the one before, to the selected one;
+    /// otherwise it is everything since the last time this was asked.
+    fn open_delta_view(&mut self) {
+        let latest = self.history.len().saturating_sub(1);
+        let (from, to) = match self.timeline_cursor {
+            Some(cursor) => match self.timeline_mark {
+                Some(mark) if mark != cursor => (mark.min(cursor), mark.max(cursor)),
+                _ if cursor > 0 => (cursor - 1, cursor),
+                _ => {
+                    self.set_status("Nothing before this snapshot".to_string(), false);
+                    return;
+                }
+            },
+            None => {
+                let from = self.delta_checkpoint.min(latest);
+                if from == latest {
+                    self.set_status("No changes since you last looked".to_string(), false);
+                    return;
+                }
+                self.delta_checkpoint = latest;
+                (from, latest)
+            }
+        };
+        let (Some(old), Some(new)) = (self.history.get(from), self.history.get(to)) else {
+            return;
+        };
+
+        match self.repo().and_then(|repo| repo.diff_snapshots(old, new)) {
+            Ok(delta) => {
+                let label = match self.timeline_cursor {
+                    Some(_) => format!("snapshot {} → {}", from + 1, to + 1),
+                    None => "changes since you last looked".to_string(),
+                };
+                debug_log(format!(
+                    "Comparing snapshots {} and {}: {} files changed",
+                    from,
+                    to,
+                    delta.files.len()
+                ));
+                self.delta_view = Some((delta, label));
+                self.current_file_index = 0;
+                self.current_hunk_index = 0;
+                self.scroll_offset = 0;
+                self.line_selection_mode = false;
+                self.selection_anchor = None;
+                self.hunk_line_memory.clear();
+            }
+            Err(e) => {
+                debug_log(format!("Failed to compare snapshots: {}", e));
+                self.set_status(format!("Failed to compare snapshots: {}", e), true);
+            }
+        }
+    }
+
+    /// Show snapshots loaded from `path` in the timeline instead of the watched history
+    pub fn open_archive(&mut self, path: &Path, snapshots: Vec<DiffSnapshot>) {
+        self.history = SnapshotHistory::with_limit(snapshots.len().max(HISTORY_LIMIT));
+        for snapshot in &snapshots {
+            self.history.record(snapshot);
+        }
+        self.archive = Some(
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+        );
+        self.timeline_mark = None;
+        self.delta_view = None;
+        self.open_timeline();
+        debug_log(format!(
+            "Opened {} snapshots from {}",
+            self.history.len(),
+            path.display()
+        ));
+    }
+
+    /// Save the history as JSON and the diff on screen as a patch, under the git directory
+    fn export_snapshots(&mut self) {
+        let dir = match self.repo() {
+            Ok(git_repo) => git_repo.git_dir().join("hunky"),
+            Err(e) => {
+                self.set_status(format!("Failed to save snapshots: {}", e), true);
+                return;
+            }
+        };
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let json_path = dir.join(format!("snapshots-{}.json", stamp));
+        let patch_path = dir.join(format!("snapshot-{}.patch", stamp));
+        let history: Vec<&DiffSnapshot> = self.history.iter().collect();
+
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| archive::save(&json_path, &history))
+            .and_then(|_| match self.active_snapshot() {
+                Some(snapshot) => archive::save(&patch_path, &[snapshot]).map(|_| true),
+                None => Ok(false),
+            });
+        match result {
+            Ok(true) => self.set_status(
+                format!(
+                    "Saved history to {} and this diff to {}",
+                    json_path.display(),
+                    patch_path.display()
+                ),
+                false,
+            ),
+            Ok(false) => {
+                self.set_status(format!("Saved history to {}", json_path.display()), false)
+            }
+            Err(e) => {
+                debug_log(format!("Failed to save snapshots: {}", e));
+                self.set_status(format!("Failed to save snapshots: {}", e), true);
+            }
+        }
+    }
+
+    fn close_delta_view(&mut self) {
+        self.delta_view = None;
+        self.current_file_index = 0;
+        self.current_hunk_index = 0;
+        self.scroll_offset = 0;
+        self.hunk_line_memory.clear();
+    }
+
+    fn close_timeline(&mut self) {
+        // Opened snapshots have no live working tree to go back to
+        if self.archive.is_some() {
+            return;
+        }
+        self.timeline_cursor = None;
+        self.timeline_mark = None;
+        self.delta_view = None;
+        (self.current_file_index, self.current_hunk_index) = self.timeline_return;
+        let files = self.active_snapshot().map(|s| s.files.len()).unwrap_or(0);
+        if self.current_file_index >= files {
+            self.current_file_index = 0;
+            self.current_hunk_index = 0;
+        }
+        let hunks = self
+            .current_file()
+            .map(|file| file.hunks.len())
+            .unwrap_or(0);
+        self.current_hunk_index = self.current_hunk_index.min(hunks.saturating_sub(1));
+        self.scroll_offset = 0;
+        self.hunk_line_memory.clear();
+        debug_log("Closed timeline".to_string());
+    }
+
+    fn open_stash_panel(&mut self) {
+        match self.repo().and_then(|repo| repo.list_stashes()) {
+            Ok(stashes) if stashes.is_empty() => {
+                self.set_status("No stash entries".to_string(), false);
+            }
+            Ok(stashes) => {
+                self.stashes = stashes;
+                self.stash_cursor = self.stash_cursor.min(self.stashes.len() - 1);
+                self.stash_panel_open = true;
+                debug_log(format!(
+                    "Opened stash panel with {} entries",
+                    self.stashes.len()
+                ));
+            }
+            Err(e) => {
+                debug_log(format!("Failed to list stashes: {}", e));
+                self.set_status(format!("Failed to list stashes: {}", e), true);
+            }
+        }
+    }
+
+    /// Show the selected stash in the diff view, read-only
+    fn preview_selected_stash(&mut self) {
+        let Some(stash) = self.stashes.get(self.stash_cursor) else {
+            return;
+        };
+
+        match self.repo().and_then(|repo| repo.get_stash_diff(&stash.sha)) {
+            Ok(snapshot) => {
+                self.stash_preview = Some(snapshot);
+                self.stash_panel_open = false;
+                self.current_file_index = 0;
+                self.current_hunk_index = 0;
+                self.scroll_offset = 0;
+                self.line_selection_mode = false;
+                self.selection_anchor = None;
+                self.focus = FocusPane::HunkView;
+            }
+            Err(e) => {
+                debug_log(format!("Failed to load stash diff: {}", e));
+                self.set_status(format!("Failed to load stash: {}", e), true);
+            }
+        }
+    }
+
+    fn close_stash_preview(&mut self) {
+        self.stash_preview = None;
+        self.current_file_index = 0;
+        self.current_hunk_index = 0;
+        self.scroll_offset = 0;
+        self.line_selection_mode = false;
+        self.stash_panel_open = true;
+    }
+
+    /// Apply (or pop) the selected stash onto the working tree
+    fn apply_selected_stash(&mut self, pop: bool) {
+        let Some(stash) = self.stashes.get(self.stash_cursor) else {
+            return;
+        };
+        let index = stash.index;
+
+        let result = if pop {
+            self.repo().and_then(|repo| repo.pop_stash(index))
+        } else {
+            self.repo().and_then(|repo| repo.apply_stash(index))
+        };
+        match result {
+            Ok(()) => {
+                let verb = if pop { "Popped" } else { "Applied" };
+                self.set_status(format!("{} stash@{{{}}}", verb, index), false);
+                self.refresh_current_snapshot_from_git();
+                if pop {
+                    self.reload_stash_panel();
+                }
+            }
+            Err(e) => {
+                debug_log(format!("Failed to apply stash: {:#}", e));
+                self.set_status(format!("{:#}", e), true);
+            }
+        }
+    }
+
+    /// Drop the selected stash after a second `d` press
+    fn drop_selected_stash(&mut self, pending: Option<PendingConfirmation>) {
+        let Some(stash) = self.stashes.get(self.stash_cursor) else {
+            return;
+        };
+        let confirmation = PendingConfirmation::DropStash {
+            sha: stash.sha.clone(),
+        };
+        let index = stash.index;
+
+        if pending.as_ref() != Some(&confirmation) {
+            self.pending_confirmation = Some(confirmation);
+            self.set_status(format!("Press d again to drop stash@{{{}}}", index), false);
+            return;
+        }
+
+        match self.repo().and_then(|repo| repo.drop_stash(index)) {
+            Ok(()) => {
+                self.set_status(format!("Dropped stash@{{{}}}", index), false);
+                self.reload_stash_panel();
+            }
+            Err(e) => {
+                debug_log(format!("Failed to drop stash: {:#}", e));
+                self.set_status(format!("{:#}", e), true);
+            }
+        }
+    }
+
+    /// Re-read the stash list after it changed, closing the panel once it is empty
+    fn reload_stash_panel(&mut self) {
+        self.stashes = self
+            .repo()
+            .and_then(|repo| repo.list_stashes())
+            .unwrap_or_default();
+        if self.stashes.is_empty() {
+            self.stash_panel_open = false;
+            self.stash_cursor = 0;
+        } else {
+            self.stash_cursor = self.stash_cursor.min(self.stashes.len() - 1);
+        }
+    }
+
+    /// Mark or unmark the current hunk for "stash selected hunks"
+    fn toggle_stash_mark(&mut self) {
+        let Some(hunk) = self
+            .current_file()
+            .and_then(|file| file.hunks.get(self.current_hunk_index))
+        else {
+            return;
+        };
+        let id = hunk.id.clone();
+        if !self.stash_marks.remove(&id) {
+            self.stash_marks.insert(id);
+        }
+    }
+
+    pub fn is_marked_for_stash(&self, hunk: &Hunk) -> bool {
+        self.stash_marks.contains(&hunk.id)
+    }
+
+    /// Stash the marked hunks, or the current hunk when nothing is marked
+    fn stash_marked_hunks(&mut self) {
+        let Some(snapshot) = self.snapshots.get(self.current_snapshot_index) else {
+            return;
+        };
+
+        let mut selected: Vec<(std::path::PathBuf, Hunk)> = snapshot
+            .files
+            .iter()
+            .flat_map(|file| {
+                file.hunks
+                    .iter()
+                    .filter(|hunk| self.stash_marks.contains(&hunk.id))
+                    .map(|hunk| (file.path.clone(), hunk.clone()))
+            })
+            .collect();
+        if selected.is_empty() {
+            if let Some(file) = self.current_file() {
+                if let Some(hunk) = file.hunks.get(self.current_hunk_index) {
+                    selected.push((file.path.clone(), hunk.clone()));
+                }
+            }
+        }
+        if selected.is_empty() {
+            return;
+        }
+
+        let count = selected.len();
+        let message = format!(
+            "{} hunk{} from hunky",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        match self
+            .repo()
+            .and_then(|repo| repo.stash_hunks(&selected, &message))
+        {
+            Ok(_) => {
+                self.stash_marks.clear();
+                self.set_status(format!("Stashed {}", message), false);
+            }
+            Err(e) => {
+                debug_log(format!("Failed to stash hunks: {}", e));
+                self.set_status(format!("Stash failed: {}", e), true);
+            }
+        }
+        self.refresh_current_snapshot_from_git();
+    }
+
+    pub fn stash_panel_open(&self) -> bool {
+        self.stash_panel_open
+    }
+
+    pub fn activity(&self) -> &ActivityStats {
+        &self.activity
+    }
+
+    pub fn activity_panel_open(&self) -> bool {
+        self.activity_panel_open
+    }
+
+    pub fn activity_cursor(&self) -> usize {
+        self.activity_cursor
+    }
+
+    pub fn history(&self) -> &SnapshotHistory {
+        &self.history
+    }
+
+    /// Name of the saved snapshots file being viewed, if hunky was started with --open
+    pub fn archive_label(&self) -> Option<&str> {
+        self.archive.as_deref()
+    }
+
+    /// Position in the history shown while the timeline is open
+    pub fn timeline_cursor(&self) -> Option<usize> {
+        self.timeline_cursor
+    }
+
+    pub fn timeline_mark(&self) -> Option<usize> {
+        self.timeline_mark
+    }
+
+    /// Label of the comparison between snapshots on screen, if any
+    pub fn delta_label(&self) -> Option<&str> {
+        self.delta_view.as_ref().map(|(_, label)| label.as_str())
+    }
+
+    pub fn stashes(&self) -> &[StashInfo] {
+        &self.stashes
+    }
+
+    pub fn stash_cursor(&self) -> usize {
+        self.stash_cursor
+    }
+
+    /// Label of the stash shown in the diff view, if a stash is being previewed
+    pub fn stash_preview_label(&self) -> Option<String> {
+        self.stash_preview.as_ref()?;
+        self.stashes
+            .get(self.stash_cursor)
+            .map(|stash| format!("stash@{{{}}}", stash.index))
+    }
+
+    /// The commit whose diff is being reviewed, once one was picked
+    fn reviewed_commit(&self) -> Option<&CommitInfo> {
+        self.review_snapshot.as_ref()?;
+        self.review_commits.get(self.review_commit_cursor)
+    }
+
+    /// Reload the commit list after history changed, staying on the reviewed commit or
+    /// following HEAD when the reviewed commit was rewritten
+    fn reload_review_commits(&mut self, reviewed_sha: &str) {
+        // HEAD moved under our own hand; keep the watcher from announcing it
+        self.refresh_repo_status();
+        let count = self.review_commits.len().max(20) + 1;
+        let commits = match self.repo().and_then(|repo| repo.get_recent_commits(count)) {
+            Ok(commits) => commits,
+            Err(e) => {
+                debug_log(format!("Failed to reload commits for review: {}", e));
+                return;
+            }
+        };
+
+        let cursor = commits.iter().position(|commit| commit.sha == reviewed_sha);
+        self.review_commits = commits;
+        match cursor {
+            Some(idx) => self.review_commit_cursor = idx,
+            None => {
+                self.review_commit_cursor = 0;
+                self.select_review_commit();
+            }
+        }
+    }
+
+    /// Commit the staged changes as a `fixup!` of the reviewed commit
+    fn create_fixup_for_reviewed_commit(&mut self) {
+        let Some(target) = self.reviewed_commit().cloned() else {
+            return;
+        };
+
+        match self
+            .repo()
+            .and_then(|repo| repo.create_fixup_commit(&target.sha))
+        {
+            Ok(oid) => {
+                let short_sha: String = oid.to_string().chars().take(7).collect();
+                self.set_status(
+                    format!(
+                        "Created {} fixup! {} (Z to autosquash)",
+                        short_sha, target.summary
+                    ),
+                    false,
+                );
+                self.reload_review_commits(&target.sha);
+            }
+            Err(e) => {
+                debug_log(format!("Failed to create fixup commit: {}", e));
+                self.set_status(format!("Fixup failed: {}", e), true);
+            }
+        }
+    }
+
+    /// Fold the staged changes into HEAD
+    fn amend_head_from_review(&mut self) {
+        let Some(reviewed_sha) = self.reviewed_commit().map(|commit| commit.sha.clone()) else {
+            return;
+        };
+
+        match self.repo().and_then(|repo| repo.amend_head_with_index()) {
+            Ok(oid) => {
+                let short_sha: String = oid.to_string().chars().take(7).collect();
+                self.set_status(format!("Amended HEAD as {}", short_sha), false);
+                self.reload_review_commits(&reviewed_sha);
+            }
+            Err(e) => {
+                debug_log(format!("Failed to amend HEAD: {}", e));
+                self.set_status(format!("Amend failed: {}", e), true);
+            }
+        }
+    }
+
+    /// Autosquash fixups into the reviewed commit. The first press only asks for
+    /// confirmation because the rebase rewrites every commit after it.
+    fn autosquash_reviewed_commit(&mut self, pending: Option<PendingConfirmation>) {
+        let Some(target) = self.reviewed_commit().cloned() else {
+            return;
+        };
+        let confirmation = PendingConfirmation::Autosquash {
+            target_sha: target.sha.clone(),
+        };
+
+        if pending.as_ref() != Some(&confirmation) {
+            self.pending_confirmation = Some(confirmation);
+            self.set_status(
+                format!(
+                    "Press Z again to autosquash fixups into {} and rewrite the commits after it",
+                    target.short_sha
+                ),
+                false,
+            );
+            return;
+        }
+
+        match self
+            .repo()
+            .and_then(|repo| repo.autosquash_onto(&target.sha))
+        {
+            Ok(()) => {
+                self.exit_review_mode();
+                self.set_status(
+                    format!("Autosquashed fixups into {}", target.summary),
+                    false,
+                );
+            }
+            Err(e) => {
+                debug_log(format!("Autosquash failed: {}", e));
+                self.set_status(e.to_string(), true);
             }
         }
     }
@@ -1296,6 +3351,8 @@ impl App {
         self.focus = FocusPane::HunkView;
         self.show_help = false;
         self.help_scroll_offset = 0;
+        // Live snapshots are ignored while reviewing; catch up with the worktree
+        self.refresh_current_snapshot_from_git();
         debug_log("Exited review mode".to_string());
     }
 
@@ -1315,6 +3372,15 @@ impl App {
     }
 
     pub fn current_snapshot(&self) -> Option<&DiffSnapshot> {
+        if let Some(preview) = &self.stash_preview {
+            return Some(preview);
+        }
+        if let Some((delta, _)) = &self.delta_view {
+            return Some(delta);
+        }
+        if let Some(cursor) = self.timeline_cursor {
+            return self.history.get(cursor);
+        }
         if self.mode == Mode::Review {
             self.review_snapshot.as_ref()
         } else {
@@ -1390,6 +3456,18 @@ impl App {
         &self.review_commits
     }
 
+    pub fn commit_composer(&self) -> Option<&CommitComposer> {
+        self.commit_composer.as_ref()
+    }
+
+    pub fn repo_status(&self) -> &RepoStatus {
+        &self.repo_status
+    }
+
+    pub fn status_message(&self) -> Option<&StatusMessage> {
+        self.status_message.as_ref()
+    }
+
     pub fn review_commit_cursor(&self) -> usize {
         self.review_commit_cursor
     }
@@ -1429,7 +3507,7 @@ impl App {
 
     /// Get the height (line count) of the help sidebar content
     pub fn help_content_height(&self) -> usize {
-        32 // Number of help lines in draw_help_sidebar
+        crate::ui::help_lines().len()
     }
 
     /// Clamp scroll offset to valid range based on content and viewport height
@@ -1456,7 +3534,7 @@ impl App {
 
     /// Get the height (line count) of the extended help content
     pub fn extended_help_content_height(&self) -> usize {
-        108 // Exact number of lines in draw_extended_help
+        crate::ui::extended_help_lines().len()
     }
 
     /// Clamp extended help scroll offset to valid range based on content and viewport height
@@ -0,0 +1,325 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::diff::{patch_file_header, DiffSnapshot, FileChange, Hunk};
+
+/// Bumped when the JSON layout changes in a way older versions can't read
+const FORMAT_VERSION: u32 = 1;
+
+/// The JSON model of saved snapshots. Only what a snapshot recorded is kept, not what was
+/// seen, staged or split while viewing it.
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedSnapshots {
+    version: u32,
+    snapshots: Vec<SavedSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedSnapshot {
+    /// Milliseconds since the Unix epoch
+    timestamp: u64,
+    head: Option<String>,
+    #[serde(default)]
+    untracked_overflow: usize,
+    files: Vec<SavedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedFile {
+    path: PathBuf,
+    status: String,
+    hunks: Vec<SavedHunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedHunk {
+    old_start: usize,
+    new_start: usize,
+    lines: Vec<String>,
+}
+
+impl From<&DiffSnapshot> for SavedSnapshot {
+    fn from(snapshot: &DiffSnapshot) -> Self {
+        Self {
+            timestamp: snapshot
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            head: snapshot.head.clone(),
+            untracked_overflow: snapshot.untracked_overflow,
+            files: snapshot
+                .files
+                .iter()
+                .map(|file| SavedFile {
+                    path: file.path.clone(),
+                    status: file.status.clone(),
+                    hunks: file
+                        .hunks
+                        .iter()
+                        .map(|hunk| SavedHunk {
+                            old_start: hunk.old_start,
+                            new_start: hunk.new_start,
+                            lines: hunk.lines.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<SavedSnapshot> for DiffSnapshot {
+    fn from(saved: SavedSnapshot) -> Self {
+        Self {
+            timestamp: UNIX_EPOCH + Duration::from_millis(saved.timestamp),
+            head: saved.head,
+            untracked_overflow: saved.untracked_overflow,
+            files: saved
+                .files
+                .into_iter()
+                .map(|file| {
+                    let hunks = file
+                        .hunks
+                        .into_iter()
+                        .map(|hunk| {
+                            Hunk::new(hunk.old_start, hunk.new_start, hunk.lines, &file.path)
+                        })
+                        .collect();
+                    FileChange {
+                        path: file.path,
+                        status: file.status,
+                        hunks,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn to_json(snapshots: &[&DiffSnapshot]) -> Result<String> {
+    let saved = SavedSnapshots {
+        version: FORMAT_VERSION,
+        snapshots: snapshots
+            .iter()
+            .map(|snapshot| (*snapshot).into())
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&saved)?)
+}
+
+pub fn from_json(text: &str) -> Result<Vec<DiffSnapshot>> {
+    let saved: SavedSnapshots = serde_json::from_str(text).context("Not a saved snapshot file")?;
+    if saved.version > FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "Saved with a newer format (version {}); this hunky reads version {}",
+            saved.version,
+            FORMAT_VERSION
+        ));
+    }
+    Ok(saved.snapshots.into_iter().map(Into::into).collect())
+}
+
+/// A multi-file unified patch of the snapshot, as `git diff HEAD` would write it. Files
+/// without hunks, like binary ones, are left out.
+pub fn to_patch(snapshot: &DiffSnapshot) -> String {
+    let mut patch = String::new();
+    for file in snapshot.files.iter().filter(|file| !file.hunks.is_empty()) {
+        let name = file.path.to_string_lossy().replace('\\', "/");
+        patch.push_str(&format!("diff --git a/{} b/{}\n", name, name));
+        patch.push_str(&patch_file_header(&file.path, &file.status));
+        for hunk in &file.hunks {
+            let body: Vec<&String> = hunk
+                .lines
+                .iter()
+                .filter(|line| line.starts_with([' ', '+', '-']))
+                .collect();
+            let old_count = body.iter().filter(|line| !line.starts_with('+')).count();
+            let new_count = body.iter().filter(|line| !line.starts_with('-')).count();
+            patch.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                hunk.old_start, old_count, hunk.new_start, new_count
+            ));
+            for line in body {
+                patch.push_str(line);
+                if !line.ends_with('\n') {
+                    patch.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    patch
+}
+
+/// Read a unified patch back into a snapshot. Anything outside file headers and hunks,
+/// like `diff --git` and `index` lines, is skipped.
+pub fn from_patch(text: &str) -> Result<DiffSnapshot> {
+    let mut files: Vec<FileChange> = Vec::new();
+    let mut old_path: Option<String> = None;
+    // Lines of the current hunk still to come on each side
+    let mut remaining = (0usize, 0usize);
+
+    for (number, line) in text.split_inclusive('\n').enumerate() {
+        // Editors and mail clients often strip the space from empty context lines
+        let line = if line == "\n" && remaining != (0, 0) {
+            " \n"
+        } else {
+            line
+        };
+        if remaining != (0, 0) || line.starts_with('\\') {
+            let Some(hunk) = files.last_mut().and_then(|file| file.hunks.last_mut()) else {
+                return Err(anyhow::anyhow!(
+                    "Line {}: hunk line outside a hunk",
+                    number + 1
+                ));
+            };
+            match line.chars().next() {
+                Some(' ') => {
+                    remaining.0 = remaining.0.saturating_sub(1);
+                    remaining.1 = remaining.1.saturating_sub(1);
+                }
+                Some('-') => remaining.0 = remaining.0.saturating_sub(1),
+                Some('+') => remaining.1 = remaining.1.saturating_sub(1),
+                Some('\\') => {
+                    // The line before has no newline; keep libgit2's marker after it,
+                    // which names the side that lacks one
+                    let Some(last) = hunk.lines.last_mut() else {
+                        continue;
+                    };
+                    let marker = match last.chars().next() {
+                        Some('-') => '>',
+                        Some('+') => '<',
+                        _ => '=',
+                    };
+                    if last.ends_with('\n') {
+                        last.pop();
+                    }
+                    hunk.lines
+                        .push(format!("{}\n\\ No newline at end of file\n", marker));
+                    continue;
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Line {}: hunk ends before its header said it would",
+                        number + 1
+                    ))
+                }
+            }
+            hunk.lines.push(line.to_string());
+            continue;
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        if let Some(path) = trimmed.strip_prefix("--- ") {
+            old_path = Some(path.to_string());
+        } else if let Some(new_path) = trimmed.strip_prefix("+++ ") {
+            let old = old_path.take().unwrap_or_default();
+            let (status, path) = match (old.as_str(), new_path) {
+                ("/dev/null", new) => ("Added", new.strip_prefix("b/").unwrap_or(new)),
+                (old, "/dev/null") => ("Deleted", old.strip_prefix("a/").unwrap_or(old)),
+                (_, new) => ("Modified", new.strip_prefix("b/").unwrap_or(new)),
+            };
+            files.push(FileChange {
+                path: PathBuf::from(path),
+                status: status.to_string(),
+                hunks: Vec::new(),
+            });
+        } else if let Some(header) = trimmed.strip_prefix("@@ -") {
+            let Some(file) = files.last_mut() else {
+                return Err(anyhow::anyhow!(
+                    "Line {}: hunk before a file header",
+                    number + 1
+                ));
+            };
+            let (old_start, old_count, new_start, new_count) = parse_hunk_header(header)
+                .with_context(|| format!("Line {}: bad hunk header", number + 1))?;
+            remaining = (old_count, new_count);
+            file.hunks
+                .push(Hunk::new(old_start, new_start, Vec::new(), &file.path));
+        }
+    }
+
+    if remaining != (0, 0) {
+        return Err(anyhow::anyhow!("Patch ends in the middle of a hunk"));
+    }
+    // An empty patch is a clean working tree, but text without any file is no patch
+    if files.is_empty() && !text.trim().is_empty() {
+        return Err(anyhow::anyhow!("Neither a patch nor saved snapshots"));
+    }
+    // Hunk ids depend on the lines, which were only known once each hunk was read
+    for file in &mut files {
+        for hunk in &mut file.hunks {
+            *hunk = Hunk::new(
+                hunk.old_start,
+                hunk.new_start,
+                std::mem::take(&mut hunk.lines),
+                &file.path,
+            );
+        }
+    }
+    Ok(DiffSnapshot {
+        timestamp: SystemTime::now(),
+        head: None,
+        files,
+        untracked_overflow: 0,
+    })
+}
+
+/// `a[,b] +c[,d] @@ ...` after the leading `@@ -`
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize, usize)> {
+    let (ranges, _) = header.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old)?;
+    let (new_start, new_count) = range(new)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// Whether `path` should be written as JSON rather than as a patch
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Write snapshots to `path`: all of them as JSON for a `.json` file, otherwise the last
+/// one as a patch
+pub fn save(path: &Path, snapshots: &[&DiffSnapshot]) -> Result<()> {
+    let content = if is_json(path) {
+        to_json(snapshots)?
+    } else {
+        let last = snapshots
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("No snapshot to save"))?;
+        to_patch(last)
+    };
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Read snapshots saved as JSON, or a patch as a single snapshot
+pub fn load(path: &Path) -> Result<Vec<DiffSnapshot>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let snapshots = if text.trim_start().starts_with('{') {
+        from_json(&text)?
+    } else {
+        let mut snapshot = from_patch(&text)?;
+        if let Ok(modified) = std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            snapshot.timestamp = modified;
+        }
+        vec![snapshot]
+    };
+    if snapshots.is_empty() {
+        return Err(anyhow::anyhow!("{} holds no snapshots", path.display()));
+    }
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+#[path = "../tests/archive.rs"]
+mod tests;
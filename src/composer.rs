@@ -0,0 +1,247 @@
+use crate::diff::StagedFile;
+
+/// Summary lines longer than this are flagged in the composer
+pub const SUMMARY_SOFT_LIMIT: usize = 72;
+
+/// Built-in message templates, offered after the repository's `commit.template`
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("feat", "feat: "),
+    ("fix", "fix: "),
+    ("docs", "docs: "),
+    ("refactor", "refactor: "),
+    ("test", "test: "),
+    ("chore", "chore: "),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComposerField {
+    Summary,
+    Body,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitTemplate {
+    pub name: String,
+    pub text: String,
+}
+
+/// State of the in-TUI commit panel: the message being written, commit options and the
+/// staged files the commit will contain.
+#[derive(Debug, Clone)]
+pub struct CommitComposer {
+    pub summary: String,
+    pub body: String,
+    pub field: ComposerField,
+    pub amend: bool,
+    pub signoff: bool,
+    pub staged_files: Vec<StagedFile>,
+    templates: Vec<CommitTemplate>,
+    template_index: Option<usize>,
+    head_message: Option<String>,
+    signoff_trailer: Option<String>,
+    // Set after the user was warned about committing an empty index
+    confirm_empty: bool,
+}
+
+impl CommitComposer {
+    pub fn new(
+        staged_files: Vec<StagedFile>,
+        repo_template: Option<String>,
+        head_message: Option<String>,
+        signoff_trailer: Option<String>,
+    ) -> Self {
+        let mut templates: Vec<CommitTemplate> = repo_template
+            .map(|text| CommitTemplate {
+                name: "commit.template".to_string(),
+                text,
+            })
+            .into_iter()
+            .collect();
+        templates.extend(BUILTIN_TEMPLATES.iter().map(|(name, text)| CommitTemplate {
+            name: name.to_string(),
+            text: text.to_string(),
+        }));
+
+        let mut composer = Self {
+            summary: String::new(),
+            body: String::new(),
+            field: ComposerField::Summary,
+            amend: false,
+            signoff: false,
+            staged_files,
+            templates,
+            template_index: None,
+            head_message,
+            signoff_trailer,
+            confirm_empty: false,
+        };
+
+        // Like `git commit`, start from the configured template when there is one
+        if composer.templates[0].name == "commit.template" {
+            composer.apply_template(0);
+        }
+        composer
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        match self.field {
+            ComposerField::Summary => self.summary.push(ch),
+            ComposerField::Body => self.body.push(ch),
+        }
+        self.confirm_empty = false;
+    }
+
+    pub fn backspace(&mut self) {
+        match self.field {
+            ComposerField::Summary => {
+                self.summary.pop();
+            }
+            ComposerField::Body => {
+                self.body.pop();
+            }
+        }
+    }
+
+    /// Enter moves from the summary to the body, and starts a new line in the body
+    pub fn newline(&mut self) {
+        match self.field {
+            ComposerField::Summary => self.field = ComposerField::Body,
+            ComposerField::Body => self.body.push('\n'),
+        }
+    }
+
+    pub fn toggle_field(&mut self) {
+        self.field = match self.field {
+            ComposerField::Summary => ComposerField::Body,
+            ComposerField::Body => ComposerField::Summary,
+        };
+    }
+
+    /// Toggle amending HEAD. An untouched message is swapped for HEAD's message and back.
+    pub fn toggle_amend(&mut self) {
+        let Some(head_message) = self.head_message.clone() else {
+            return;
+        };
+
+        if self.amend {
+            let (summary, body) = split_message(&head_message);
+            if self.message_body() == join_message(&summary, &body) {
+                self.set_message("");
+            }
+        } else if self.is_pristine() {
+            self.set_message(&head_message);
+        }
+        self.amend = !self.amend;
+        self.template_index = None;
+    }
+
+    pub fn toggle_signoff(&mut self) {
+        if self.signoff_trailer.is_some() {
+            self.signoff = !self.signoff;
+        }
+    }
+
+    /// Replace the message with the next template, when the message is still untouched
+    pub fn cycle_template(&mut self) {
+        if !self.is_pristine() {
+            return;
+        }
+        let next = self
+            .template_index
+            .map_or(0, |idx| (idx + 1) % self.templates.len());
+        self.apply_template(next);
+    }
+
+    pub fn template_name(&self) -> Option<&str> {
+        self.template_index
+            .map(|idx| self.templates[idx].name.as_str())
+    }
+
+    pub fn has_signoff_identity(&self) -> bool {
+        self.signoff_trailer.is_some()
+    }
+
+    /// Whether the commit would not record any staged change
+    pub fn index_is_empty(&self) -> bool {
+        self.staged_files.is_empty() && !self.amend
+    }
+
+    /// Ask for confirmation before committing an empty index. Returns true once the commit
+    /// may go ahead.
+    pub fn confirm_commit(&mut self) -> bool {
+        if self.index_is_empty() && !self.confirm_empty {
+            self.confirm_empty = true;
+            return false;
+        }
+        true
+    }
+
+    pub fn awaiting_empty_confirmation(&self) -> bool {
+        self.confirm_empty
+    }
+
+    /// The full commit message, including the sign-off trailer when enabled
+    pub fn message(&self) -> String {
+        let mut message = self.message_body();
+        if self.signoff {
+            if let Some(trailer) = &self.signoff_trailer {
+                if !message.lines().any(|line| line == trailer) {
+                    message.push_str("\n\n");
+                    message.push_str(trailer);
+                }
+            }
+        }
+        message.push('\n');
+        message
+    }
+
+    fn message_body(&self) -> String {
+        join_message(&self.summary, &self.body)
+    }
+
+    /// True when the message is empty or still exactly the selected template
+    fn is_pristine(&self) -> bool {
+        let original = match self.template_index {
+            Some(idx) => {
+                let (summary, body) = split_message(&self.templates[idx].text);
+                join_message(&summary, &body)
+            }
+            None => String::new(),
+        };
+        self.message_body() == original
+    }
+
+    fn apply_template(&mut self, idx: usize) {
+        let text = self.templates[idx].text.clone();
+        self.set_message(&text);
+        self.template_index = Some(idx);
+    }
+
+    fn set_message(&mut self, message: &str) {
+        (self.summary, self.body) = split_message(message);
+        self.field = ComposerField::Summary;
+    }
+}
+
+/// Split a message into its summary line and the body after the blank separator line
+fn split_message(message: &str) -> (String, String) {
+    let (summary, body) = message.split_once('\n').unwrap_or((message, ""));
+    (
+        summary.to_string(),
+        body.trim_start_matches('\n').trim_end().to_string(),
+    )
+}
+
+fn join_message(summary: &str, body: &str) -> String {
+    let summary = summary.trim_end();
+    let body = body.trim();
+    if body.is_empty() {
+        summary.to_string()
+    } else {
+        format!("{}\n\n{}", summary, body)
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/composer.rs"]
+mod tests;
@@ -5,13 +5,93 @@ use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
 pub struct DiffSnapshot {
-    #[allow(dead_code)]
     pub timestamp: SystemTime,
+    /// Commit the working tree was compared against; None before the first commit and for
+    /// commit or stash diffs
+    pub head: Option<String>,
     pub files: Vec<FileChange>,
+    /// Untracked files left out of `files` by the untracked file cap
+    pub untracked_overflow: usize,
+}
+
+impl DiffSnapshot {
+    /// Whether both snapshots hold the same hunks for the same files, regardless of when
+    /// they were taken or what is staged
+    pub fn same_changes(&self, other: &DiffSnapshot) -> bool {
+        self.untracked_overflow == other.untracked_overflow
+            && self.files.len() == other.files.len()
+            && self.files.iter().zip(&other.files).all(|(a, b)| {
+                a.path == b.path
+                    && a.status == b.status
+                    && a.hunks.len() == b.hunks.len()
+                    && a.hunks.iter().zip(&b.hunks).all(|(a, b)| {
+                        a.old_start == b.old_start
+                            && a.new_start == b.new_start
+                            && a.lines == b.lines
+                    })
+            })
+    }
+
+    /// Added and removed lines across every hunk
+    pub fn line_counts(&self) -> (usize, usize) {
+        let mut added = 0;
+        let mut removed = 0;
+        for line in self
+            .files
+            .iter()
+            .flat_map(|file| &file.hunks)
+            .flat_map(|hunk| &hunk.lines)
+        {
+            if line.starts_with('+') && !line.starts_with("+++") {
+                added += 1;
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                removed += 1;
+            }
+        }
+        (added, removed)
+    }
+}
+
+/// How untracked files appear in snapshots
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UntrackedMode {
+    /// Every untracked file, with its content as a hunk
+    #[default]
+    Show,
+    /// Untracked directories as a single entry
+    Collapse,
+    Hide,
+}
+
+impl UntrackedMode {
+    pub fn next(self) -> Self {
+        match self {
+            UntrackedMode::Show => UntrackedMode::Collapse,
+            UntrackedMode::Collapse => UntrackedMode::Hide,
+            UntrackedMode::Hide => UntrackedMode::Show,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UntrackedMode::Show => "show",
+            UntrackedMode::Collapse => "collapse",
+            UntrackedMode::Hide => "hide",
+        }
+    }
+}
+
+/// Where an ignore pattern for an untracked path is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IgnoreTarget {
+    /// The shared `.gitignore` at the top of the working tree
+    GitIgnore,
+    /// The local-only `.git/info/exclude`
+    InfoExclude,
 }
 
 /// Metadata about a git commit for the review mode commit picker
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CommitInfo {
     pub sha: String,
     pub short_sha: String,
@@ -19,6 +99,281 @@ pub struct CommitInfo {
     pub author: String,
 }
 
+/// A multi-step git operation that is waiting to be continued or aborted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepoOperation {
+    Rebase,
+    Merge,
+    CherryPick,
+    Revert,
+    Bisect,
+    ApplyMailbox,
+}
+
+impl RepoOperation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RepoOperation::Rebase => "REBASING",
+            RepoOperation::Merge => "MERGING",
+            RepoOperation::CherryPick => "CHERRY-PICKING",
+            RepoOperation::Revert => "REVERTING",
+            RepoOperation::Bisect => "BISECTING",
+            RepoOperation::ApplyMailbox => "APPLYING PATCHES",
+        }
+    }
+}
+
+/// Where HEAD points, how it relates to its upstream and what operation is in progress
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoStatus {
+    /// Checked-out branch, or the branch being rebased while HEAD is detached by a rebase
+    pub branch: Option<String>,
+    pub head_short_sha: Option<String>,
+    pub detached: bool,
+    /// HEAD names a branch that has no commits yet
+    pub unborn: bool,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub operation: Option<RepoOperation>,
+}
+
+/// The checked-out branch and commit, compared by the watcher to notice HEAD moves
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeadState {
+    pub branch: Option<String>,
+    pub oid: Option<String>,
+}
+
+/// How HEAD moved between two observations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeadMoveKind {
+    /// A new commit on top of the previous HEAD, on the same branch
+    Commit,
+    /// A different branch was checked out, or HEAD was detached
+    Checkout,
+    /// The branch now points somewhere that does not build on the previous HEAD,
+    /// e.g. after a reset, an amend or a rebase
+    Reset,
+}
+
+/// Why the working tree went from having changes to having none
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CleanReason {
+    /// The changes were reverted or discarded; HEAD did not move
+    Cleared,
+    /// The changes went into a new commit
+    Committed,
+    /// HEAD was reset, or another branch or commit was checked out
+    Reset,
+}
+
+impl CleanReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            CleanReason::Cleared => "All changes were reverted",
+            CleanReason::Committed => "All changes were committed",
+            CleanReason::Reset => "HEAD was reset or switched; no changes remain",
+        }
+    }
+}
+
+/// HEAD moved outside hunky; `commit` is the commit HEAD now points at
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadMove {
+    pub kind: HeadMoveKind,
+    pub branch: Option<String>,
+    pub commit: CommitInfo,
+}
+
+/// Hunks turning `old` into `new`, with three lines of context like the working tree diff.
+/// A last line without a newline gets git's "\ No newline at end of file" marker.
+pub fn content_hunks(old: &str, new: &str, path: &Path) -> Vec<Hunk> {
+    use similar::{ChangeTag, TextDiff};
+
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(3)
+        .iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            // An empty range starts at the line before it, like git
+            let start = |range: &std::ops::Range<usize>| {
+                if range.is_empty() {
+                    range.start
+                } else {
+                    range.start + 1
+                }
+            };
+
+            let mut lines = Vec::new();
+            for op in group {
+                for change in diff.iter_changes(op) {
+                    let (origin, marker) = match change.tag() {
+                        ChangeTag::Equal => (' ', '='),
+                        ChangeTag::Delete => ('-', '<'),
+                        ChangeTag::Insert => ('+', '>'),
+                    };
+                    lines.push(format!("{}{}", origin, change.value()));
+                    if change.missing_newline() {
+                        lines.push(format!("{}\n\\ No newline at end of file\n", marker));
+                    }
+                }
+            }
+            Hunk::new(start(&old_range), start(&new_range), lines, path)
+        })
+        .collect()
+}
+
+/// The `---`/`+++` lines of a patch for `path` with the given file status. New files come
+/// from `/dev/null` and deleted files go to it, the way git writes them.
+pub fn patch_file_header(path: &Path, status: &str) -> String {
+    let name = path.to_string_lossy().replace('\\', "/");
+    let old = match status {
+        "Added" | "Untracked" => "/dev/null".to_string(),
+        _ => format!("a/{}", name),
+    };
+    let new = match status {
+        "Deleted" => "/dev/null".to_string(),
+        _ => format!("b/{}", name),
+    };
+    format!("--- {}\n+++ {}\n", old, new)
+}
+
+/// Which side of a conflict to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictChoice {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs
+    Both,
+}
+
+/// One `<<<<<<<` ... `>>>>>>>` block of a conflicted file. `start` and `end` are the 0-based
+/// line indices of the opening and one past the closing marker in the working tree file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictRegion {
+    pub start: usize,
+    pub end: usize,
+    pub ours_label: String,
+    pub theirs_label: String,
+    pub ours: Vec<String>,
+    /// The merge base's lines, when known from diff3 markers or the index
+    pub base: Option<Vec<String>>,
+    pub theirs: Vec<String>,
+}
+
+impl ConflictRegion {
+    /// The lines that replace the whole region for `choice`
+    pub fn resolution(&self, choice: ConflictChoice) -> Vec<String> {
+        match choice {
+            ConflictChoice::Ours => self.ours.clone(),
+            ConflictChoice::Theirs => self.theirs.clone(),
+            ConflictChoice::Both => [self.ours.as_slice(), self.theirs.as_slice()].concat(),
+        }
+    }
+}
+
+/// A path with unmerged entries in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictedFile {
+    pub path: PathBuf,
+    /// Whether each side has the file; a missing side deleted it
+    pub has_base: bool,
+    pub has_ours: bool,
+    pub has_theirs: bool,
+    pub regions: Vec<ConflictRegion>,
+}
+
+/// Find the conflict marker blocks in `content`. Lines keep their terminators. Unterminated
+/// blocks are ignored.
+pub fn parse_conflict_regions(content: &str) -> Vec<ConflictRegion> {
+    fn marker_label<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+        let rest = line.strip_prefix(marker)?;
+        let rest = rest.trim_end_matches(['\n', '\r']);
+        if rest.is_empty() {
+            Some("")
+        } else {
+            rest.strip_prefix(' ')
+        }
+    }
+
+    enum Section {
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut regions = Vec::new();
+    let mut current: Option<(ConflictRegion, Section)> = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let Some((region, section)) = current.as_mut() else {
+            if let Some(label) = marker_label(line, "<<<<<<<") {
+                current = Some((
+                    ConflictRegion {
+                        start: idx,
+                        end: idx,
+                        ours_label: label.to_string(),
+                        theirs_label: String::new(),
+                        ours: Vec::new(),
+                        base: None,
+                        theirs: Vec::new(),
+                    },
+                    Section::Ours,
+                ));
+            }
+            continue;
+        };
+
+        match section {
+            Section::Ours if marker_label(line, "|||||||").is_some() => {
+                region.base = Some(Vec::new());
+                *section = Section::Base;
+            }
+            Section::Ours | Section::Base if marker_label(line, "=======") == Some("") => {
+                *section = Section::Theirs;
+            }
+            Section::Theirs if marker_label(line, ">>>>>>>").is_some() => {
+                region.theirs_label = marker_label(line, ">>>>>>>").unwrap_or("").to_string();
+                region.end = idx + 1;
+                if let Some((region, _)) = current.take() {
+                    regions.push(region);
+                }
+            }
+            Section::Ours => region.ours.push(line.to_string()),
+            Section::Base => region
+                .base
+                .get_or_insert_with(Vec::new)
+                .push(line.to_string()),
+            Section::Theirs => region.theirs.push(line.to_string()),
+        }
+    }
+
+    regions
+}
+
+/// An entry of the stash list, newest first
+#[derive(Debug, Clone)]
+pub struct StashInfo {
+    pub index: usize,
+    pub sha: String,
+    pub message: String,
+    pub time: SystemTime,
+    pub file_count: usize,
+}
+
+/// A file with changes staged in the index, as listed by the commit composer
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedFile {
+    pub path: PathBuf,
+    pub status: String,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct FileChange {
     pub path: PathBuf,
@@ -39,6 +394,8 @@ pub struct Hunk {
     pub accepted: bool,
     #[allow(dead_code)]
     pub id: HunkId,
+    /// For sub-hunks produced by a split: the original hunk and this piece's line offset in it
+    pub split_from: Option<(HunkId, usize)>,
 }
 
 impl Hunk {
@@ -76,7 +433,140 @@ impl Hunk {
             staged_line_indices: HashSet::new(),
             accepted: false,
             id,
+            split_from: None,
+        }
+    }
+
+    fn is_body_line(line: &str) -> bool {
+        line.starts_with(' ')
+            || (line.starts_with('+') && !line.starts_with("+++"))
+            || (line.starts_with('-') && !line.starts_with("---"))
+    }
+
+    fn is_addition(line: &str) -> bool {
+        line.starts_with('+') && !line.starts_with("+++")
+    }
+
+    /// Whether a new sub-hunk may start at `line_index`. Splitting between two added lines is
+    /// refused because the index could not tell which side a staged insertion belongs to.
+    pub fn can_split_at(&self, line_index: usize) -> bool {
+        if line_index == 0 || line_index >= self.lines.len() {
+            return false;
+        }
+        if !Self::is_body_line(&self.lines[line_index]) {
+            return false;
+        }
+
+        let previous_body = self.lines[..line_index]
+            .iter()
+            .rev()
+            .find(|line| Self::is_body_line(line));
+        !(Self::is_addition(&self.lines[line_index])
+            && previous_body.is_some_and(|line| Self::is_addition(line)))
+    }
+
+    /// Split points at context boundaries, like `git add -p`'s `s`: the middle of every
+    /// context run that separates two groups of changes.
+    pub fn context_split_points(&self) -> Vec<usize> {
+        let mut points = Vec::new();
+        let mut seen_change = false;
+        let mut context_run_start: Option<usize> = None;
+
+        for (idx, line) in self.lines.iter().enumerate() {
+            if line.starts_with(' ') {
+                if seen_change && context_run_start.is_none() {
+                    context_run_start = Some(idx);
+                }
+            } else if Self::is_body_line(line) {
+                if let Some(run_start) = context_run_start.take() {
+                    // Give the earlier group the first half of the shared context
+                    points.push(run_start + (idx - run_start).div_ceil(2));
+                }
+                seen_change = true;
+            }
+        }
+
+        points
+    }
+
+    /// Split into consecutive sub-hunks, each starting at one of `points`. Invalid points are
+    /// ignored and pieces without any change lines are dropped.
+    pub fn split_at(&self, points: &[usize], file_path: &Path) -> Vec<Hunk> {
+        let mut starts: Vec<usize> = points
+            .iter()
+            .copied()
+            .filter(|&point| self.can_split_at(point))
+            .collect();
+        starts.sort_unstable();
+        starts.dedup();
+
+        if starts.is_empty() {
+            return vec![self.clone()];
+        }
+
+        let mut pieces = Vec::new();
+        let mut old_lineno = self.old_start;
+        let mut new_lineno = self.new_start;
+        let mut piece_start = 0;
+        let mut piece_old = old_lineno;
+        let mut piece_new = new_lineno;
+        let mut boundaries = starts.into_iter().chain(std::iter::once(self.lines.len()));
+        let mut next_boundary = boundaries.next();
+
+        for idx in 0..=self.lines.len() {
+            if Some(idx) == next_boundary {
+                let lines = self.lines[piece_start..idx].to_vec();
+                if lines.iter().any(|line| Self::is_change_line(line)) {
+                    let old_count = lines
+                        .iter()
+                        .filter(|l| {
+                            l.starts_with(' ') || (l.starts_with('-') && !l.starts_with("---"))
+                        })
+                        .count();
+                    let new_count = lines
+                        .iter()
+                        .filter(|l| l.starts_with(' ') || Self::is_addition(l))
+                        .count();
+                    // Follow the unified diff convention: an empty side starts at the line before
+                    let old_start = if old_count == 0 {
+                        piece_old.saturating_sub(1)
+                    } else {
+                        piece_old
+                    };
+                    let new_start = if new_count == 0 {
+                        piece_new.saturating_sub(1)
+                    } else {
+                        piece_new
+                    };
+
+                    let mut piece = Hunk::new(old_start, new_start, lines, file_path);
+                    piece.split_from = Some((self.id.clone(), piece_start));
+                    pieces.push(piece);
+                }
+                piece_start = idx;
+                piece_old = old_lineno;
+                piece_new = new_lineno;
+                next_boundary = boundaries.next();
+            }
+
+            if let Some(line) = self.lines.get(idx) {
+                if line.starts_with(' ') {
+                    old_lineno += 1;
+                    new_lineno += 1;
+                } else if line.starts_with('-') && !line.starts_with("---") {
+                    old_lineno += 1;
+                } else if Self::is_addition(line) {
+                    new_lineno += 1;
+                }
+            }
         }
+
+        pieces
+    }
+
+    /// Whether `line` is an added or removed line rather than context or a file header
+    pub fn is_change_line(line: &str) -> bool {
+        Self::is_addition(line) || (line.starts_with('-') && !line.starts_with("---"))
     }
 }
 
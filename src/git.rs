@@ -1,506 +1,1700 @@
 use anyhow::{Context, Result};
-use git2::{Delta, DiffOptions, Repository};
+use git2::{Delta, DiffOptions, Repository, RepositoryState};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 
-use crate::diff::{CommitInfo, DiffSnapshot, FileChange, Hunk};
+use crate::diff::{
+    content_hunks, parse_conflict_regions, patch_file_header, CommitInfo, ConflictChoice,
+    ConflictRegion, ConflictedFile, DiffSnapshot, FileChange, HeadMove, HeadMoveKind, HeadState,
+    Hunk, HunkId, IgnoreTarget, RepoOperation, RepoStatus, StagedFile, StashInfo, UntrackedMode,
+};
+
+/// Untracked files listed in a snapshot before the rest are only counted
+pub const UNTRACKED_FILE_LIMIT: usize = 200;
+
+/// Untracked files larger than this are listed without their content
+pub const UNTRACKED_MAX_FILE_SIZE: u64 = 256 * 1024;
+
+/// Bursts touching more paths than this get a full snapshot instead of an incremental one
+pub const INCREMENTAL_PATH_LIMIT: usize = 256;
 
 #[derive(Clone)]
 pub struct GitRepo {
     repo_path: PathBuf,
+    // Usually `<repo_path>/.git`, but GIT_DIR or a `.git` file may put it anywhere
+    git_dir: PathBuf,
+    // Shared between clones so the watcher's snapshots follow the app's toggle
+    untracked_mode: Arc<RwLock<UntrackedMode>>,
+    // Whether snapshots include the working trees of submodules; shared like untracked_mode
+    recurse_submodules: Arc<AtomicBool>,
 }
 
-impl GitRepo {
-    fn is_change_line(line: &str) -> bool {
-        (line.starts_with('+') && !line.starts_with("+++"))
-            || (line.starts_with('-') && !line.starts_with("---"))
+/// What is staged for a set of paths, as read by `GitRepo::staged_changes`
+#[derive(Debug, Default)]
+pub struct StagedChanges {
+    paths: HashSet<PathBuf>,
+    // (HEAD line number, content) of deletions already in the index
+    staged_deletions: HashMap<PathBuf, HashSet<(usize, String)>>,
+    // (worktree line number, content) of additions not yet in the index
+    unstaged_additions: HashMap<PathBuf, HashSet<(usize, String)>>,
+}
+
+impl StagedChanges {
+    /// Take over what was read from the submodule at `submodule_path`, keyed by
+    /// superproject paths
+    fn absorb_submodule(&mut self, submodule_path: &Path, submodule: StagedChanges) {
+        self.paths.extend(
+            submodule
+                .paths
+                .into_iter()
+                .map(|path| submodule_path.join(path)),
+        );
+        for (path, deletions) in submodule.staged_deletions {
+            self.staged_deletions
+                .insert(submodule_path.join(path), deletions);
+        }
+        for (path, additions) in submodule.unstaged_additions {
+            self.unstaged_additions
+                .insert(submodule_path.join(path), additions);
+        }
     }
 
-    fn hunk_line_coordinates(
-        hunk: &Hunk,
-        line_index: usize,
-    ) -> Option<(Option<usize>, Option<usize>)> {
-        if line_index >= hunk.lines.len() {
+    /// Indices of the staged lines in `hunk`, or None if `file_path` was not read
+    pub fn staged_lines(&self, hunk: &Hunk, file_path: &Path) -> Option<HashSet<usize>> {
+        if !self.paths.contains(file_path) {
             return None;
         }
+        let empty = HashSet::new();
+        let staged_deletions = self.staged_deletions.get(file_path).unwrap_or(&empty);
+        let unstaged_additions = self.unstaged_additions.get(file_path).unwrap_or(&empty);
+        let mut staged_lines = HashSet::new();
 
+        // Walk the target hunk and compute exact old/new coordinates for each line,
+        // then check whether that exact change exists in the staged index diff.
         let mut old_lineno = hunk.old_start;
         let mut new_lineno = hunk.new_start;
 
-        for (idx, line) in hunk.lines.iter().enumerate() {
-            let coords = if line.starts_with(' ') {
-                let current = (Some(old_lineno), Some(new_lineno));
+        for (hunk_idx, hunk_line) in hunk.lines.iter().enumerate() {
+            if hunk_line.starts_with(' ') {
                 old_lineno += 1;
                 new_lineno += 1;
-                current
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                let current = (Some(old_lineno), None);
+            } else if hunk_line.starts_with('-') && !hunk_line.starts_with("---") {
+                let content = hunk_line[1..].trim_end_matches('\n').to_string();
+                if staged_deletions.contains(&(old_lineno, content)) {
+                    staged_lines.insert(hunk_idx);
+                }
                 old_lineno += 1;
-                current
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                let current = (None, Some(new_lineno));
+            } else if hunk_line.starts_with('+') && !hunk_line.starts_with("+++") {
+                let content = hunk_line[1..].trim_end_matches('\n').to_string();
+                // '+' line is staged if it is NOT present as an unstaged worktree addition.
+                if !unstaged_additions.contains(&(new_lineno, content)) {
+                    staged_lines.insert(hunk_idx);
+                }
                 new_lineno += 1;
-                current
-            } else {
-                continue;
-            };
-
-            if idx == line_index {
-                return Some(coords);
             }
         }
 
-        None
+        Some(staged_lines)
     }
+}
 
-    fn single_line_patch_header(
-        hunk: &Hunk,
-        line_index: usize,
-    ) -> Option<(usize, usize, usize, usize)> {
-        if line_index >= hunk.lines.len() {
-            return None;
-        }
+impl GitRepo {
+    fn change_line_indices(hunk: &Hunk) -> HashSet<usize> {
+        hunk.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, line)| Hunk::is_change_line(line).then_some(idx))
+            .collect()
+    }
 
-        let mut old_lineno = hunk.old_start;
-        let mut new_lineno = hunk.new_start;
+    /// Number of HEAD lines a hunk covers (context plus removals).
+    fn hunk_old_line_count(hunk: &Hunk) -> usize {
+        hunk.lines
+            .iter()
+            .filter(|line| {
+                line.starts_with(' ') || (line.starts_with('-') && !line.starts_with("---"))
+            })
+            .count()
+    }
 
+    /// Build the index-side content of a hunk when exactly `staged` change lines are applied.
+    /// Context and unstaged removals are kept, staged removals dropped, and only staged
+    /// additions are included.
+    fn hunk_image(hunk: &Hunk, staged: &HashSet<usize>) -> Vec<String> {
+        let mut image = Vec::new();
         for (idx, line) in hunk.lines.iter().enumerate() {
-            if idx == line_index {
-                if line.starts_with('-') && !line.starts_with("---") {
-                    return Some((old_lineno, 1, new_lineno, 0));
-                }
-                if line.starts_with('+') && !line.starts_with("+++") {
-                    return Some((old_lineno, 0, new_lineno, 1));
+            if let Some(content) = line.strip_prefix(' ') {
+                image.push(content.to_string());
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                if !staged.contains(&idx) {
+                    image.push(line[1..].to_string());
                 }
-                return None;
+            } else if line.starts_with('+') && !line.starts_with("+++") && staged.contains(&idx) {
+                image.push(line[1..].to_string());
             }
+        }
+        image
+    }
 
-            if line.starts_with(' ') {
-                old_lineno += 1;
-                new_lineno += 1;
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                old_lineno += 1;
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                new_lineno += 1;
+    /// Split raw blob content into lines, keeping line terminators.
+    fn split_blob_lines(content: &[u8]) -> Vec<&[u8]> {
+        content.split_inclusive(|b| *b == b'\n').collect()
+    }
+
+    /// Zero-context HEAD->index blocks for a file as `(old_begin, old_len, new_len)`, where
+    /// `old_begin` is a 0-based line boundary in the HEAD blob.
+    fn staged_blocks_for_file(
+        repo: &Repository,
+        file_path: &Path,
+    ) -> Result<Vec<(usize, usize, usize)>> {
+        let head_tree = match repo.head() {
+            Ok(head) => head.peel_to_tree().ok(),
+            Err(_) => None,
+        };
+
+        let mut diff_opts = DiffOptions::new();
+        diff_opts.pathspec(file_path);
+        diff_opts.context_lines(0);
+
+        let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_opts))?;
+        let mut blocks = Vec::new();
+        diff.foreach(
+            &mut |_, _| true,
+            None,
+            Some(&mut |_, hunk| {
+                let old_len = hunk.old_lines() as usize;
+                let new_len = hunk.new_lines() as usize;
+                // libgit2 reports the line *before* an empty range as its start.
+                let old_begin = if old_len == 0 {
+                    hunk.old_start() as usize
+                } else {
+                    hunk.old_start() as usize - 1
+                };
+                blocks.push((old_begin, old_len, new_len));
+                true
+            }),
+            None,
+        )?;
+
+        Ok(blocks)
+    }
+
+    /// Map the HEAD line range `[begin, end)` onto the index, using the staged blocks for the
+    /// file. Staged blocks inside the range are absorbed into it; blocks that straddle a
+    /// boundary make the mapping ambiguous and are reported as an error.
+    ///
+    /// `open_start`/`open_end` say whether the hunk begins/ends with an added line, in which
+    /// case staged insertions exactly on that boundary belong to it.
+    fn map_head_range_to_index(
+        blocks: &[(usize, usize, usize)],
+        begin: usize,
+        end: usize,
+        open_start: bool,
+        open_end: bool,
+    ) -> Result<(usize, usize)> {
+        let mut shift_before: isize = 0;
+        let mut shift_inside: isize = 0;
+
+        for &(old_begin, old_len, new_len) in blocks {
+            let delta = new_len as isize - old_len as isize;
+            let old_end = old_begin + old_len;
+
+            let inside = if old_len == 0 {
+                (begin < old_begin && old_begin < end)
+                    || (open_start && old_begin == begin)
+                    || (open_end && old_begin == end)
+            } else {
+                old_begin >= begin && old_end <= end
+            };
+
+            if inside {
+                shift_inside += delta;
+            } else if old_end <= begin {
+                shift_before += delta;
+            } else if old_begin >= end {
+                // Entirely after the range; does not affect it.
+            } else {
+                return Err(anyhow::anyhow!(
+                    "Staged changes overlap the hunk boundary; stage or unstage the surrounding hunk first"
+                ));
             }
         }
 
-        None
+        let index_begin = (begin as isize + shift_before).max(0) as usize;
+        let index_end =
+            (end as isize + shift_before + shift_inside).max(index_begin as isize) as usize;
+        Ok((index_begin, index_end))
     }
 
-    fn build_single_line_patch(
+    /// Rewrite the index entry for `file_path` so that exactly `desired` change lines of `hunk`
+    /// are staged. The index blob is built in-process from the current index content, so the
+    /// update is atomic and does not depend on an external `git` binary.
+    ///
+    /// When `expected_current` is given, the index region covered by the hunk must match the
+    /// image implied by that staged set; otherwise the index holds changes the hunk cannot
+    /// express and a partial update would silently discard them.
+    fn write_hunk_selection(
+        &self,
+        hunk: &Hunk,
+        file_path: &Path,
+        desired: &HashSet<usize>,
+        expected_current: Option<&HashSet<usize>>,
+    ) -> Result<()> {
+        let image = Self::hunk_image(hunk, desired);
+        self.write_hunk_region(hunk, file_path, &image, expected_current)?;
+
+        crate::logger::debug(format!(
+            "write_hunk_selection file={} old_start={} staged={}",
+            file_path.display(),
+            hunk.old_start,
+            desired.len()
+        ));
+
+        Ok(())
+    }
+
+    /// Replace the index region covered by `hunk` with `image`.
+    fn write_hunk_region(
         &self,
         hunk: &Hunk,
-        line_index: usize,
         file_path: &Path,
-    ) -> Result<String> {
-        // Verify the line exists
-        if line_index >= hunk.lines.len() {
-            return Err(anyhow::anyhow!("Line index out of bounds"));
+        image: &[String],
+        expected_current: Option<&HashSet<usize>>,
+    ) -> Result<()> {
+        if let Some((submodule, path)) = self.submodule_for(file_path) {
+            return submodule.write_hunk_region(hunk, &path, image, expected_current);
+        }
+        let repo = self.open()?;
+        let mut index = repo.index()?;
+
+        let existing_entry = index.get_path(file_path, 0);
+        let current_content = match &existing_entry {
+            Some(entry) => repo.find_blob(entry.id)?.content().to_vec(),
+            None => Vec::new(),
+        };
+        let current_lines = Self::split_blob_lines(&current_content);
+
+        let old_count = Self::hunk_old_line_count(hunk);
+        let head_begin = if old_count == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let head_end = head_begin + old_count;
+
+        // Ignore "\ No newline at end of file" markers when looking at the hunk edges.
+        let body_lines: Vec<&String> = hunk
+            .lines
+            .iter()
+            .filter(|line| line.starts_with([' ', '+', '-']))
+            .collect();
+        let open_start = body_lines.first().is_none_or(|line| line.starts_with('+'));
+        let open_end = body_lines.last().is_none_or(|line| line.starts_with('+'));
+
+        let blocks = Self::staged_blocks_for_file(&repo, file_path)?;
+        let (region_begin, region_end) =
+            Self::map_head_range_to_index(&blocks, head_begin, head_end, open_start, open_end)?;
+        if region_end > current_lines.len() {
+            return Err(anyhow::anyhow!(
+                "Hunk is out of date for {}; refresh and try again",
+                file_path.display()
+            ));
         }
 
-        let selected_line = &hunk.lines[line_index];
+        if let Some(current) = expected_current {
+            let expected_image = Self::hunk_image(hunk, current);
+            let region_matches = expected_image.len() == region_end - region_begin
+                && expected_image
+                    .iter()
+                    .zip(&current_lines[region_begin..region_end])
+                    .all(|(expected, actual)| expected.as_bytes() == *actual);
+            if !region_matches {
+                return Err(anyhow::anyhow!(
+                    "Index for {} has changes this hunk does not describe; stage or unstage the whole hunk instead",
+                    file_path.display()
+                ));
+            }
+        }
+
+        let mut new_content = Vec::with_capacity(current_content.len());
+        for line in &current_lines[..region_begin] {
+            new_content.extend_from_slice(line);
+        }
+        for line in image {
+            new_content.extend_from_slice(line.as_bytes());
+        }
+        for line in &current_lines[region_end..] {
+            new_content.extend_from_slice(line);
+        }
 
-        // Only allow patching change lines
-        if !Self::is_change_line(selected_line) {
-            return Err(anyhow::anyhow!("Can only patch + or - lines"));
+        // A region that empties the file is a deletion (file in HEAD, gone from the working
+        // tree) or a new file with nothing staged; both leave no index entry behind.
+        if new_content.is_empty() {
+            let in_head = repo
+                .head()
+                .ok()
+                .and_then(|head| head.peel_to_tree().ok())
+                .is_some_and(|tree| tree.get_path(file_path).is_ok());
+            let in_worktree = self.repo_path.join(file_path).exists();
+            if in_head != in_worktree {
+                if existing_entry.is_some() {
+                    index.remove_path(file_path)?;
+                    index.write()?;
+                }
+                crate::logger::debug(format!(
+                    "write_hunk_region removed {} from the index",
+                    file_path.display()
+                ));
+                return Ok(());
+            }
         }
 
-        let (old_start, old_line_count, new_start, new_line_count) =
-            Self::single_line_patch_header(hunk, line_index)
-                .ok_or_else(|| anyhow::anyhow!("Can only patch + or - lines"))?;
+        let mut entry = match existing_entry {
+            Some(entry) => entry,
+            None => self.new_index_entry(&repo, file_path)?,
+        };
+        // Staged content replaces an intent-to-add placeholder
+        entry.flags_extended &= !git2::IndexEntryExtendedFlag::INTENT_TO_ADD.bits();
+        // Reset cached stat data so git re-checks the worktree file against the new blob.
+        entry.ctime = git2::IndexTime::new(0, 0);
+        entry.mtime = git2::IndexTime::new(0, 0);
+        entry.dev = 0;
+        entry.ino = 0;
+        entry.uid = 0;
+        entry.gid = 0;
+
+        index.add_frombuffer(&entry, &new_content)?;
+        index.write()?;
 
-        // Create a proper unified diff patch
-        let mut patch = String::new();
-        patch.push_str(&format!(
-            "diff --git a/{} b/{}\n",
+        crate::logger::debug(format!(
+            "write_hunk_region file={} index_region={}..{} lines={}",
             file_path.display(),
-            file_path.display()
-        ));
-        patch.push_str(&format!("--- a/{}\n", file_path.display()));
-        patch.push_str(&format!("+++ b/{}\n", file_path.display()));
-        patch.push_str(&format!(
-            "@@ -{},{} +{},{} @@\n",
-            old_start, old_line_count, new_start, new_line_count
+            region_begin,
+            region_end,
+            image.len()
         ));
 
-        patch.push_str(selected_line);
-        if !selected_line.ends_with('\n') {
-            patch.push('\n');
+        Ok(())
+    }
+
+    /// Build an index entry template for a path that is not yet in the index.
+    fn new_index_entry(&self, repo: &Repository, file_path: &Path) -> Result<git2::IndexEntry> {
+        let head_mode = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_tree().ok())
+            .and_then(|tree| tree.get_path(file_path).ok())
+            .map(|entry| entry.filemode() as u32);
+
+        let mode =
+            head_mode.unwrap_or_else(|| Self::workdir_file_mode(&self.repo_path.join(file_path)));
+
+        Ok(git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: git2::Oid::zero(),
+            flags: 0,
+            flags_extended: 0,
+            path: file_path.to_string_lossy().as_bytes().to_vec(),
+        })
+    }
+
+    #[cfg(unix)]
+    fn workdir_file_mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+
+        match std::fs::metadata(path) {
+            Ok(meta) if meta.permissions().mode() & 0o111 != 0 => 0o100755,
+            _ => 0o100644,
         }
+    }
 
-        Ok(patch)
+    #[cfg(not(unix))]
+    fn workdir_file_mode(_path: &Path) -> u32 {
+        0o100644
     }
 
-    fn apply_single_line_patch_raw(
+    /// Set exactly which change lines of a hunk are staged, in a single index update.
+    pub fn set_hunk_staged_lines(
         &self,
         hunk: &Hunk,
-        line_index: usize,
         file_path: &Path,
-        reverse: bool,
+        desired_staged_indices: &HashSet<usize>,
     ) -> Result<()> {
-        use std::io::Write;
-        use std::process::Command;
+        let all_change_indices = Self::change_line_indices(hunk);
+        let desired: HashSet<usize> = desired_staged_indices
+            .intersection(&all_change_indices)
+            .copied()
+            .collect();
+
+        // Whole-hunk requests replace the region outright: the result is fully defined by
+        // HEAD (nothing staged) or the worktree (everything staged).
+        if desired.is_empty() || desired.len() == all_change_indices.len() {
+            return self.write_hunk_selection(hunk, file_path, &desired, None);
+        }
+
+        let currently_staged = self.detect_staged_lines(hunk, file_path)?;
+        self.write_hunk_selection(hunk, file_path, &desired, Some(&currently_staged))
+    }
+
+    pub fn toggle_hunk_staging(&self, hunk: &Hunk, file_path: &Path) -> Result<bool> {
+        // Returns true if final state is staged, false if final state is unstaged.
+        let currently_staged = self.detect_staged_lines(hunk, file_path)?;
+        let all_change_indices = Self::change_line_indices(hunk);
+
+        // Hunk-mode `s` behavior:
+        // - fully staged hunk => unstage hunk
+        // - partially/unstaged hunk => stage remaining lines
+        if currently_staged.len() < all_change_indices.len() {
+            self.stage_hunk(hunk, file_path)?;
+            Ok(true)
+        } else {
+            self.unstage_hunk(hunk, file_path)?;
+            Ok(false)
+        }
+    }
+
+    /// Find the repository containing `path`. When GIT_DIR is set it wins, with
+    /// GIT_WORK_TREE, `core.worktree` or else `path` naming the working tree, as for git
+    /// itself. GIT_DIR names one repository, so callers opening several must not mix it in.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if let Some(git_dir) = std::env::var_os("GIT_DIR") {
+            let work_tree = std::env::var_os("GIT_WORK_TREE")
+                .map(PathBuf::from)
+                .or_else(|| Self::configured_work_tree(Path::new(&git_dir)))
+                .unwrap_or_else(|| path.as_ref().to_path_buf());
+            return Self::with_git_dir(work_tree, git_dir)
+                .context("Failed to open the repository in GIT_DIR");
+        }
+
+        let repo = Repository::discover(path.as_ref()).context("Failed to find git repository")?;
+        // libgit2 places the working tree of `git init --separate-git-dir` next to the
+        // git directory; git places it where the `.git` file is.
+        if let Some(workdir) = Self::gitfile_workdir(path.as_ref()) {
+            if repo.workdir() != Some(workdir.as_path()) {
+                repo.set_workdir(&workdir, false)?;
+            }
+        }
+        Self::from_repository(&repo)
+    }
+
+    /// `core.worktree` of the git directory, relative to it unless absolute
+    fn configured_work_tree(git_dir: &Path) -> Option<PathBuf> {
+        let repo = Repository::open_bare(git_dir).ok()?;
+        let work_tree = repo.config().ok()?.get_path("core.worktree").ok()?;
+        Some(git_dir.join(work_tree))
+    }
+
+    /// The nearest directory at or above `path` holding a `.git` file rather than a
+    /// `.git` directory
+    fn gitfile_workdir(path: &Path) -> Option<PathBuf> {
+        let start = path.canonicalize().ok()?;
+        let dir = start.ancestors().find(|dir| dir.join(".git").exists())?;
+        dir.join(".git").is_file().then(|| dir.to_path_buf())
+    }
+
+    /// Open a repository whose git directory and working tree are in unrelated places,
+    /// like `git --git-dir=<git_dir> --work-tree=<work_tree>`.
+    pub fn with_git_dir<P: AsRef<Path>, Q: AsRef<Path>>(work_tree: P, git_dir: Q) -> Result<Self> {
+        let repo = Repository::open(git_dir.as_ref()).context("Failed to open git directory")?;
+        repo.set_workdir(work_tree.as_ref(), false)
+            .context("Failed to use the working tree")?;
+        Self::from_repository(&repo)
+    }
+
+    fn from_repository(repo: &Repository) -> Result<Self> {
+        let repo_path = repo
+            .workdir()
+            .context("Repository has no working directory")?
+            .to_path_buf();
+
+        Ok(Self {
+            repo_path,
+            git_dir: repo.path().to_path_buf(),
+            untracked_mode: Arc::new(RwLock::new(UntrackedMode::default())),
+            recurse_submodules: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Open the repository with this working tree, wherever its git directory lives
+    pub fn open(&self) -> Result<Repository> {
+        let repo = Repository::open(&self.git_dir)?;
+        if repo.workdir() != Some(self.repo_path.as_path()) {
+            repo.set_workdir(&self.repo_path, false)?;
+        }
+        Ok(repo)
+    }
+
+    /// A `git` command that runs against this repository even when its git directory is
+    /// not `<repo_path>/.git`
+    fn git_command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new("git");
+        command
+            .current_dir(&self.repo_path)
+            .env("GIT_DIR", &self.git_dir)
+            .env("GIT_WORK_TREE", &self.repo_path);
+        command
+    }
+
+    /// Git directories that live outside the working tree and hold this worktree's HEAD,
+    /// index and refs: the `.git/worktrees/<name>` directory of a linked worktree and the
+    /// main repository's `.git`. Empty for an ordinary `.git` inside the working tree.
+    pub fn external_git_dirs(&self) -> Result<Vec<PathBuf>> {
+        let repo = self.open()?;
+        let git_dir = repo.path().to_path_buf();
+        let common_dir = Self::common_dir(&repo);
+
+        let mut dirs = Vec::new();
+        for dir in [git_dir, common_dir] {
+            if !dir.starts_with(&self.repo_path) && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Working tree files a polling watcher should look at: every tracked file and every
+    /// untracked file that isn't ignored, relative to the working tree
+    pub fn watched_paths(&self) -> Result<Vec<PathBuf>> {
+        let repo = self.open()?;
+        let mut opts = git2::StatusOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_unmodified(true)
+            .include_ignored(false)
+            .exclude_submodules(true);
+        let statuses = repo.statuses(Some(&mut opts))?;
+        Ok(statuses
+            .iter()
+            .filter_map(|entry| entry.path().map(PathBuf::from))
+            .collect())
+    }
+
+    /// Working directories of the main worktree and every valid linked worktree of this
+    /// repository, this one included.
+    pub fn worktree_paths(&self) -> Result<Vec<PathBuf>> {
+        let repo = self.open()?;
+        let common = Repository::open(Self::common_dir(&repo))?;
+
+        let mut paths = Vec::new();
+        if let Some(workdir) = common.workdir() {
+            paths.push(workdir.to_path_buf());
+        }
+        for name in common.worktrees()?.iter().flatten() {
+            match common.find_worktree(name) {
+                Ok(worktree) if worktree.validate().is_ok() => {
+                    paths.push(worktree.path().to_path_buf());
+                }
+                Ok(_) => crate::logger::debug(format!("Skipping stale worktree {}", name)),
+                Err(e) => crate::logger::debug(format!("Failed to open worktree {}: {}", name, e)),
+            }
+        }
+        Ok(paths)
+    }
+
+    /// The `.git` directory shared by all worktrees. A linked worktree's git directory
+    /// points at it through its `commondir` file.
+    fn common_dir(repo: &Repository) -> PathBuf {
+        let git_dir = repo.path();
+        match std::fs::read_to_string(git_dir.join("commondir")) {
+            Ok(content) => {
+                let common = git_dir.join(content.trim());
+                common.canonicalize().unwrap_or(common)
+            }
+            Err(_) => git_dir.to_path_buf(),
+        }
+    }
+
+    pub fn untracked_mode(&self) -> UntrackedMode {
+        *self
+            .untracked_mode
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_untracked_mode(&self, mode: UntrackedMode) {
+        *self
+            .untracked_mode
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = mode;
+    }
+
+    pub fn recurses_submodules(&self) -> bool {
+        self.recurse_submodules.load(Ordering::Relaxed)
+    }
+
+    pub fn set_recurse_submodules(&self, recurse: bool) {
+        self.recurse_submodules.store(recurse, Ordering::Relaxed);
+    }
+
+    /// The checked-out submodule at `path`, sharing this repository's settings
+    fn open_submodule(&self, path: &Path) -> Option<GitRepo> {
+        let workdir = self.repo_path.join(path);
+        let repo = Repository::open(&workdir).ok()?;
+        // An uninitialized submodule is an empty directory inside the superproject
+        let repo_path = repo.workdir()?.to_path_buf();
+        if !repo_path.starts_with(&workdir) {
+            return None;
+        }
+        Some(GitRepo {
+            repo_path,
+            git_dir: repo.path().to_path_buf(),
+            untracked_mode: self.untracked_mode.clone(),
+            recurse_submodules: self.recurse_submodules.clone(),
+        })
+    }
+
+    /// When recursing into submodules, the submodule that owns `file_path` and the path
+    /// relative to it, so staging happens in the submodule's own index.
+    fn submodule_for(&self, file_path: &Path) -> Option<(GitRepo, PathBuf)> {
+        self.submodule_paths().iter().find_map(|submodule_path| {
+            let rel_path = Self::path_in_submodule(file_path, submodule_path)?;
+            Some((self.open_submodule(submodule_path)?, rel_path.to_path_buf()))
+        })
+    }
+
+    /// Paths of the superproject's submodules, or none when not recursing into them
+    fn submodule_paths(&self) -> Vec<PathBuf> {
+        if !self.recurses_submodules() {
+            return Vec::new();
+        }
+        let Ok(repo) = self.open() else {
+            return Vec::new();
+        };
+        repo.submodules()
+            .map(|submodules| {
+                submodules
+                    .iter()
+                    .map(|submodule| submodule.path().to_path_buf())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `file_path` relative to the submodule at `submodule_path`, if it lies inside it
+    fn path_in_submodule<'a>(file_path: &'a Path, submodule_path: &Path) -> Option<&'a Path> {
+        file_path
+            .strip_prefix(submodule_path)
+            .ok()
+            .filter(|rel_path| !rel_path.as_os_str().is_empty())
+    }
+
+    /// Diff snapshots of the checked-out submodules' working trees, with paths and hunk
+    /// ids prefixed by the submodule path
+    fn submodule_files(&self, repo: &Repository) -> Result<(Vec<FileChange>, usize)> {
+        let mut files = Vec::new();
+        let mut untracked_overflow = 0;
+        for submodule in repo.submodules()? {
+            let Some(sub_repo) = self.open_submodule(submodule.path()) else {
+                continue;
+            };
+            let nested = match sub_repo.get_diff_snapshot() {
+                Ok(nested) => nested,
+                Err(e) => {
+                    crate::logger::debug(format!(
+                        "Failed to diff submodule {}: {}",
+                        submodule.path().display(),
+                        e
+                    ));
+                    continue;
+                }
+            };
+            untracked_overflow += nested.untracked_overflow;
+            for mut file in nested.files {
+                file.path = submodule.path().join(&file.path);
+                for hunk in &mut file.hunks {
+                    hunk.id = HunkId::new(&file.path, hunk.old_start, hunk.new_start, &hunk.lines);
+                }
+                files.push(file);
+            }
+        }
+        Ok((files, untracked_overflow))
+    }
+
+    /// Append an anchored pattern for the untracked `path` to `.gitignore` or
+    /// `info/exclude`, unless the pattern is already there. Linked worktrees share the
+    /// exclude file of the main git directory, as git only reads that one.
+    pub fn ignore_path(&self, path: &Path, target: IgnoreTarget) -> Result<()> {
+        if let Some((submodule, path)) = self.submodule_for(path) {
+            return submodule.ignore_path(&path, target);
+        }
+        let repo = self.open()?;
+        let ignore_file = match target {
+            IgnoreTarget::GitIgnore => self.repo_path.join(".gitignore"),
+            IgnoreTarget::InfoExclude => Self::common_dir(&repo).join("info").join("exclude"),
+        };
+
+        let path_text = path.to_string_lossy().replace('\\', "/");
+        let pattern = format!("/{}", path_text.trim_start_matches('/'));
+
+        let mut content = std::fs::read_to_string(&ignore_file).unwrap_or_default();
+        if content.lines().any(|line| line.trim() == pattern) {
+            return Ok(());
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&pattern);
+        content.push('\n');
+
+        if let Some(parent) = ignore_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&ignore_file, content)
+            .with_context(|| format!("Failed to write {}", ignore_file.display()))?;
+        Ok(())
+    }
+
+    /// Run `git commit` interactively, allowing Git to launch the configured editor.
+    pub fn commit_with_editor(&self) -> Result<std::process::ExitStatus> {
+        let mut command = self.git_command();
+        command.arg("commit");
+        self.run_in_terminal(command, "`git commit`")
+    }
+
+    /// Run a command that takes over the terminal, like an editor, from the working tree
+    fn run_in_terminal(
+        &self,
+        mut command: std::process::Command,
+        description: &str,
+    ) -> Result<std::process::ExitStatus> {
+        command
+            .current_dir(&self.repo_path)
+            .status()
+            .with_context(|| format!("Failed to launch {}", description))
+    }
+
+    /// The checked-out branch and commit; the commit is None on an unborn branch.
+    pub fn head_state(&self) -> Result<HeadState> {
+        let repo = self.open()?;
+        let head = match repo.head() {
+            Ok(head) => head,
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                return Ok(HeadState {
+                    branch: Self::unborn_branch_name(&repo),
+                    oid: None,
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(HeadState {
+            branch: head
+                .is_branch()
+                .then(|| head.shorthand().map(str::to_string))
+                .flatten(),
+            oid: head.target().map(|oid| oid.to_string()),
+        })
+    }
+
+    /// Classify how HEAD got from `from` to `to`. Returns None when HEAD did not move to
+    /// a different commit or branch, or when it now points at an unborn branch.
+    pub fn describe_head_move(&self, from: &HeadState, to: &HeadState) -> Result<Option<HeadMove>> {
+        if from == to {
+            return Ok(None);
+        }
+        let Some(to_oid) = &to.oid else {
+            return Ok(None);
+        };
+
+        let repo = self.open()?;
+        let commit = repo.find_commit(git2::Oid::from_str(to_oid)?)?;
+        let kind = if from.branch != to.branch {
+            HeadMoveKind::Checkout
+        } else {
+            let builds_on_previous = match &from.oid {
+                Some(from_oid) => commit.parent_ids().any(|id| id.to_string() == *from_oid),
+                // The first commit on an unborn branch
+                None => commit.parent_count() == 0,
+            };
+            if builds_on_previous {
+                HeadMoveKind::Commit
+            } else {
+                HeadMoveKind::Reset
+            }
+        };
+
+        Ok(Some(HeadMove {
+            kind,
+            branch: to.branch.clone(),
+            commit: Self::commit_info(&commit),
+        }))
+    }
+
+    /// The operation in progress in a repository in `state`, if any
+    fn operation_for(state: RepositoryState) -> Option<RepoOperation> {
+        match state {
+            RepositoryState::Clean => None,
+            RepositoryState::Merge => Some(RepoOperation::Merge),
+            RepositoryState::Revert | RepositoryState::RevertSequence => {
+                Some(RepoOperation::Revert)
+            }
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                Some(RepoOperation::CherryPick)
+            }
+            RepositoryState::Bisect => Some(RepoOperation::Bisect),
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => Some(RepoOperation::Rebase),
+            RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => {
+                Some(RepoOperation::ApplyMailbox)
+            }
+        }
+    }
+
+    /// Branch, upstream and in-progress operation for the header.
+    pub fn repo_status(&self) -> Result<RepoStatus> {
+        let repo = self.open()?;
+        let mut status = RepoStatus {
+            detached: repo.head_detached().unwrap_or(false),
+            ..RepoStatus::default()
+        };
+
+        match repo.head() {
+            Ok(head) => {
+                if head.is_branch() {
+                    status.branch = head.shorthand().map(str::to_string);
+                }
+                if let Some(oid) = head.target() {
+                    status.head_short_sha = Some(oid.to_string()[..7].to_string());
+                }
+
+                if let Some(branch_name) = status.branch.clone() {
+                    let branch = repo.find_branch(&branch_name, git2::BranchType::Local)?;
+                    if let Ok(upstream) = branch.upstream() {
+                        status.upstream = upstream.name()?.map(str::to_string);
+                        if let (Some(local), Some(remote)) =
+                            (head.target(), upstream.get().target())
+                        {
+                            let (ahead, behind) = repo.graph_ahead_behind(local, remote)?;
+                            status.ahead = ahead;
+                            status.behind = behind;
+                        }
+                    }
+                }
+            }
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                status.unborn = true;
+                status.branch = Self::unborn_branch_name(&repo);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        status.operation = Self::operation_for(repo.state());
+
+        // A rebase detaches HEAD; name the branch being rebased instead.
+        if status.operation == Some(RepoOperation::Rebase) && status.branch.is_none() {
+            status.branch = ["rebase-merge", "rebase-apply"].iter().find_map(|dir| {
+                let head_name =
+                    std::fs::read_to_string(repo.path().join(dir).join("head-name")).ok()?;
+                let head_name = head_name.trim();
+                Some(
+                    head_name
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(head_name)
+                        .to_string(),
+                )
+            });
+        }
+
+        Ok(status)
+    }
+
+    /// Files whose index content differs from HEAD, with line counts.
+    pub fn staged_files(&self) -> Result<Vec<StagedFile>> {
+        let repo = self.open()?;
+        let head_tree = match repo.head() {
+            Ok(head) => head.peel_to_tree().ok(),
+            Err(_) => None,
+        };
+
+        let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, None)?;
+        let mut files = Vec::new();
+        for (idx, delta) in diff.deltas().enumerate() {
+            let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+                continue;
+            };
+            let (insertions, deletions) = match git2::Patch::from_diff(&diff, idx)? {
+                Some(patch) => {
+                    let (_, insertions, deletions) = patch.line_stats()?;
+                    (insertions, deletions)
+                }
+                None => (0, 0),
+            };
+            files.push(StagedFile {
+                path: path.to_path_buf(),
+                status: format!("{:?}", delta.status()),
+                insertions,
+                deletions,
+            });
+        }
+
+        Ok(files)
+    }
+
+    /// Full message of the HEAD commit, if there is one.
+    pub fn head_commit_message(&self) -> Result<Option<String>> {
+        let repo = self.open()?;
+        let head = match repo.head() {
+            Ok(head) => head.peel_to_commit()?,
+            Err(_) => return Ok(None),
+        };
+        Ok(head.message().map(str::to_string))
+    }
+
+    /// Contents of the file configured as `commit.template`, if any.
+    pub fn commit_template(&self) -> Result<Option<String>> {
+        let repo = self.open()?;
+        let config = repo.config()?;
+        let Ok(template_path) = config.get_path("commit.template") else {
+            return Ok(None);
+        };
+        let template_path = if template_path.is_relative() {
+            self.repo_path.join(template_path)
+        } else {
+            template_path
+        };
+
+        let template = std::fs::read_to_string(&template_path).with_context(|| {
+            format!("Failed to read commit template {}", template_path.display())
+        })?;
+        Ok(Some(template))
+    }
+
+    /// The `Signed-off-by` trailer for the configured committer identity.
+    pub fn signoff_trailer(&self) -> Result<String> {
+        let repo = self.open()?;
+        let signature = repo
+            .signature()
+            .context("Set user.name and user.email to sign off commits")?;
+        Ok(format!(
+            "Signed-off-by: {} <{}>",
+            signature.name().unwrap_or(""),
+            signature.email().unwrap_or("")
+        ))
+    }
+
+    /// Create a commit from the current index, or amend HEAD with it, without leaving the
+    /// process. Comment lines are stripped from `message` the way `git commit` does.
+    pub fn create_commit(&self, message: &str, amend: bool) -> Result<git2::Oid> {
+        let mut repo = self.open()?;
+        let message = git2::message_prettify(message, Some(b'#'))?;
+        if message.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Aborting commit due to empty commit message"
+            ));
+        }
+
+        // A merge commit gets the merged heads as extra parents; other operations in
+        // progress are left to git, which knows how to continue them
+        let merging = match Self::operation_for(repo.state()) {
+            None | Some(RepoOperation::Bisect) => false,
+            Some(RepoOperation::Merge) if amend => {
+                return Err(anyhow::anyhow!(
+                    "A merge is in progress; commit it before amending"
+                ))
+            }
+            Some(RepoOperation::Merge) => true,
+            Some(operation) => {
+                return Err(anyhow::anyhow!(
+                    "The repository is {}; finish that with git before committing",
+                    operation.label()
+                ))
+            }
+        };
+        let mut merge_heads = Vec::new();
+        if merging {
+            repo.mergehead_foreach(|oid| {
+                merge_heads.push(*oid);
+                true
+            })?;
+        }
+
+        let signature = repo
+            .signature()
+            .context("Set user.name and user.email before committing")?;
+        let mut index = repo.index()?;
+        if index.has_conflicts() {
+            return Err(anyhow::anyhow!(
+                "Resolve the conflicted files before committing"
+            ));
+        }
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        let oid = if amend {
+            let head = repo
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .context("There is no commit to amend")?;
+            head.amend(
+                Some("HEAD"),
+                None,
+                Some(&signature),
+                None,
+                Some(&message),
+                Some(&tree),
+            )?
+        } else {
+            let mut parents = Vec::new();
+            if let Ok(head) = repo.head() {
+                parents.push(head.peel_to_commit()?);
+            }
+            for oid in merge_heads {
+                parents.push(repo.find_commit(oid)?);
+            }
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let oid = repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &message,
+                &tree,
+                &parents,
+            )?;
+            if merging {
+                // Drops MERGE_HEAD and MERGE_MSG, as `git commit` does
+                repo.cleanup_state()?;
+            }
+            oid
+        };
+
+        crate::logger::debug(format!("create_commit oid={} amend={}", oid, amend));
+        Ok(oid)
+    }
+
+    /// Commit the staged changes as `fixup! <summary>` of `target_sha`, ready for autosquash.
+    pub fn create_fixup_commit(&self, target_sha: &str) -> Result<git2::Oid> {
+        let summary = {
+            let repo = self.open()?;
+            let oid = git2::Oid::from_str(target_sha).context("Invalid commit SHA")?;
+            let target = repo.find_commit(oid)?;
+            target.summary().unwrap_or("").to_string()
+        };
+        if self.staged_files()?.is_empty() {
+            return Err(anyhow::anyhow!("Nothing staged for the fixup commit"));
+        }
+
+        self.create_commit(&format!("fixup! {}\n", summary), false)
+    }
+
+    /// Fold the staged changes into HEAD, keeping its message.
+    pub fn amend_head_with_index(&self) -> Result<git2::Oid> {
+        if self.staged_files()?.is_empty() {
+            return Err(anyhow::anyhow!("Nothing staged to amend HEAD with"));
+        }
+        let message = self
+            .head_commit_message()?
+            .context("There is no commit to amend")?;
 
-        let patch = self.build_single_line_patch(hunk, line_index, file_path)?;
+        self.create_commit(&message, true)
+    }
+
+    /// Squash pending `fixup!`/`squash!` commits into their targets with a non-interactive
+    /// `git rebase --autosquash` starting at `target_sha`. Local changes are stashed around the
+    /// rebase, and a failed rebase is aborted so the branch is left as it was. Refuses to start
+    /// while another operation is in progress, so only its own rebase is ever aborted.
+    pub fn autosquash_onto(&self, target_sha: &str) -> Result<()> {
+        let has_parent = {
+            let repo = self.open()?;
+            if let Some(operation) = Self::operation_for(repo.state()) {
+                return Err(anyhow::anyhow!(
+                    "The repository is {}; finish that with git before autosquashing",
+                    operation.label()
+                ));
+            }
+            if Self::rebase_in_progress(&repo) {
+                return Err(anyhow::anyhow!(
+                    "A rebase is in progress; finish that with git before autosquashing"
+                ));
+            }
+            let oid = git2::Oid::from_str(target_sha).context("Invalid commit SHA")?;
+            let parent_count = repo.find_commit(oid)?.parent_count();
+            parent_count > 0
+        };
 
-        let mut cmd = Command::new("git");
-        cmd.arg("apply")
-            .arg("--cached")
-            .arg("--unidiff-zero")
-            .arg("--recount");
-        if reverse {
-            cmd.arg("--reverse");
+        let mut rebase = self.git_command();
+        rebase
+            .args(["rebase", "--interactive", "--autosquash", "--autostash"])
+            // Accept the generated todo list and combined messages as they are.
+            .env("GIT_SEQUENCE_EDITOR", "true")
+            .env("GIT_EDITOR", "true");
+        if has_parent {
+            rebase.arg(format!("{}^", target_sha));
+        } else {
+            rebase.arg("--root");
         }
-        cmd.arg("-")
-            .current_dir(&self.repo_path)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
 
-        let mut child = cmd.spawn()?;
+        let output = rebase.output().context("Failed to run `git rebase`")?;
+        if output.status.success() {
+            crate::logger::debug(format!("autosquash onto {} succeeded", target_sha));
+            return Ok(());
+        }
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(patch.as_bytes())?;
+        // Nothing was in progress before, so a rebase left behind is the one started here
+        if self
+            .open()
+            .is_ok_and(|repo| Self::rebase_in_progress(&repo))
+        {
+            let _ = self.git_command().args(["rebase", "--abort"]).output();
         }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("rebase failed");
+        Err(anyhow::anyhow!("Autosquash aborted: {}", reason.trim()))
+    }
 
-        let output = child.wait_with_output()?;
+    /// Whether a rebase has left its state directory in the git directory
+    fn rebase_in_progress(repo: &Repository) -> bool {
+        ["rebase-merge", "rebase-apply"]
+            .iter()
+            .any(|dir| repo.path().join(dir).exists())
+    }
 
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr);
-            let patch_preview = if patch.len() > 500 {
-                format!("{}... (truncated)", &patch[..500])
-            } else {
-                patch.clone()
+    /// List the stash entries with their age and the number of files each one touches.
+    pub fn list_stashes(&self) -> Result<Vec<StashInfo>> {
+        let mut repo = self.open()?;
+        let mut entries = Vec::new();
+        repo.stash_foreach(|index, message, oid| {
+            entries.push((index, message.to_string(), *oid));
+            true
+        })?;
+
+        let mut stashes = Vec::with_capacity(entries.len());
+        for (index, message, oid) in entries {
+            let commit = repo.find_commit(oid)?;
+            let base_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
             };
-            let action = if reverse { "unstage" } else { "stage" };
-            return Err(anyhow::anyhow!(
-                "Failed to {} line: {}\nPatch was:\n{}",
-                action,
-                error_msg,
-                patch_preview
-            ));
+            let diff = repo.diff_tree_to_tree(base_tree.as_ref(), Some(&commit.tree()?), None)?;
+            // Untracked files live in an optional third parent
+            let untracked = match commit.parent(2) {
+                Ok(parent) => repo
+                    .diff_tree_to_tree(None, Some(&parent.tree()?), None)?
+                    .deltas()
+                    .len(),
+                Err(_) => 0,
+            };
+
+            stashes.push(StashInfo {
+                index,
+                sha: oid.to_string(),
+                message,
+                time: std::time::UNIX_EPOCH
+                    + std::time::Duration::from_secs(commit.time().seconds().max(0) as u64),
+                file_count: diff.deltas().len() + untracked,
+            });
         }
 
-        Ok(())
+        Ok(stashes)
     }
 
-    fn is_noop_patch_apply_error(err: &anyhow::Error) -> bool {
-        let msg = err.to_string().to_lowercase();
-        msg.contains("patch does not apply") || msg.contains("no valid patches in input")
+    /// Apply a stash entry to the working tree, keeping it in the list.
+    pub fn apply_stash(&self, index: usize) -> Result<()> {
+        let mut repo = self.open()?;
+        repo.stash_apply(index, None)
+            .with_context(|| format!("Failed to apply stash@{{{}}}", index))?;
+        Ok(())
     }
 
-    fn change_line_indices(hunk: &Hunk) -> std::collections::HashSet<usize> {
-        hunk.lines
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, line)| Self::is_change_line(line).then_some(idx))
-            .collect()
+    /// Apply a stash entry and remove it from the list if it applied cleanly.
+    pub fn pop_stash(&self, index: usize) -> Result<()> {
+        let mut repo = self.open()?;
+        repo.stash_pop(index, None)
+            .with_context(|| format!("Failed to pop stash@{{{}}}", index))?;
+        Ok(())
     }
 
-    fn sort_indices_desc_by_position(
-        hunk: &Hunk,
-        indices: &std::collections::HashSet<usize>,
-    ) -> Vec<usize> {
-        let mut ordered: Vec<usize> = indices.iter().copied().collect();
-        ordered.sort_by(|a, b| {
-            let a_pos = Self::hunk_line_coordinates(hunk, *a)
-                .map(|(old, new)| old.or(new).unwrap_or(0))
-                .unwrap_or(0);
-            let b_pos = Self::hunk_line_coordinates(hunk, *b)
-                .map(|(old, new)| old.or(new).unwrap_or(0))
-                .unwrap_or(0);
-            b_pos.cmp(&a_pos).then_with(|| b.cmp(a))
-        });
-        ordered
+    pub fn drop_stash(&self, index: usize) -> Result<()> {
+        let mut repo = self.open()?;
+        repo.stash_drop(index)
+            .with_context(|| format!("Failed to drop stash@{{{}}}", index))?;
+        Ok(())
     }
 
-    fn unstaged_hunk_count_for_file(&self, file_path: &Path) -> Result<usize> {
-        let repo = Repository::open(&self.repo_path)?;
-        let index = repo.index()?;
+    /// Move the given hunks out of the working tree and index into a new stash entry. The
+    /// entry records the hunks as unstaged changes on top of HEAD, so applying it later
+    /// brings back exactly these hunks.
+    pub fn stash_hunks(&self, hunks: &[(PathBuf, Hunk)], message: &str) -> Result<git2::Oid> {
+        if hunks.is_empty() {
+            return Err(anyhow::anyhow!("No hunks selected to stash"));
+        }
 
-        let mut diff_opts = DiffOptions::new();
-        diff_opts.pathspec(file_path);
-        diff_opts.context_lines(3);
+        let repo = self.open()?;
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("Cannot stash hunks without an initial commit")?;
+        let head_tree = head.tree()?;
+        let signature = repo
+            .signature()
+            .context("Set user.name and user.email before stashing")?;
+
+        // Group by file and apply each file's hunks bottom-up so earlier positions hold.
+        let mut by_file: Vec<(&PathBuf, Vec<&Hunk>)> = Vec::new();
+        for (path, hunk) in hunks {
+            match by_file.iter_mut().find(|(p, _)| *p == path) {
+                Some((_, file_hunks)) => file_hunks.push(hunk),
+                None => by_file.push((path, vec![hunk])),
+            }
+        }
+        for (_, file_hunks) in &mut by_file {
+            file_hunks.sort_by_key(|hunk| std::cmp::Reverse(hunk.old_start));
+        }
 
-        let diff = repo.diff_index_to_workdir(Some(&index), Some(&mut diff_opts))?;
-        let mut count: usize = 0;
-        diff.foreach(
-            &mut |_, _| true,
+        // Build the stashed worktree tree: HEAD plus only the selected hunks.
+        let mut stash_index = git2::Index::new()?;
+        stash_index.read_tree(&head_tree)?;
+        for (path, file_hunks) in &by_file {
+            let head_content = match head_tree.get_path(path) {
+                Ok(entry) => repo.find_blob(entry.id())?.content().to_vec(),
+                Err(_) => Vec::new(),
+            };
+            let mut lines: Vec<Vec<u8>> = Self::split_blob_lines(&head_content)
+                .into_iter()
+                .map(<[u8]>::to_vec)
+                .collect();
+            for hunk in file_hunks {
+                let old_count = Self::hunk_old_line_count(hunk);
+                let begin = if old_count == 0 {
+                    hunk.old_start
+                } else {
+                    hunk.old_start.saturating_sub(1)
+                };
+                let end = begin + old_count;
+                if end > lines.len() {
+                    return Err(anyhow::anyhow!(
+                        "Hunk is out of date for {}; refresh and try again",
+                        path.display()
+                    ));
+                }
+                let new_side = Self::patch_side(&hunk.lines, '+')
+                    .into_iter()
+                    .map(String::into_bytes);
+                lines.splice(begin..end, new_side);
+            }
+
+            let full_path = self.repo_path.join(path);
+            if lines.is_empty() && !full_path.exists() {
+                stash_index.remove_path(path)?;
+                continue;
+            }
+            let mut entry = match stash_index.get_path(path, 0) {
+                Some(entry) => entry,
+                None => self.new_index_entry(&repo, path)?,
+            };
+            // The in-memory index has no repository, so write the blob ourselves
+            entry.id = repo.blob(&lines.concat())?;
+            stash_index.add(&entry)?;
+        }
+        let worktree_tree = repo.find_tree(stash_index.write_tree_to(&repo)?)?;
+
+        let branch = repo
+            .head()
+            .ok()
+            .and_then(|head| head.shorthand().map(str::to_string))
+            .unwrap_or_else(|| "(no branch)".to_string());
+        let head_summary = format!(
+            "{} {}",
+            &head.id().to_string()[..7],
+            head.summary().unwrap_or("")
+        );
+        let index_commit = repo.commit(
             None,
-            Some(&mut |_, _| {
-                count += 1;
-                true
-            }),
+            &signature,
+            &signature,
+            &format!("index on {}: {}", branch, head_summary),
+            &head_tree,
+            &[&head],
+        )?;
+        let index_commit = repo.find_commit(index_commit)?;
+        let stash_message = format!("On {}: {}", branch, message);
+        let stash_oid = repo.commit(
             None,
+            &signature,
+            &signature,
+            &stash_message,
+            &worktree_tree,
+            &[&head, &index_commit],
         )?;
 
-        Ok(count)
-    }
-
-    #[allow(dead_code)]
-    fn set_hunk_staged_lines_with_reset(
-        &self,
-        hunk: &Hunk,
-        file_path: &Path,
-        desired_staged_indices: &std::collections::HashSet<usize>,
-        reset_indices: &std::collections::HashSet<usize>,
-    ) -> Result<()> {
-        use std::collections::HashSet;
-
-        let all_change_indices = Self::change_line_indices(hunk);
-        let desired: HashSet<usize> = desired_staged_indices
-            .intersection(&all_change_indices)
-            .copied()
-            .collect();
-
-        let reset_filtered: HashSet<usize> = reset_indices
-            .intersection(&all_change_indices)
-            .copied()
+        // Take the hunks out of the working tree and index before recording the entry, and
+        // put both back if either step fails, so the changes are never lost or kept twice.
+        let index_path = repo.path().join("index");
+        let saved_index = std::fs::read(&index_path).ok();
+        let saved_files: Vec<(&PathBuf, Option<Vec<u8>>)> = by_file
+            .iter()
+            .map(|(path, _)| (*path, std::fs::read(self.repo_path.join(path)).ok()))
             .collect();
+        let result = by_file
+            .iter()
+            .try_for_each(|(path, file_hunks)| {
+                let mut ordered = file_hunks.clone();
+                ordered.sort_by_key(|hunk| std::cmp::Reverse(hunk.new_start));
+                ordered.into_iter().try_for_each(|hunk| {
+                    self.discard_hunk_lines(hunk, path, &Self::change_line_indices(hunk))
+                })
+            })
+            .and_then(|()| self.store_stash(stash_oid, &stash_message));
+        if let Err(err) = result {
+            for (path, content) in saved_files {
+                let full_path = self.repo_path.join(path);
+                let _ = match content {
+                    Some(content) => std::fs::write(&full_path, content),
+                    None => std::fs::remove_file(&full_path),
+                };
+            }
+            if let Some(index) = saved_index {
+                let _ = std::fs::write(&index_path, index);
+            }
+            return Err(err);
+        }
 
         crate::logger::debug(format!(
-            "set_hunk_staged_lines_with_reset file={} old_start={} new_start={} all_changes={} desired={} reset={}",
-            file_path.display(),
-            hunk.old_start,
-            hunk.new_start,
-            all_change_indices.len(),
-            desired.len(),
-            reset_indices.len()
+            "stash_hunks stored {} hunk(s) as {}",
+            hunks.len(),
+            stash_oid
         ));
+        Ok(stash_oid)
+    }
 
-        // Fast path for whole-hunk unstage requests.
-        if desired.is_empty() && reset_filtered.len() == all_change_indices.len() {
-            if let Err(full_unstage_err) = self.unstage_hunk(hunk, file_path) {
-                crate::logger::debug(format!(
-                    "full hunk unstage failed; aborting without fallback file={}: {}",
-                    file_path.display(),
-                    full_unstage_err
-                ));
-                return Err(anyhow::anyhow!(
-                    "Failed to unstage hunk in {}",
-                    file_path.display()
-                ));
-            } else {
-                return Ok(());
-            }
+    /// Record `oid` as the newest stash entry, like `git stash store`.
+    fn store_stash(&self, oid: git2::Oid, message: &str) -> Result<()> {
+        let output = self
+            .git_command()
+            .args(["stash", "store", "-m", message])
+            .arg(oid.to_string())
+            .output()
+            .context("Failed to run `git stash store`")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "git stash store failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
         }
+        Ok(())
+    }
 
-        // Reset staged state for requested lines and tolerate no-op reverse-patch failures.
-        let reset_order = Self::sort_indices_desc_by_position(hunk, &reset_filtered);
-        for idx in reset_order {
-            if let Err(e) = self.apply_single_line_patch_raw(hunk, idx, file_path, true) {
-                if !Self::is_noop_patch_apply_error(&e) {
-                    crate::logger::warn(format!(
-                        "reset reverse-apply failed at idx={} file={}: {}",
-                        idx,
-                        file_path.display(),
-                        e
-                    ));
-                    return Err(e);
+    /// Paths with unmerged index stages, with the conflict blocks currently in the working
+    /// tree. Regions written without diff3 markers get their base from the index stages.
+    pub fn conflicted_files(&self) -> Result<Vec<ConflictedFile>> {
+        let repo = self.open()?;
+        let index = repo.index()?;
+        if !index.has_conflicts() {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            let Some(entry) = conflict
+                .our
+                .as_ref()
+                .or(conflict.their.as_ref())
+                .or(conflict.ancestor.as_ref())
+            else {
+                continue;
+            };
+            let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+
+            let content = std::fs::read(self.repo_path.join(&path))
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default();
+            let mut regions = parse_conflict_regions(&content);
+
+            if regions.iter().any(|region| region.base.is_none()) {
+                if let (Some(base), Some(ours), Some(theirs)) =
+                    (&conflict.ancestor, &conflict.our, &conflict.their)
+                {
+                    match self.merge_with_base(&repo, base, ours, theirs) {
+                        Ok(merged) => Self::fill_conflict_bases(&mut regions, merged),
+                        Err(e) => crate::logger::debug(format!(
+                            "Failed to recover merge base for {}: {}",
+                            path.display(),
+                            e
+                        )),
+                    }
                 }
-                crate::logger::trace(format!(
-                    "reset reverse-apply no-op at idx={} file={}",
-                    idx,
-                    file_path.display()
-                ));
             }
-        }
 
-        if desired.is_empty() {
-            return Ok(());
+            files.push(ConflictedFile {
+                path,
+                has_base: conflict.ancestor.is_some(),
+                has_ours: conflict.our.is_some(),
+                has_theirs: conflict.their.is_some(),
+                regions,
+            });
         }
 
-        if desired.len() == all_change_indices.len() {
-            // Prefer atomic hunk stage; avoid per-line fallback for full-hunk requests
-            // because it can drift/duplicate in partial-index states.
-            if let Err(stage_err) = self.stage_hunk(hunk, file_path) {
-                crate::logger::debug(format!(
-                    "full hunk stage failed in set_hunk_staged_lines_with_reset; aborting without fallback file={}: {}",
-                    file_path.display(),
-                    stage_err
-                ));
-                return Err(anyhow::anyhow!(
-                    "Failed to fully stage hunk in {}",
-                    file_path.display()
-                ));
-            }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
 
-            return Ok(());
+    /// Attach base lines to regions that match a region of the diff3 merge on both sides.
+    /// Regions the user already edited simply keep no base.
+    fn fill_conflict_bases(regions: &mut [ConflictRegion], mut merged: Vec<ConflictRegion>) {
+        for region in regions.iter_mut().filter(|region| region.base.is_none()) {
+            if let Some(pos) = merged
+                .iter()
+                .position(|m| m.ours == region.ours && m.theirs == region.theirs)
+            {
+                region.base = merged.remove(pos).base;
+            }
         }
+    }
 
-        let stage_order = Self::sort_indices_desc_by_position(hunk, &desired);
-        for idx in stage_order {
-            self.apply_single_line_patch_raw(hunk, idx, file_path, false)?;
+    /// Re-run the file merge of the three index stages with diff3 markers and return its
+    /// conflict regions. libgit2 writes the merged file into a scratch directory outside the
+    /// repository, which is removed again afterwards.
+    fn merge_with_base(
+        &self,
+        repo: &Repository,
+        base: &git2::IndexEntry,
+        ours: &git2::IndexEntry,
+        theirs: &git2::IndexEntry,
+    ) -> Result<Vec<ConflictRegion>> {
+        // The entries keep their stage in their flags, so this index holds just the conflict
+        let mut index = git2::Index::new()?;
+        for entry in [base, ours, theirs] {
+            index.add(entry)?;
         }
 
-        Ok(())
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let scratch =
+            std::env::temp_dir().join(format!("hunky-merge-{}-{}", std::process::id(), unique));
+        std::fs::create_dir_all(&scratch)?;
+        let path = PathBuf::from(String::from_utf8_lossy(&ours.path).into_owned());
+        let merged = repo
+            .checkout_index(
+                Some(&mut index),
+                Some(
+                    git2::build::CheckoutBuilder::new()
+                        .target_dir(&scratch)
+                        .force()
+                        .conflict_style_diff3(true)
+                        .ancestor_label("base")
+                        .our_label("ours")
+                        .their_label("theirs"),
+                ),
+            )
+            .context("Failed to merge the conflict stages")
+            .and_then(|()| {
+                std::fs::read(scratch.join(&path)).context("Failed to read the merged file")
+            });
+        let _ = std::fs::remove_dir_all(&scratch);
+
+        Ok(parse_conflict_regions(&String::from_utf8_lossy(&merged?)))
     }
 
-    #[allow(dead_code)]
-    fn set_hunk_staged_lines(
+    /// Replace conflict region `region_index` of `path` in the working tree with the chosen
+    /// side(s). The file stays unmerged until it is marked resolved.
+    pub fn resolve_conflict_region(
         &self,
-        hunk: &Hunk,
-        file_path: &Path,
-        desired_staged_indices: &std::collections::HashSet<usize>,
+        path: &Path,
+        region_index: usize,
+        choice: ConflictChoice,
     ) -> Result<()> {
-        let currently_staged = self.detect_staged_lines(hunk, file_path)?;
-        self.set_hunk_staged_lines_with_reset(
-            hunk,
-            file_path,
-            desired_staged_indices,
-            &currently_staged,
-        )
+        let full_path = self.repo_path.join(path);
+        let content = std::fs::read_to_string(&full_path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let regions = parse_conflict_regions(&content);
+        let region = regions
+            .get(region_index)
+            .context("The conflict region no longer exists")?;
+
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let mut resolved: Vec<String> = lines[..region.start]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        resolved.extend(region.resolution(choice));
+        resolved.extend(lines[region.end..].iter().map(|line| line.to_string()));
+        std::fs::write(&full_path, resolved.concat())?;
+        Ok(())
     }
 
-    pub fn toggle_hunk_staging(&self, hunk: &Hunk, file_path: &Path) -> Result<bool> {
-        // Returns true if final state is staged, false if final state is unstaged.
-        let currently_staged = self.detect_staged_lines(hunk, file_path)?;
-        let all_change_indices = Self::change_line_indices(hunk);
-
-        // Hunk-mode `s` behavior:
-        // - fully staged hunk => unstage hunk
-        // - partially/unstaged hunk => stage remaining lines
-        if currently_staged.len() < all_change_indices.len() {
-            // Partial hunk: safely stage remaining unstaged changes only when this
-            // file has a single unstaged hunk (equivalent to "stage the rest").
-            if self.unstaged_hunk_count_for_file(file_path)? == 1 {
-                self.stage_file(file_path)?;
-                return Ok(true);
-            }
-
-            // Multiple unstaged hunks in file: best-effort stage only the remaining
-            // lines in the selected hunk. Use both detected staged lines and UI hints
-            // to avoid reapplying already-staged lines.
-            let mut staged_known = currently_staged.clone();
-            for idx in &hunk.staged_line_indices {
-                if all_change_indices.contains(idx) {
-                    staged_known.insert(*idx);
-                }
-            }
-
-            let mut remaining: std::collections::HashSet<usize> = all_change_indices
-                .difference(&staged_known)
-                .copied()
-                .collect();
-
-            // Run up to two passes to absorb coordinate drift after first apply set.
-            for _ in 0..2 {
-                if remaining.is_empty() {
-                    return Ok(true);
-                }
-
-                let stage_order = Self::sort_indices_desc_by_position(hunk, &remaining);
-                let mut progress = false;
-                for idx in stage_order {
-                    match self.apply_single_line_patch_raw(hunk, idx, file_path, false) {
-                        Ok(_) => progress = true,
-                        Err(e) if Self::is_noop_patch_apply_error(&e) => {
-                            crate::logger::trace(format!(
-                                "toggle stage remaining no-op at idx={} file={}",
-                                idx,
-                                file_path.display()
-                            ));
-                        }
-                        Err(e) => return Err(e),
-                    }
-                }
-
-                let staged_after = self.detect_staged_lines(hunk, file_path)?;
-                remaining = all_change_indices
-                    .difference(&staged_after)
-                    .copied()
-                    .collect();
-
-                if !progress {
-                    break;
-                }
+    /// Resolve a whole file to one side's version from the index, deleting it when that
+    /// side deleted it, and mark it resolved.
+    pub fn take_conflict_side(&self, path: &Path, choice: ConflictChoice) -> Result<()> {
+        let repo = self.open()?;
+        let stage = match choice {
+            ConflictChoice::Ours => 2,
+            ConflictChoice::Theirs => 3,
+            ConflictChoice::Both => {
+                return Err(anyhow::anyhow!(
+                    "Pick ours or theirs for {}; there are no regions to combine",
+                    path.display()
+                ))
             }
+        };
 
-            return Err(anyhow::anyhow!(
-                "Cannot safely complete partial hunk staging in {} (multiple unstaged hunks)",
-                file_path.display()
-            ));
+        let full_path = self.repo_path.join(path);
+        match repo.index()?.get_path(path, stage) {
+            Some(entry) => std::fs::write(&full_path, repo.find_blob(entry.id)?.content())?,
+            None if full_path.exists() => std::fs::remove_file(&full_path)?,
+            None => {}
         }
+        self.mark_conflict_resolved(path)
+    }
 
-        // Fully staged hunk => unstage full hunk.
-        match self.unstage_hunk(hunk, file_path) {
-            Ok(_) => Ok(false),
-            Err(e) => {
-                crate::logger::debug(format!(
-                    "full hunk unstage failed in toggle; aborting without fallback file={}: {}",
-                    file_path.display(),
-                    e
+    /// Stage the working tree version of a conflicted path, or its deletion, which clears
+    /// the unmerged stages. Refuses while conflict markers remain.
+    pub fn mark_conflict_resolved(&self, path: &Path) -> Result<()> {
+        let repo = self.open()?;
+        let mut index = repo.index()?;
+        let full_path = self.repo_path.join(path);
+
+        if full_path.exists() {
+            let content = std::fs::read(&full_path)?;
+            let remaining = parse_conflict_regions(&String::from_utf8_lossy(&content)).len();
+            if remaining > 0 {
+                return Err(anyhow::anyhow!(
+                    "{} still has {} unresolved conflict{}",
+                    path.display(),
+                    remaining,
+                    if remaining == 1 { "" } else { "s" }
                 ));
-                Err(anyhow::anyhow!(
-                    "Failed to unstage hunk in {}",
-                    file_path.display()
-                ))
             }
+            index.add_path(path)?;
+        } else {
+            index.remove_path(path)?;
         }
+        index.write()?;
+        Ok(())
     }
 
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let repo_path = Repository::discover(path.as_ref())
-            .context("Failed to find git repository")?
-            .workdir()
-            .context("Repository has no working directory")?
-            .to_path_buf();
-
-        Ok(Self { repo_path })
-    }
-
-    pub fn repo_path(&self) -> &Path {
-        &self.repo_path
+    /// Path of the scratch file used to edit hunks, inside the git directory.
+    pub fn hunk_edit_file(&self) -> Result<PathBuf> {
+        let repo = self.open()?;
+        Ok(repo.path().join("HUNKY_EDIT_HUNK.diff"))
     }
 
-    /// Run `git commit` interactively, allowing Git to launch the configured editor.
-    pub fn commit_with_editor(&self) -> Result<std::process::ExitStatus> {
+    /// Open `path` in the editor Git would use (`GIT_EDITOR`, `core.editor`, `VISUAL`,
+    /// `EDITOR`, then `vi`).
+    pub fn edit_file_with_editor(&self, path: &Path) -> Result<std::process::ExitStatus> {
         use std::process::Command;
 
-        let status = Command::new("git")
-            .arg("commit")
-            .current_dir(&self.repo_path)
-            .status()
-            .context("Failed to run `git commit`")?;
+        let output = self
+            .git_command()
+            .args(["var", "GIT_EDITOR"])
+            .output()
+            .context("Failed to run `git var GIT_EDITOR`")?;
+        let editor = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let editor = if editor.is_empty() {
+            "vi".to_string()
+        } else {
+            editor
+        };
 
-        Ok(status)
+        // Like Git, let the shell interpret the editor so it may carry arguments.
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(path);
+        self.run_in_terminal(command, &format!("editor `{}`", editor))
     }
 
     /// Get a list of recent commits (up to `count`) for the commit review picker.
     pub fn get_recent_commits(&self, count: usize) -> Result<Vec<CommitInfo>> {
-        let repo = Repository::open(&self.repo_path)?;
+        let repo = self.open()?;
+        // An unborn branch simply has no history yet
+        if let Err(e) = repo.head() {
+            if e.code() == git2::ErrorCode::UnbornBranch {
+                return Ok(Vec::new());
+            }
+        }
         let mut revwalk = repo.revwalk()?;
         revwalk.push_head().context("No commits found")?;
         revwalk.set_sorting(git2::Sort::TIME)?;
 
         let mut commits = Vec::new();
         for oid in revwalk.take(count) {
-            let oid = oid?;
-            let commit = repo.find_commit(oid)?;
-            let sha = oid.to_string();
-            let short_sha = sha[..7.min(sha.len())].to_string();
-            let summary = commit.summary().unwrap_or("").to_string();
-            let author = commit.author().name().unwrap_or("unknown").to_string();
-            commits.push(CommitInfo {
-                sha,
-                short_sha,
-                summary,
-                author,
-            });
+            let commit = repo.find_commit(oid?)?;
+            commits.push(Self::commit_info(&commit));
         }
 
         Ok(commits)
     }
 
+    /// Commits whose parents were cut off by a shallow clone, from `.git/shallow`
+    fn shallow_boundary(repo: &Repository) -> HashSet<git2::Oid> {
+        if !repo.is_shallow() {
+            return HashSet::new();
+        }
+        std::fs::read_to_string(Self::common_dir(repo).join("shallow"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| git2::Oid::from_str(line.trim()).ok())
+            .collect()
+    }
+
+    /// The branch an unborn HEAD names, read from HEAD's symbolic target
+    fn unborn_branch_name(repo: &Repository) -> Option<String> {
+        let head = repo.find_reference("HEAD").ok()?;
+        let target = head.symbolic_target()?;
+        Some(
+            target
+                .strip_prefix("refs/heads/")
+                .unwrap_or(target)
+                .to_string(),
+        )
+    }
+
+    fn commit_info(commit: &git2::Commit) -> CommitInfo {
+        let sha = commit.id().to_string();
+        CommitInfo {
+            short_sha: sha[..7.min(sha.len())].to_string(),
+            sha,
+            summary: commit.summary().unwrap_or("").to_string(),
+            author: commit.author().name().unwrap_or("unknown").to_string(),
+        }
+    }
+
     /// Get a DiffSnapshot for a specific commit (diff between commit's parent and the commit).
     pub fn get_commit_diff(&self, commit_sha: &str) -> Result<DiffSnapshot> {
-        let repo = Repository::open(&self.repo_path)?;
+        let repo = self.open()?;
         let oid = git2::Oid::from_str(commit_sha).context("Invalid commit SHA")?;
         let commit = repo.find_commit(oid)?;
         let commit_tree = commit.tree()?;
+        let short_sha = &commit_sha[..7.min(commit_sha.len())];
 
+        // Diffing a shallow clone's oldest commit against nothing would show every file
+        // as added, so say what is missing instead
+        if Self::shallow_boundary(&repo).contains(&oid) {
+            return Err(anyhow::anyhow!(
+                "{} is the oldest commit in this shallow clone; its parent was not fetched \
+                 (git fetch --deepen=1)",
+                short_sha
+            ));
+        }
         let parent_tree = if commit.parent_count() > 0 {
-            Some(commit.parent(0)?.tree()?)
+            let parent = commit
+                .parent(0)
+                .and_then(|parent| parent.tree())
+                .with_context(|| format!("The parent of {} is not available", short_sha))?;
+            Some(parent)
         } else {
             None
         };
@@ -551,10 +1745,39 @@ impl GitRepo {
 
         Ok(DiffSnapshot {
             timestamp: std::time::SystemTime::now(),
+            head: None,
             files,
+            untracked_overflow: 0,
         })
     }
 
+    /// The changes a stash entry records, like [`Self::get_commit_diff`], plus the untracked
+    /// files `git stash -u` keeps in a third parent, listed as `Untracked`.
+    pub fn get_stash_diff(&self, stash_sha: &str) -> Result<DiffSnapshot> {
+        let mut snapshot = self.get_commit_diff(stash_sha)?;
+        let repo = self.open()?;
+        let oid = git2::Oid::from_str(stash_sha).context("Invalid commit SHA")?;
+        let Ok(untracked) = repo.find_commit(oid)?.parent(2) else {
+            return Ok(snapshot);
+        };
+        let untracked_tree = untracked.tree()?;
+        let diff = repo.diff_tree_to_tree(None, Some(&untracked_tree), None)?;
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            let hunks = self
+                .get_commit_file_hunks(&repo, path, None, &untracked_tree)
+                .unwrap_or_default();
+            snapshot.files.push(FileChange {
+                path: path.to_path_buf(),
+                status: "Untracked".to_string(),
+                hunks,
+            });
+        }
+        Ok(snapshot)
+    }
+
     /// Get hunks for a file from a commit diff (parent tree vs commit tree).
     fn get_commit_file_hunks(
         &self,
@@ -619,26 +1842,272 @@ impl GitRepo {
             }),
         )?;
 
-        if *in_hunk.borrow() && !current_hunk_lines.borrow().is_empty() {
-            hunks.borrow_mut().push(Hunk::new(
-                *current_old_start.borrow(),
-                *current_new_start.borrow(),
-                current_hunk_lines.borrow().clone(),
-                &path_buf,
+        if *in_hunk.borrow() && !current_hunk_lines.borrow().is_empty() {
+            hunks.borrow_mut().push(Hunk::new(
+                *current_old_start.borrow(),
+                *current_new_start.borrow(),
+                current_hunk_lines.borrow().clone(),
+                &path_buf,
+            ));
+        }
+
+        let result = hunks.borrow().clone();
+        Ok(result)
+    }
+
+    pub fn get_diff_snapshot(&self) -> Result<DiffSnapshot> {
+        let repo = self.open()?;
+        let head = Self::head_oid(&repo);
+        let (mut files, mut untracked_overflow) = self.collect_file_changes(&repo, &[])?;
+
+        // Changes inside submodules are listed under the submodule path
+        if self.recurses_submodules() {
+            let (submodule_files, submodule_overflow) = self.submodule_files(&repo)?;
+            files.extend(submodule_files);
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            untracked_overflow += submodule_overflow;
+        }
+
+        Ok(DiffSnapshot {
+            timestamp: std::time::SystemTime::now(),
+            head,
+            files,
+            untracked_overflow,
+        })
+    }
+
+    /// Bring `previous` up to date by re-diffing only `changed_paths` (relative to the
+    /// working tree) and keeping every other file as it was. Falls back to a full snapshot
+    /// whenever a partial diff could miss something: collapsed or hidden untracked files,
+    /// submodules, directories, or an untracked file cap that was or would be reached.
+    pub fn update_snapshot(
+        &self,
+        previous: &DiffSnapshot,
+        changed_paths: &[PathBuf],
+    ) -> Result<DiffSnapshot> {
+        let repo = self.open()?;
+        let incremental = !changed_paths.is_empty()
+            && changed_paths.len() <= INCREMENTAL_PATH_LIMIT
+            && previous.untracked_overflow == 0
+            && self.untracked_mode() == UntrackedMode::Show
+            && !self.recurses_submodules()
+            && repo.submodules().map(|s| s.is_empty()).unwrap_or(false)
+            && !changed_paths
+                .iter()
+                .any(|path| self.repo_path.join(path).is_dir());
+        if !incremental {
+            return self.get_diff_snapshot();
+        }
+
+        // A removed directory takes the files previously listed under it along
+        let mut paths: Vec<PathBuf> = changed_paths.to_vec();
+        for file in &previous.files {
+            if !paths.contains(&file.path)
+                && changed_paths.iter().any(|path| file.path.starts_with(path))
+            {
+                paths.push(file.path.clone());
+            }
+        }
+
+        let (changed_files, _) = self.collect_file_changes(&repo, &paths)?;
+        let mut files: Vec<FileChange> = previous
+            .files
+            .iter()
+            .filter(|file| !paths.contains(&file.path))
+            .cloned()
+            .chain(changed_files)
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let untracked_count = files.iter().filter(|f| f.status == "Untracked").count();
+        if untracked_count > UNTRACKED_FILE_LIMIT {
+            return self.get_diff_snapshot();
+        }
+
+        Ok(DiffSnapshot {
+            timestamp: std::time::SystemTime::now(),
+            head: Self::head_oid(&repo),
+            files,
+            untracked_overflow: 0,
+        })
+    }
+
+    fn head_oid(repo: &Repository) -> Option<String> {
+        repo.head()
+            .ok()
+            .and_then(|head| head.target())
+            .map(|oid| oid.to_string())
+    }
+
+    /// A file's content at `commit`, or None when it did not exist there
+    fn blob_at(repo: &Repository, commit: Option<&str>, path: &Path) -> Result<Option<Vec<u8>>> {
+        let Some(commit) = commit else {
+            return Ok(None);
+        };
+        let tree = repo.find_commit(git2::Oid::from_str(commit)?)?.tree()?;
+        match tree.get_path(path) {
+            Ok(entry) => Ok(Some(repo.find_blob(entry.id())?.content().to_vec())),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// A file's working tree content as `snapshot` saw it: the file at the commit the
+    /// snapshot was taken against with the snapshot's hunks applied. None when the file did
+    /// not exist; an error when the snapshot does not hold its content, as for binary or
+    /// oversized files.
+    fn snapshot_content(
+        repo: &Repository,
+        snapshot: &DiffSnapshot,
+        path: &Path,
+    ) -> Result<Option<Vec<u8>>> {
+        let base = Self::blob_at(repo, snapshot.head.as_deref(), path)?;
+        let Some(file) = snapshot.files.iter().find(|file| file.path == path) else {
+            return Ok(base);
+        };
+        if file.status == "Deleted" {
+            return Ok(None);
+        }
+        if file.hunks.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} has no recorded content",
+                path.display()
             ));
         }
 
-        let result = hunks.borrow().clone();
-        Ok(result)
+        // Apply the hunks bottom-up so earlier positions hold
+        let base = base.unwrap_or_default();
+        let mut lines: Vec<Vec<u8>> = Self::split_blob_lines(&base)
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect();
+        let mut hunks: Vec<&Hunk> = file.hunks.iter().collect();
+        hunks.sort_by_key(|hunk| std::cmp::Reverse(hunk.old_start));
+        for hunk in hunks {
+            let old_count = Self::hunk_old_line_count(hunk);
+            let begin = if old_count == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let end = begin + old_count;
+            let old_side = Self::patch_side(&hunk.lines, '-');
+            if end > lines.len()
+                || lines[begin..end]
+                    .iter()
+                    .zip(&old_side)
+                    .any(|(line, expected)| line != expected.as_bytes())
+            {
+                return Err(anyhow::anyhow!(
+                    "{} does not match the commit the snapshot was taken against",
+                    path.display()
+                ));
+            }
+            let new_side = Self::patch_side(&hunk.lines, '+')
+                .into_iter()
+                .map(String::into_bytes);
+            lines.splice(begin..end, new_side);
+        }
+        Ok(Some(lines.concat()))
     }
 
-    pub fn get_diff_snapshot(&self) -> Result<DiffSnapshot> {
-        let repo = Repository::open(&self.repo_path)?;
+    /// What changed in the working tree between two snapshots, as a snapshot of its own:
+    /// each file's content in `from` is diffed against its content in `to`. Files whose
+    /// content either snapshot lacks are listed without hunks.
+    pub fn diff_snapshots(&self, from: &DiffSnapshot, to: &DiffSnapshot) -> Result<DiffSnapshot> {
+        let repo = self.open()?;
+
+        let mut paths: Vec<PathBuf> = from
+            .files
+            .iter()
+            .chain(&to.files)
+            .map(|file| file.path.clone())
+            .collect();
+        // A commit in between changes files neither snapshot lists
+        if let (Some(a), Some(b)) = (&from.head, &to.head) {
+            if a != b {
+                let a = repo.find_commit(git2::Oid::from_str(a)?)?.tree()?;
+                let b = repo.find_commit(git2::Oid::from_str(b)?)?.tree()?;
+                let diff = repo.diff_tree_to_tree(Some(&a), Some(&b), None)?;
+                paths.extend(
+                    diff.deltas()
+                        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+                        .map(Path::to_path_buf),
+                );
+            }
+        }
+        paths.sort();
+        paths.dedup();
+
+        let mut files = Vec::new();
+        for path in paths {
+            let old = Self::snapshot_content(&repo, from, &path);
+            let new = Self::snapshot_content(&repo, to, &path);
+            let (old, new) = match (old, new) {
+                (Ok(old), Ok(new)) => (old, new),
+                (old, new) => {
+                    // Only worth listing when something about the file changed
+                    if old.ok() != new.ok() {
+                        files.push(FileChange {
+                            path,
+                            status: "Modified".to_string(),
+                            hunks: Vec::new(),
+                        });
+                    }
+                    continue;
+                }
+            };
+            if old == new {
+                continue;
+            }
+            let status = match (&old, &new) {
+                (None, _) => "Added",
+                (_, None) => "Deleted",
+                _ => "Modified",
+            };
+            let old = old.unwrap_or_default();
+            let new = new.unwrap_or_default();
+            let hunks = match (std::str::from_utf8(&old), std::str::from_utf8(&new)) {
+                (Ok(old), Ok(new)) => content_hunks(old, new, &path),
+                // Binary content is listed without hunks
+                _ => Vec::new(),
+            };
+            files.push(FileChange {
+                path,
+                status: status.to_string(),
+                hunks,
+            });
+        }
+
+        Ok(DiffSnapshot {
+            timestamp: to.timestamp,
+            head: to.head.clone(),
+            files,
+            untracked_overflow: 0,
+        })
+    }
 
+    /// Diff HEAD against the working tree, limited to `paths` unless it is empty. Tracked
+    /// files get their hunks from the same pass; untracked files are read one by one so
+    /// oversized ones can be skipped.
+    fn collect_file_changes(
+        &self,
+        repo: &Repository,
+        paths: &[PathBuf],
+    ) -> Result<(Vec<FileChange>, usize)> {
         // Get the diff between HEAD and working directory (includes both staged and unstaged)
+        let untracked_mode = self.untracked_mode();
         let mut diff_opts = DiffOptions::new();
-        diff_opts.include_untracked(true);
-        diff_opts.recurse_untracked_dirs(true);
+        diff_opts.include_untracked(untracked_mode != UntrackedMode::Hide);
+        // Without recursion, libgit2 reports a wholly untracked directory as one entry
+        diff_opts.recurse_untracked_dirs(untracked_mode == UntrackedMode::Show);
+        diff_opts.context_lines(3);
+        if !paths.is_empty() {
+            for path in paths {
+                diff_opts.pathspec(path);
+            }
+            diff_opts.disable_pathspec_match(true);
+        }
 
         // Get HEAD tree (handle empty repo case)
         let head_tree = match repo.head() {
@@ -650,48 +2119,129 @@ impl GitRepo {
         let diff =
             repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut diff_opts))?;
 
-        let mut files = Vec::new();
+        // Conflicted paths are shown in the conflict view, not as hunks with raw markers
+        let conflicted: HashSet<PathBuf> = repo
+            .index()?
+            .conflicts()?
+            .filter_map(|conflict| {
+                let conflict = conflict.ok()?;
+                let entry = conflict.our.or(conflict.their).or(conflict.ancestor)?;
+                Some(PathBuf::from(
+                    String::from_utf8_lossy(&entry.path).into_owned(),
+                ))
+            })
+            .collect();
+
+        use std::cell::RefCell;
+
+        // The file the hunk and line callbacks belong to, if it is being kept
+        struct Collected {
+            files: Vec<FileChange>,
+            current: Option<usize>,
+            hunk: Option<(usize, usize, Vec<String>)>,
+        }
+        impl Collected {
+            fn finish_hunk(&mut self) {
+                if let (Some(file_idx), Some((old_start, new_start, lines))) =
+                    (self.current, self.hunk.take())
+                {
+                    if !lines.is_empty() {
+                        let file = &mut self.files[file_idx];
+                        let hunk = Hunk::new(old_start, new_start, lines, &file.path);
+                        file.hunks.push(hunk);
+                    }
+                }
+            }
+        }
+
+        let collected = RefCell::new(Collected {
+            files: Vec::new(),
+            current: None,
+            hunk: None,
+        });
+        let mut untracked_count = 0;
+        let mut untracked_overflow = 0;
 
         diff.foreach(
             &mut |delta, _progress| {
+                let mut collected = collected.borrow_mut();
+                collected.finish_hunk();
+                collected.current = None;
+
                 let file_path = match delta.status() {
                     Delta::Added | Delta::Modified | Delta::Deleted => {
                         delta.new_file().path().or_else(|| delta.old_file().path())
                     }
+                    Delta::Untracked if untracked_count >= UNTRACKED_FILE_LIMIT => {
+                        untracked_overflow += 1;
+                        None
+                    }
+                    Delta::Untracked => {
+                        untracked_count += 1;
+                        delta.new_file().path()
+                    }
                     _ => None,
                 };
 
-                if let Some(path) = file_path {
-                    files.push(FileChange {
+                if let Some(path) = file_path.filter(|path| !conflicted.contains(*path)) {
+                    collected.files.push(FileChange {
                         path: path.to_path_buf(),
                         status: format!("{:?}", delta.status()),
                         hunks: Vec::new(),
                     });
+                    collected.current = Some(collected.files.len() - 1);
                 }
                 true
             },
             None,
-            None,
-            None,
+            Some(&mut |_, hunk| {
+                let mut collected = collected.borrow_mut();
+                collected.finish_hunk();
+                collected.hunk = Some((
+                    hunk.old_start() as usize,
+                    hunk.new_start() as usize,
+                    Vec::new(),
+                ));
+                true
+            }),
+            Some(&mut |_, _, line| {
+                if let Some((_, _, lines)) = collected.borrow_mut().hunk.as_mut() {
+                    let content = String::from_utf8_lossy(line.content()).to_string();
+                    lines.push(format!("{}{}", line.origin(), content));
+                }
+                true
+            }),
         )?;
+        collected.borrow_mut().finish_hunk();
+        let mut files = collected.into_inner().files;
 
-        // Now get the actual diff content for each file
+        // Untracked content was left out of the pass above
         for file in &mut files {
-            if let Ok(hunks) = self.get_file_hunks(&repo, &file.path) {
+            if file.status != "Untracked" || !self.shows_untracked_content(&file.path) {
+                continue;
+            }
+            if let Ok(hunks) = self.get_file_hunks(repo, &file.path) {
                 file.hunks = hunks;
             }
         }
 
-        Ok(DiffSnapshot {
-            timestamp: std::time::SystemTime::now(),
-            files,
-        })
+        Ok((files, untracked_overflow))
+    }
+
+    /// Collapsed directories and files over the size cap are listed without content
+    fn shows_untracked_content(&self, path: &Path) -> bool {
+        std::fs::metadata(self.repo_path.join(path))
+            .map(|meta| meta.is_file() && meta.len() <= UNTRACKED_MAX_FILE_SIZE)
+            .unwrap_or(false)
     }
 
     fn get_file_hunks(&self, repo: &Repository, path: &Path) -> Result<Vec<Hunk>> {
         let mut diff_opts = DiffOptions::new();
         diff_opts.pathspec(path);
         diff_opts.context_lines(3);
+        // Untracked files show their whole content as added lines
+        diff_opts.include_untracked(true);
+        diff_opts.show_untracked_content(true);
 
         // Get HEAD tree (handle empty repo case)
         let head_tree = match repo.head() {
@@ -772,96 +2322,110 @@ impl GitRepo {
         Ok(result)
     }
 
-    /// Stage an entire file
+    /// Stage an entire file, or its deletion when it is gone from the working tree
     pub fn stage_file(&self, file_path: &Path) -> Result<()> {
-        let repo = Repository::open(&self.repo_path)?;
+        if let Some((submodule, path)) = self.submodule_for(file_path) {
+            return submodule.stage_file(&path);
+        }
+        let repo = self.open()?;
         let mut index = repo.index()?;
-        index.add_path(file_path)?;
+        if self.repo_path.join(file_path).exists() {
+            index.add_path(file_path)?;
+        } else {
+            index.remove_path(file_path)?;
+        }
         index.write()?;
         Ok(())
     }
 
-    /// Stage a specific hunk by applying it as a patch
-    pub fn stage_hunk(&self, hunk: &Hunk, file_path: &Path) -> Result<()> {
-        use std::io::Write;
-        use std::process::Command;
-
-        // Create a proper unified diff patch
-        let mut patch = String::new();
-
-        // Diff header
-        patch.push_str(&format!(
-            "diff --git a/{} b/{}\n",
-            file_path.display(),
-            file_path.display()
-        ));
-        patch.push_str(&format!("--- a/{}\n", file_path.display()));
-        patch.push_str(&format!("+++ b/{}\n", file_path.display()));
-
-        // Count actual add/remove lines for the hunk header
-        let mut old_lines = 0;
-        let mut new_lines = 0;
-        for line in &hunk.lines {
-            if line.starts_with('-') && !line.starts_with("---") {
-                old_lines += 1;
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                new_lines += 1;
-            } else if line.starts_with(' ') {
-                old_lines += 1;
-                new_lines += 1;
-            }
+    /// Record an untracked file in the index without content, like `git add -N`, so it
+    /// shows up as a new file whose lines can be staged piece by piece.
+    pub fn intent_to_add(&self, file_path: &Path) -> Result<()> {
+        if let Some((submodule, path)) = self.submodule_for(file_path) {
+            return submodule.intent_to_add(&path);
         }
-
-        // Hunk header
-        patch.push_str(&format!(
-            "@@ -{},{} +{},{} @@\n",
-            hunk.old_start, old_lines, hunk.new_start, new_lines
-        ));
-
-        // Hunk content
-        for line in &hunk.lines {
-            patch.push_str(line);
-            if !line.ends_with('\n') {
-                patch.push('\n');
-            }
+        let repo = self.open()?;
+        let mut index = repo.index()?;
+        if index.get_path(file_path, 0).is_some() {
+            return Err(anyhow::anyhow!(
+                "{} is already in the index",
+                file_path.display()
+            ));
         }
 
-        // Use git apply to stage the hunk
-        let mut child = Command::new("git")
-            .arg("apply")
-            .arg("--cached")
-            .arg("--unidiff-zero")
-            .arg("-")
-            .current_dir(&self.repo_path)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()?;
+        let mut entry = self.new_index_entry(&repo, file_path)?;
+        entry.id = repo.blob(&[])?;
+        entry.flags_extended |= git2::IndexEntryExtendedFlag::INTENT_TO_ADD.bits();
+        index.add(&entry)?;
+        index.write()?;
+        Ok(())
+    }
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(patch.as_bytes())?;
-        }
+    /// Stage a specific hunk by writing its worktree side into the index
+    pub fn stage_hunk(&self, hunk: &Hunk, file_path: &Path) -> Result<()> {
+        let all_change_indices = Self::change_line_indices(hunk);
+        self.write_hunk_selection(hunk, file_path, &all_change_indices, None)
+            .map_err(|e| anyhow::anyhow!("Failed to stage hunk: {}", e))
+    }
 
-        let output = child.wait_with_output()?;
+    /// Detect which lines in a hunk are currently staged in the index
+    /// Returns a HashSet of line indices that are staged
+    pub fn detect_staged_lines(&self, hunk: &Hunk, file_path: &Path) -> Result<HashSet<usize>> {
+        let staged = self.staged_changes(&[file_path])?;
+        Ok(staged.staged_lines(hunk, file_path).unwrap_or_default())
+    }
 
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to stage hunk: {}", error_msg));
+    /// Read what is staged for all of `paths` with one HEAD→index and one index→worktree
+    /// diff, plus one pair per submodule that owns some of them.
+    pub fn staged_changes(&self, paths: &[&Path]) -> Result<StagedChanges> {
+        let submodule_paths = self.submodule_paths();
+        let mut own_paths = Vec::new();
+        let mut submodule_files: Vec<(&Path, Vec<&Path>)> = Vec::new();
+        for &path in paths {
+            let owner = submodule_paths.iter().find_map(|submodule_path| {
+                Some((
+                    submodule_path.as_path(),
+                    Self::path_in_submodule(path, submodule_path)?,
+                ))
+            });
+            match owner {
+                Some((submodule_path, rel_path)) => {
+                    match submodule_files
+                        .iter_mut()
+                        .find(|(owner_path, _)| *owner_path == submodule_path)
+                    {
+                        Some((_, rel_paths)) => rel_paths.push(rel_path),
+                        None => submodule_files.push((submodule_path, vec![rel_path])),
+                    }
+                }
+                None => own_paths.push(path),
+            }
         }
 
-        Ok(())
+        let mut staged = self.own_staged_changes(&own_paths)?;
+        for (submodule_path, rel_paths) in submodule_files {
+            // Files of a submodule that cannot be read stay unknown to the caller
+            let Some(submodule) = self.open_submodule(submodule_path) else {
+                continue;
+            };
+            if let Ok(submodule_staged) = submodule.staged_changes(&rel_paths) {
+                staged.absorb_submodule(submodule_path, submodule_staged);
+            }
+        }
+        Ok(staged)
     }
 
-    /// Detect which lines in a hunk are currently staged in the index
-    /// Returns a HashSet of line indices that are staged
-    pub fn detect_staged_lines(
-        &self,
-        hunk: &Hunk,
-        file_path: &Path,
-    ) -> Result<std::collections::HashSet<usize>> {
-        use std::collections::HashSet;
-
-        let repo = Repository::open(&self.repo_path)?;
+    /// `staged_changes` for paths of this repository's own index
+    fn own_staged_changes(&self, paths: &[&Path]) -> Result<StagedChanges> {
+        let mut staged = StagedChanges {
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+            ..StagedChanges::default()
+        };
+        // An empty pathspec would diff everything
+        if paths.is_empty() {
+            return Ok(staged);
+        }
+        let repo = self.open()?;
 
         // Get diff from HEAD to index (only staged changes)
         let head_tree = match repo.head() {
@@ -870,36 +2434,46 @@ impl GitRepo {
         };
 
         let mut diff_opts = DiffOptions::new();
-        diff_opts.pathspec(file_path);
+        for path in paths {
+            diff_opts.pathspec(path);
+        }
+        diff_opts.disable_pathspec_match(true);
 
         let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_opts))?;
 
         let index = repo.index()?;
         let mut unstaged_opts = DiffOptions::new();
-        unstaged_opts.pathspec(file_path);
+        for path in paths {
+            unstaged_opts.pathspec(path);
+        }
+        // A new file with nothing staged has no index entry; all of its lines are unstaged.
+        unstaged_opts
+            .disable_pathspec_match(true)
+            .include_untracked(true)
+            .show_untracked_content(true);
         let unstaged_diff = repo.diff_index_to_workdir(Some(&index), Some(&mut unstaged_opts))?;
 
-        let mut staged_lines = HashSet::new();
-
         // Track exact staged deletions by (HEAD old line number, content_without_prefix).
         // For additions we prefer deriving from unstaged additions (index->worktree) because
         // HEAD->index new line numbers can diverge from HEAD->worktree when unstaged changes
         // exist earlier in the same hunk.
-        let mut staged_deletions: HashSet<(usize, String)> = HashSet::new();
-        let mut unstaged_additions: HashSet<(usize, String)> = HashSet::new();
-
         diff.foreach(
             &mut |_, _| true,
             None,
             None,
-            Some(&mut |_, _, line| {
-                let content = String::from_utf8_lossy(line.content())
-                    .trim_end_matches('\n')
-                    .to_string();
-
+            Some(&mut |delta, _, line| {
                 if line.origin() == '-' {
-                    if let Some(old_lineno) = line.old_lineno() {
-                        staged_deletions.insert((old_lineno as usize, content));
+                    if let (Some(path), Some(old_lineno)) =
+                        (delta.old_file().path(), line.old_lineno())
+                    {
+                        let content = String::from_utf8_lossy(line.content())
+                            .trim_end_matches('\n')
+                            .to_string();
+                        staged
+                            .staged_deletions
+                            .entry(path.to_path_buf())
+                            .or_default()
+                            .insert((old_lineno as usize, content));
                     }
                 }
                 true
@@ -910,45 +2484,26 @@ impl GitRepo {
             &mut |_, _| true,
             None,
             None,
-            Some(&mut |_, _, line| {
+            Some(&mut |delta, _, line| {
                 if line.origin() == '+' {
-                    let content = String::from_utf8_lossy(line.content())
-                        .trim_end_matches('\n')
-                        .to_string();
-                    if let Some(new_lineno) = line.new_lineno() {
-                        unstaged_additions.insert((new_lineno as usize, content));
+                    if let (Some(path), Some(new_lineno)) =
+                        (delta.new_file().path(), line.new_lineno())
+                    {
+                        let content = String::from_utf8_lossy(line.content())
+                            .trim_end_matches('\n')
+                            .to_string();
+                        staged
+                            .unstaged_additions
+                            .entry(path.to_path_buf())
+                            .or_default()
+                            .insert((new_lineno as usize, content));
                     }
                 }
                 true
             }),
         )?;
 
-        // Walk the target hunk and compute exact old/new coordinates for each line,
-        // then check whether that exact change exists in the staged index diff.
-        let mut old_lineno = hunk.old_start;
-        let mut new_lineno = hunk.new_start;
-
-        for (hunk_idx, hunk_line) in hunk.lines.iter().enumerate() {
-            if hunk_line.starts_with(' ') {
-                old_lineno += 1;
-                new_lineno += 1;
-            } else if hunk_line.starts_with('-') && !hunk_line.starts_with("---") {
-                let content = hunk_line[1..].trim_end_matches('\n').to_string();
-                if staged_deletions.contains(&(old_lineno, content)) {
-                    staged_lines.insert(hunk_idx);
-                }
-                old_lineno += 1;
-            } else if hunk_line.starts_with('+') && !hunk_line.starts_with("+++") {
-                let content = hunk_line[1..].trim_end_matches('\n').to_string();
-                // '+' line is staged if it is NOT present as an unstaged worktree addition.
-                if !unstaged_additions.contains(&(new_lineno, content)) {
-                    staged_lines.insert(hunk_idx);
-                }
-                new_lineno += 1;
-            }
-        }
-
-        Ok(staged_lines)
+        Ok(staged)
     }
 
     /// Stage a single line from a hunk
@@ -962,7 +2517,7 @@ impl GitRepo {
             .lines
             .get(line_index)
             .ok_or_else(|| anyhow::anyhow!("Line index out of bounds"))?;
-        if !Self::is_change_line(selected_line) {
+        if !Hunk::is_change_line(selected_line) {
             return Err(anyhow::anyhow!("Can only stage + or - lines"));
         }
 
@@ -982,7 +2537,9 @@ impl GitRepo {
             line_index
         ));
 
-        self.apply_single_line_patch_raw(hunk, line_index, file_path, false)?;
+        let mut desired = currently_staged;
+        desired.insert(line_index);
+        self.set_hunk_staged_lines(hunk, file_path, &desired)?;
 
         let staged_after = self.detect_staged_lines(hunk, file_path)?;
         if !staged_after.contains(&line_index) {
@@ -1007,7 +2564,7 @@ impl GitRepo {
             .lines
             .get(line_index)
             .ok_or_else(|| anyhow::anyhow!("Line index out of bounds"))?;
-        if !Self::is_change_line(selected_line) {
+        if !Hunk::is_change_line(selected_line) {
             return Err(anyhow::anyhow!("Can only unstage + or - lines"));
         }
 
@@ -1027,7 +2584,9 @@ impl GitRepo {
             line_index
         ));
 
-        self.apply_single_line_patch_raw(hunk, line_index, file_path, true)?;
+        let mut desired = currently_staged;
+        desired.remove(&line_index);
+        self.set_hunk_staged_lines(hunk, file_path, &desired)?;
 
         let staged_after = self.detect_staged_lines(hunk, file_path)?;
         if staged_after.contains(&line_index) {
@@ -1043,94 +2602,231 @@ impl GitRepo {
 
     /// Unstage an entire file
     pub fn unstage_file(&self, file_path: &Path) -> Result<()> {
-        use std::process::Command;
-
-        let output = Command::new("git")
-            .arg("reset")
-            .arg("HEAD")
-            .arg("--")
-            .arg(file_path)
-            .current_dir(&self.repo_path)
-            .output()?;
-
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to unstage file: {}", error_msg));
+        if let Some((submodule, path)) = self.submodule_for(file_path) {
+            return submodule.unstage_file(&path);
         }
+        let repo = self.open()?;
+        let head_commit = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel(git2::ObjectType::Commit).ok());
+
+        // With an unborn HEAD this removes the path from the index entirely.
+        repo.reset_default(head_commit.as_ref(), [file_path])
+            .map_err(|e| anyhow::anyhow!("Failed to unstage file: {}", e))?;
 
         Ok(())
     }
 
-    /// Unstage a specific hunk by applying the reverse patch
+    /// Unstage a specific hunk by restoring its HEAD side in the index
     pub fn unstage_hunk(&self, hunk: &Hunk, file_path: &Path) -> Result<()> {
-        use std::io::Write;
-        use std::process::Command;
+        self.write_hunk_selection(hunk, file_path, &HashSet::new(), None)
+            .map_err(|e| anyhow::anyhow!("Failed to unstage hunk: {}", e))
+    }
+
+    /// Discard the given change lines of a hunk, reverting them to their HEAD content in
+    /// both the index and the working tree. Other lines of the hunk are left untouched.
+    pub fn discard_hunk_lines(
+        &self,
+        hunk: &Hunk,
+        file_path: &Path,
+        line_indices: &HashSet<usize>,
+    ) -> Result<()> {
+        if let Some((submodule, path)) = self.submodule_for(file_path) {
+            return submodule.discard_hunk_lines(hunk, &path, line_indices);
+        }
+        let selected: HashSet<usize> = line_indices
+            .intersection(&Self::change_line_indices(hunk))
+            .copied()
+            .collect();
+        if selected.is_empty() {
+            return Ok(());
+        }
 
-        // Create a proper unified diff patch
-        let mut patch = String::new();
+        let full_path = self.repo_path.join(file_path);
+        let current_content = std::fs::read(&full_path)
+            .with_context(|| format!("Failed to read {}", file_path.display()))?;
+        let current_lines = Self::split_blob_lines(&current_content);
 
-        // Diff header
-        patch.push_str(&format!(
-            "diff --git a/{} b/{}\n",
-            file_path.display(),
-            file_path.display()
-        ));
-        patch.push_str(&format!("--- a/{}\n", file_path.display()));
-        patch.push_str(&format!("+++ b/{}\n", file_path.display()));
-
-        // Count actual add/remove lines for the hunk header
-        let mut old_lines = 0;
-        let mut new_lines = 0;
-        for line in &hunk.lines {
-            if line.starts_with('-') && !line.starts_with("---") {
-                old_lines += 1;
+        // The hunk's new side is in worktree coordinates; make sure it still matches.
+        let worktree_image: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|line| {
+                line.starts_with(' ') || (line.starts_with('+') && !line.starts_with("+++"))
+            })
+            .map(|line| &line[1..])
+            .collect();
+        let region_begin = if worktree_image.is_empty() {
+            hunk.new_start
+        } else {
+            hunk.new_start.saturating_sub(1)
+        };
+        let region_end = region_begin + worktree_image.len();
+        let region_matches = region_end <= current_lines.len()
+            && worktree_image
+                .iter()
+                .zip(&current_lines[region_begin..region_end])
+                .all(|(expected, actual)| expected.as_bytes() == *actual);
+        if !region_matches {
+            return Err(anyhow::anyhow!(
+                "{} changed since this hunk was loaded; refresh and try again",
+                file_path.display()
+            ));
+        }
+
+        // Drop the selected lines from the index first so nothing staged is left behind.
+        let currently_staged = self.detect_staged_lines(hunk, file_path)?;
+        if !currently_staged.is_disjoint(&selected) {
+            let remaining: HashSet<usize> =
+                currently_staged.difference(&selected).copied().collect();
+            self.set_hunk_staged_lines(hunk, file_path, &remaining)?;
+        }
+
+        let mut new_content = Vec::with_capacity(current_content.len());
+        for line in &current_lines[..region_begin] {
+            new_content.extend_from_slice(line);
+        }
+        for (idx, line) in hunk.lines.iter().enumerate() {
+            let keep = if line.starts_with(' ') {
+                true
             } else if line.starts_with('+') && !line.starts_with("+++") {
-                new_lines += 1;
-            } else if line.starts_with(' ') {
-                old_lines += 1;
-                new_lines += 1;
+                !selected.contains(&idx)
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                selected.contains(&idx)
+            } else {
+                false
+            };
+            if keep {
+                new_content.extend_from_slice(&line.as_bytes()[1..]);
             }
         }
+        for line in &current_lines[region_end..] {
+            new_content.extend_from_slice(line);
+        }
+
+        std::fs::write(&full_path, new_content)
+            .with_context(|| format!("Failed to write {}", file_path.display()))?;
+
+        Ok(())
+    }
 
-        // Hunk header
+    /// Render a hunk as a patch for manual editing, in the spirit of `git add -p`'s `e`.
+    pub fn hunk_edit_patch(hunk: &Hunk, file_path: &Path, status: &str) -> String {
+        let body: Vec<&String> = hunk
+            .lines
+            .iter()
+            .filter(|line| line.starts_with([' ', '+', '-']))
+            .collect();
+        let old_count = body.iter().filter(|line| !line.starts_with('+')).count();
+        let new_count = body.iter().filter(|line| !line.starts_with('-')).count();
+
+        let mut patch = format!(
+            "# Manual hunk edit mode for {}\n# The edited lines become the staged version of this hunk.\n",
+            file_path.display()
+        );
+        patch.push_str(&patch_file_header(file_path, status));
         patch.push_str(&format!(
             "@@ -{},{} +{},{} @@\n",
-            hunk.old_start, old_lines, hunk.new_start, new_lines
+            hunk.old_start, old_count, hunk.new_start, new_count
         ));
-
-        // Hunk content
-        for line in &hunk.lines {
+        for line in body {
             patch.push_str(line);
             if !line.ends_with('\n') {
-                patch.push('\n');
+                patch.push_str("\n\\ No newline at end of file\n");
             }
         }
+        patch.push_str(
+            "# ---\n\
+             # To leave a '-' line unstaged, make it a ' ' line (context).\n\
+             # To leave a '+' line unstaged, delete it.\n\
+             # Lines starting with # will be removed.\n\
+             # If the edited hunk does not apply, nothing is staged.\n",
+        );
+        patch
+    }
 
-        // Use git apply --reverse to unstage the hunk
-        let mut child = Command::new("git")
-            .arg("apply")
-            .arg("--cached")
-            .arg("--reverse")
-            .arg("--unidiff-zero")
-            .arg("-")
-            .current_dir(&self.repo_path)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()?;
+    /// Parse an edited hunk patch into its body lines, each keeping its ' ', '+' or '-' prefix.
+    fn parse_edited_hunk(edited: &str) -> Result<Vec<String>> {
+        let mut lines = edited
+            .split_inclusive('\n')
+            .filter(|line| !line.starts_with('#'))
+            .skip_while(|line| line.starts_with("--- ") || line.starts_with("+++ "));
+
+        match lines.next() {
+            Some(header) if header.starts_with("@@ ") => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Edited patch must start with an @@ hunk header"
+                ))
+            }
+        }
+
+        let mut body: Vec<String> = Vec::new();
+        for (number, line) in lines.enumerate() {
+            if line.starts_with('\\') {
+                // "\ No newline at end of file" applies to the line before it.
+                match body.last_mut() {
+                    Some(previous) if previous.ends_with('\n') => {
+                        previous.pop();
+                    }
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Unexpected no-newline marker on body line {}",
+                            number + 1
+                        ))
+                    }
+                }
+            } else if line == "\n" {
+                // Editors often strip the space from empty context lines.
+                body.push(" \n".to_string());
+            } else if line.starts_with([' ', '+', '-']) {
+                // Most editors save without a final newline; only an explicit marker
+                // means the line has none.
+                let mut line = line.to_string();
+                if !line.ends_with('\n') {
+                    line.push('\n');
+                }
+                body.push(line);
+            } else if line.starts_with("@@") {
+                return Err(anyhow::anyhow!("Edited patch must contain a single hunk"));
+            } else {
+                return Err(anyhow::anyhow!(
+                    "Body line {} does not start with ' ', '+' or '-'",
+                    number + 1
+                ));
+            }
+        }
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(patch.as_bytes())?;
+        if !body.iter().any(|line| !line.starts_with(' ')) {
+            return Err(anyhow::anyhow!("Edited hunk has no changes"));
         }
+        Ok(body)
+    }
 
-        let output = child.wait_with_output()?;
+    /// Contents of one side of a patch body: context lines plus lines prefixed with `side`.
+    fn patch_side(lines: &[String], side: char) -> Vec<String> {
+        lines
+            .iter()
+            .filter(|line| line.starts_with([' ', side]))
+            .map(|line| line[1..].to_string())
+            .collect()
+    }
 
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to unstage hunk: {}", error_msg));
+    /// Stage a manually edited version of `hunk`. The edited patch must keep the hunk's HEAD
+    /// side intact (context and removals); its new side replaces the hunk's region in the index.
+    pub fn apply_edited_hunk(&self, hunk: &Hunk, file_path: &Path, edited: &str) -> Result<()> {
+        let body = Self::parse_edited_hunk(edited)?;
+
+        if Self::patch_side(&body, '-') != Self::patch_side(&hunk.lines, '-') {
+            return Err(anyhow::anyhow!(
+                "Edited hunk does not apply: context and '-' lines must match the original"
+            ));
         }
 
-        Ok(())
+        let image = Self::patch_side(&body, '+');
+        self.write_hunk_region(hunk, file_path, &image, None)
+            .map_err(|e| anyhow::anyhow!("Failed to stage edited hunk: {}", e))
     }
 }
 
@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use crate::diff::DiffSnapshot;
+
+/// How many working tree states a repository's history keeps
+pub const HISTORY_LIMIT: usize = 200;
+
+/// The working tree states the watcher reported, oldest first. Consecutive snapshots with
+/// the same changes are kept once, and the oldest are dropped past the limit.
+#[derive(Debug, Clone)]
+pub struct SnapshotHistory {
+    entries: VecDeque<DiffSnapshot>,
+    limit: usize,
+}
+
+impl Default for SnapshotHistory {
+    fn default() -> Self {
+        Self::with_limit(HISTORY_LIMIT)
+    }
+}
+
+impl SnapshotHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Add a snapshot unless it matches the latest one. Returns how many of the oldest
+    /// entries were dropped to make room, so positions into the history can follow along.
+    pub fn record(&mut self, snapshot: &DiffSnapshot) -> usize {
+        if self
+            .entries
+            .back()
+            .is_some_and(|latest| latest.same_changes(snapshot))
+        {
+            return 0;
+        }
+        self.entries.push_back(snapshot.clone());
+        let dropped = self.entries.len().saturating_sub(self.limit);
+        self.entries.drain(..dropped);
+        dropped
+    }
+
+    pub fn get(&self, index: usize) -> Option<&DiffSnapshot> {
+        self.entries.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DiffSnapshot> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/history.rs"]
+mod tests;
@@ -115,10 +115,6 @@ pub fn error(msg: impl AsRef<str>) {
     log(LogLevel::Error, msg);
 }
 
-pub fn warn(msg: impl AsRef<str>) {
-    log(LogLevel::Warn, msg);
-}
-
 #[allow(dead_code)]
 pub fn info(msg: impl AsRef<str>) {
     log(LogLevel::Info, msg);
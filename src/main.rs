@@ -1,6 +1,10 @@
+mod activity;
 mod app;
+mod archive;
+mod composer;
 mod diff;
 mod git;
+mod history;
 mod logger;
 mod syntax;
 mod ui;
@@ -9,23 +13,142 @@ mod watcher;
 use anyhow::Result;
 use app::App;
 use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(name = "hunky")]
 #[command(about = "A TUI for streaming git changes in real-time", long_about = None)]
 struct Args {
-    /// Path to the git repository to watch
+    /// Path to a git repository or worktree to watch; repeat to watch several
     #[arg(short, long, default_value = ".")]
-    repo: String,
+    repo: Vec<String>,
+
+    /// Also watch every worktree of each repository
+    #[arg(short, long)]
+    worktrees: bool,
+
+    /// Show changes inside submodules under their paths
+    #[arg(long)]
+    recurse_submodules: bool,
+
+    /// Quiet time in milliseconds after the last file change before a snapshot is taken
+    #[arg(long, default_value_t = 250)]
+    debounce_ms: u64,
+
+    /// Longest time in milliseconds a change waits for a snapshot while files keep changing
+    #[arg(long, default_value_t = 1000)]
+    max_latency_ms: u64,
+
+    /// How to notice file changes; poll works where native events don't arrive, like NFS,
+    /// some container bind mounts and WSL paths into Windows drives
+    #[arg(long, value_enum, default_value_t = watcher::WatchBackend::Auto)]
+    watch_backend: watcher::WatchBackend,
+
+    /// How often the poll backend checks files for changes, in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    poll_interval_ms: u64,
+
+    /// Open snapshots saved with --export or from inside hunky, read-only, instead of
+    /// watching the working tree
+    #[arg(long, value_name = "FILE", conflicts_with = "export")]
+    open: Option<PathBuf>,
+
+    /// Save the working tree's changes of the single --repo to a file and exit; a .json file
+    /// can be opened again with --open, anything else is written as a patch
+    #[arg(long, value_name = "FILE")]
+    export: Option<PathBuf>,
+}
+
+impl Args {
+    fn watch_config(&self) -> watcher::WatchConfig {
+        watcher::WatchConfig {
+            quiet_period: Duration::from_millis(self.debounce_ms),
+            max_latency: Duration::from_millis(self.max_latency_ms.max(self.debounce_ms)),
+            backend: self.watch_backend,
+            poll_interval: Duration::from_millis(self.poll_interval_ms.max(1)),
+        }
+    }
+
+    /// GIT_DIR names a single repository, which every `--repo` would otherwise open
+    fn check_git_dir(&self, git_dir_set: bool) -> Result<()> {
+        if git_dir_set && (self.repo.len() > 1 || self.worktrees) {
+            return Err(anyhow::anyhow!(
+                "GIT_DIR names a single repository; unset it to watch several with --repo or \
+                 --worktrees"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The given repositories followed by all of their worktrees
+fn with_worktrees(repos: &[String]) -> Result<Vec<String>> {
+    let mut paths = repos.to_vec();
+    for repo in repos {
+        let git_repo = git::GitRepo::new(repo)?;
+        for path in git_repo.worktree_paths()? {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(paths)
+}
+
+/// Save the working tree's changes of the one repository given to `path`, returning how
+/// many files changed
+fn export(args: &Args, path: &Path) -> Result<usize> {
+    let [repo] = args.repo.as_slice() else {
+        return Err(anyhow::anyhow!(
+            "--export saves one repository; pass a single --repo"
+        ));
+    };
+    let git_repo = git::GitRepo::new(repo)?;
+    git_repo.set_recurse_submodules(args.recurse_submodules);
+    let snapshot = git_repo.get_diff_snapshot()?;
+    archive::save(path, &[&snapshot])?;
+    Ok(snapshot.files.len())
+}
+
+/// A read-only view of saved snapshots, which needs no repository or watcher
+fn open_saved(path: &Path) -> Result<App> {
+    Ok(App::from_archive(path, archive::load(path)?))
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
     logger::init();
+    args.check_git_dir(std::env::var_os("GIT_DIR").is_some())?;
+
+    if let Some(path) = &args.export {
+        let count = export(&args, path)?;
+        println!(
+            "Saved {} changed file{} to {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(path) = &args.open {
+        // Read the file before the terminal is taken over, so errors show up in the shell
+        let mut app = open_saved(path)?;
+        app.run().await?;
+        return Ok(());
+    }
+
+    let repo_paths = if args.worktrees {
+        with_worktrees(&args.repo)?
+    } else {
+        args.repo.clone()
+    };
 
-    // Initialize the application with the specified repository
-    let mut app = App::new(&args.repo).await?;
+    // Initialize the application with the specified repositories
+    let mut app = App::with_repos(&repo_paths, args.watch_config()).await?;
+    if args.recurse_submodules {
+        app.set_recurse_submodules(true);
+    }
 
     // Run the application
     app.run().await?;
@@ -2,12 +2,37 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Sparkline, Wrap},
     Frame,
 };
 
+use crate::activity::{sparkline, BUCKET_COUNT, BUCKET_WIDTH};
 use crate::app::{App, FocusPane, Mode, StreamSpeed, StreamingType};
+use crate::composer::{CommitComposer, ComposerField, SUMMARY_SOFT_LIMIT};
+use crate::diff::{ConflictedFile, UntrackedMode};
 use crate::syntax::SyntaxHighlighter;
+use std::time::{Duration, SystemTime};
+
+/// Format how long ago something happened, e.g. "5m ago"
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Format how long ago something happened down to the second, e.g. "3m 05s ago"
+fn format_elapsed(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m {:02}s ago", secs / 60, secs % 60),
+        _ => format_age(age),
+    }
+}
 
 /// Fade a color by reducing its brightness (for context lines)
 fn fade_color(color: Color) -> Color {
@@ -131,6 +156,65 @@ impl<'a> UI<'a> {
         }
         title_left.push(Span::styled(mode_text, Style::default().fg(Color::Yellow)));
 
+        if let Some(label) = self.app.repo_label() {
+            title_left.push(Span::raw(" | "));
+            title_left.push(Span::styled(label, Style::default().fg(Color::Magenta)));
+            let changed = self.app.repos_with_new_events();
+            if available_width > 80 && !changed.is_empty() {
+                title_left.push(Span::styled(
+                    format!(" (changed: {})", changed.join(", ")),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+        }
+
+        if available_width > 40 {
+            title_left.extend(self.branch_spans(available_width > 80));
+        }
+
+        if let Some(label) = self.app.stash_preview_label() {
+            title_left.push(Span::raw(" | "));
+            title_left.push(Span::styled(
+                format!("{} (read-only)", label),
+                Style::default().fg(Color::Magenta),
+            ));
+        } else if let Some(label) = self.app.delta_label() {
+            title_left.push(Span::raw(" | "));
+            title_left.push(Span::styled(
+                format!("{} (read-only)", label),
+                Style::default().fg(Color::Magenta),
+            ));
+        } else if let Some(cursor) = self.app.timeline_cursor() {
+            title_left.push(Span::raw(" | "));
+            title_left.push(Span::styled(
+                format!(
+                    "{} {}/{} (read-only)",
+                    self.app.archive_label().unwrap_or("snapshot"),
+                    cursor + 1,
+                    self.app.history().len()
+                ),
+                Style::default().fg(Color::Magenta),
+            ));
+        }
+
+        // Show the latest status message after the mode, truncated to the space left
+        if let Some(status) = self.app.status_message() {
+            let used = title_left.iter().map(|s| s.content.len()).sum::<usize>() + 3;
+            let room = available_width.saturating_sub(used + help_width + 1);
+            if room > 0 {
+                let color = if status.is_error {
+                    Color::Red
+                } else {
+                    Color::Green
+                };
+                title_left.push(Span::raw(" | "));
+                title_left.push(Span::styled(
+                    status.text.chars().take(room).collect::<String>(),
+                    Style::default().fg(color),
+                ));
+            }
+        }
+
         // Calculate padding to right-align help hint
         let left_width = title_left.iter().map(|s| s.content.len()).sum::<usize>();
         let padding_width = available_width.saturating_sub(left_width + help_width);
@@ -147,7 +231,75 @@ impl<'a> UI<'a> {
         frame.render_widget(header, area);
     }
 
+    /// Branch, detached HEAD, ahead/behind and in-progress operation for the header
+    fn branch_spans(&self, wide: bool) -> Vec<Span<'static>> {
+        let status = self.app.repo_status();
+        let sha = status.head_short_sha.as_deref().unwrap_or("?");
+
+        let head_text = match (&status.branch, status.detached) {
+            (Some(branch), _) if status.unborn => format!("{} (no commits)", branch),
+            (Some(branch), false) => branch.clone(),
+            (Some(branch), true) => format!("{} @ {}", branch, sha),
+            (None, _) => format!("detached @ {}", sha),
+        };
+        let head_color = if status.detached {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+
+        let mut spans = vec![
+            Span::raw(" | "),
+            Span::styled(head_text, Style::default().fg(head_color)),
+        ];
+        if wide && status.upstream.is_some() && (status.ahead > 0 || status.behind > 0) {
+            let mut counts = String::new();
+            if status.ahead > 0 {
+                counts.push_str(&format!(" ↑{}", status.ahead));
+            }
+            if status.behind > 0 {
+                counts.push_str(&format!(" ↓{}", status.behind));
+            }
+            spans.push(Span::styled(counts, Style::default().fg(Color::Cyan)));
+        }
+        if let Some(operation) = status.operation {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                operation.label(),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+        }
+        let conflicts = self.app.conflicts().len();
+        if conflicts > 0 {
+            spans.push(Span::styled(
+                format!(" {} conflicted (M)", conflicts),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        spans
+    }
+
     fn draw_main_content(&self, frame: &mut Frame, area: Rect) -> (u16, u16, u16) {
+        if let Some(composer) = self.app.commit_composer() {
+            self.draw_commit_composer(frame, area, composer);
+            return (0, 0, 0);
+        }
+
+        if self.app.stash_panel_open() {
+            self.draw_stash_panel(frame, area);
+            return (0, 0, 0);
+        }
+
+        if self.app.activity_panel_open() {
+            self.draw_activity_panel(frame, area);
+            return (0, 0, 0);
+        }
+
+        if self.app.conflict_view_open() {
+            self.draw_conflict_view(frame, area);
+            return (0, 0, 0);
+        }
+
         // Check if commit picker overlay should be shown
         if self.app.review_selecting_commit() {
             self.draw_commit_picker(frame, area);
@@ -160,6 +312,19 @@ impl<'a> UI<'a> {
             return (0, help_height, 0);
         }
 
+        // The timeline runs along the bottom while stepping through the history
+        let area = match self.app.timeline_cursor() {
+            Some(cursor) => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(4)])
+                    .split(area);
+                self.draw_timeline(frame, chunks[1], cursor);
+                chunks[0]
+            }
+            None => area,
+        };
+
         // Check if help sidebar should be shown
         if self.app.show_help() {
             // Split into 3 columns: file list, diff, help
@@ -205,16 +370,22 @@ impl<'a> UI<'a> {
 
         let is_review_mode = self.app.mode() == Mode::Review;
 
-        let items: Vec<ListItem> = snapshot
+        let mut items: Vec<ListItem> = snapshot
             .files
             .iter()
             .enumerate()
             .map(|(idx, file)| {
-                let file_name = file
+                let mut file_name = file
                     .path
                     .file_name()
                     .and_then(|n| n.to_str())
-                    .unwrap_or("unknown");
+                    .unwrap_or("unknown")
+                    .to_string();
+                let is_untracked = file.status == "Untracked";
+                // Collapsed untracked directories keep their trailing slash
+                if is_untracked && file.path.to_string_lossy().ends_with('/') {
+                    file_name.push('/');
+                }
 
                 let is_selected = idx == self.app.current_file_index();
                 let name_style = if is_selected {
@@ -227,7 +398,9 @@ impl<'a> UI<'a> {
 
                 let hunk_count = file.hunks.len();
 
-                let count_text = if is_review_mode {
+                let count_text = if is_untracked && hunk_count == 0 {
+                    " (untracked)".to_string()
+                } else if is_review_mode {
                     let accepted_count = file.hunks.iter().filter(|h| h.accepted).count();
                     if accepted_count > 0 {
                         format!(" ({}) [{}✓]", hunk_count, accepted_count)
@@ -274,12 +447,33 @@ impl<'a> UI<'a> {
                 ListItem::new(content)
             })
             .collect();
+        if snapshot.files.is_empty() && snapshot.untracked_overflow == 0 && !is_review_mode {
+            items.push(ListItem::new(Line::styled(
+                "✓ Working tree clean",
+                Style::default().fg(Color::Green),
+            )));
+        }
+        if snapshot.untracked_overflow > 0 {
+            items.push(ListItem::new(Line::styled(
+                format!("… {} more untracked files", snapshot.untracked_overflow),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
 
-        let title = if self.app.focus() == FocusPane::FileList {
-            "Files [FOCUSED]"
+        let mut title = if self.app.focus() == FocusPane::FileList {
+            "Files [FOCUSED]".to_string()
         } else {
-            "Files"
+            "Files".to_string()
         };
+        if self.app.untracked_mode() != UntrackedMode::Show {
+            title.push_str(&format!(
+                " (untracked: {})",
+                self.app.untracked_mode().label()
+            ));
+        }
+        if self.app.recurses_submodules() {
+            title.push_str(" (submodules)");
+        }
 
         let border_style = if self.app.focus() == FocusPane::FileList {
             Style::default().fg(Color::Cyan)
@@ -300,12 +494,47 @@ impl<'a> UI<'a> {
         frame.render_stateful_widget(list, area, &mut state);
     }
 
+    /// Whether the snapshot on screen is the live working tree and it has no changes
+    fn working_tree_clean(&self) -> bool {
+        self.app.mode() != Mode::Review
+            && self.app.current_snapshot().is_some_and(|snapshot| {
+                snapshot.files.is_empty() && snapshot.untracked_overflow == 0
+            })
+    }
+
     fn draw_diff_content(&self, frame: &mut Frame, area: Rect) -> u16 {
         // Return viewport height for clamping
         let viewport_height = area.height.saturating_sub(2); // Subtract borders
 
         let file = match self.app.current_file() {
             Some(f) => f,
+            None if self.working_tree_clean() => {
+                let reason = if self.app.timeline_cursor().is_some() {
+                    "No changes at this point in the history"
+                } else {
+                    self.app
+                        .clean_reason()
+                        .map(|reason| reason.description())
+                        .unwrap_or("No changes against HEAD")
+                };
+                let lines = vec![
+                    Line::styled(
+                        "✓ Working tree clean",
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Line::from(""),
+                    Line::from(reason),
+                    Line::from(""),
+                    Line::styled("Waiting for changes…", Style::default().fg(Color::DarkGray)),
+                ];
+                let clean = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title("Diff"))
+                    .wrap(Wrap { trim: true });
+                frame.render_widget(clean, area);
+                return viewport_height;
+            }
             None => {
                 let empty = Paragraph::new("No file selected")
                     .block(Block::default().borders(Borders::ALL).title("Diff"));
@@ -404,6 +633,12 @@ impl<'a> UI<'a> {
             }
         };
 
+        let hunk_header = if self.app.is_marked_for_stash(hunk) {
+            format!("{} [STASH]", hunk_header)
+        } else {
+            hunk_header
+        };
+
         let header_style = if is_review_mode && hunk.accepted {
             Style::default().fg(Color::Green)
         } else if is_partially_staged {
@@ -477,13 +712,24 @@ impl<'a> UI<'a> {
         // Red additions: bg 52 → 235 (darker gray-red), prefix 88 → 124 (softer red)
         let line_selection_mode = self.app.line_selection_mode();
         let selected_line = self.app.selected_line_index();
+        let selected_range = self.app.selected_line_range();
 
         for (original_idx, line) in &changes {
-            let is_selected = line_selection_mode && *original_idx == selected_line;
+            let is_cursor = line_selection_mode && *original_idx == selected_line;
+            let in_range = selected_range
+                .map(|(start, end)| *original_idx >= start && *original_idx <= end)
+                .unwrap_or(false);
+            let is_selected = is_cursor || in_range;
             let is_staged = hunk.staged_line_indices.contains(original_idx);
 
             // Build 4-character indicator prefix: [selection (2)][staged (2)]
-            let selection_marker = if is_selected { "► " } else { "  " };
+            let selection_marker = if is_cursor {
+                "► "
+            } else if in_range {
+                "│ "
+            } else {
+                "  "
+            };
             let staged_marker = if is_staged { "✓ " } else { "  " };
             let indicator_prefix = format!("{}{}", selection_marker, staged_marker);
 
@@ -600,6 +846,11 @@ impl<'a> UI<'a> {
         } else {
             ""
         };
+        let title_split = if hunk.split_from.is_some() {
+            " split"
+        } else {
+            ""
+        };
 
         let border_style = if self.app.focus() == FocusPane::HunkView {
             Style::default().fg(Color::Cyan)
@@ -612,10 +863,11 @@ impl<'a> UI<'a> {
                 Block::default()
                     .borders(Borders::ALL)
                     .title(format!(
-                        "{} (Hunk {}/{}{})",
+                        "{} (Hunk {}/{}{}{})",
                         file.path.to_string_lossy(),
                         self.app.current_hunk_index() + 1,
                         file.hunks.len(),
+                        title_split,
                         title_focus
                     ))
                     .border_style(border_style),
@@ -636,70 +888,7 @@ impl<'a> UI<'a> {
         // Return viewport height for clamping
         let viewport_height = area.height.saturating_sub(2); // Subtract borders
 
-        let help_lines = vec![
-            Line::from(Span::styled(
-                "Navigation",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("Q: Quit"),
-            Line::from("Tab/Shift+Tab: Focus"),
-            Line::from("Space: Next Hunk"),
-            Line::from("B: Prev Hunk"),
-            Line::from("J/K: Scroll/Nav"),
-            Line::from("N/P: Next/Prev File"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Modes",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("M: Cycle Mode"),
-            Line::from("  View → Streaming"),
-            Line::from("  (Buffered/Auto)"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Display",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("W: Toggle Wrap"),
-            Line::from("Y: Toggle Syntax"),
-            Line::from("F: Filenames Only"),
-            Line::from("H: Toggle Help"),
-            Line::from("Shift+H: Extended Help"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Staging",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("L: Line Mode"),
-            Line::from("S: Stage/Unstage"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Review",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("R: Review Commit"),
-            Line::from("S: Accept (in review)"),
-            Line::from("ESC: Exit Review"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Other",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("C: Commit (Open Editor)"),
-            Line::from("ESC: Reset to Defaults"),
-        ];
+        let help_lines = help_lines();
 
         let is_focused = self.app.focus() == FocusPane::HelpSidebar;
         let border_color = if is_focused {
@@ -723,6 +912,523 @@ impl<'a> UI<'a> {
         viewport_height
     }
 
+    fn draw_commit_composer(&self, frame: &mut Frame, area: Rect, composer: &CommitComposer) {
+        let file_rows = composer.staged_files.len().clamp(1, 8) as u16 + 2;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),         // Summary
+                Constraint::Min(3),            // Body
+                Constraint::Length(file_rows), // Staged files
+                Constraint::Length(3),         // Options and keys
+            ])
+            .split(area);
+
+        let field_style = |field: ComposerField| {
+            if composer.field == field {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            }
+        };
+        let cursor = |field: ComposerField| if composer.field == field { "█" } else { "" };
+
+        let summary_len = composer.summary.chars().count();
+        let summary_title = if summary_len > SUMMARY_SOFT_LIMIT {
+            Line::from(vec![
+                Span::raw("Summary "),
+                Span::styled(
+                    format!(
+                        "({}/{} - consider shortening)",
+                        summary_len, SUMMARY_SOFT_LIMIT
+                    ),
+                    Style::default().fg(Color::Yellow),
+                ),
+            ])
+        } else {
+            Line::from(format!("Summary ({}/{})", summary_len, SUMMARY_SOFT_LIMIT))
+        };
+        let summary = Paragraph::new(format!(
+            "{}{}",
+            composer.summary,
+            cursor(ComposerField::Summary)
+        ))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(field_style(ComposerField::Summary))
+                .title(summary_title),
+        );
+        frame.render_widget(summary, chunks[0]);
+
+        let body_lines: Vec<Line> = format!("{}{}", composer.body, cursor(ComposerField::Body))
+            .split('\n')
+            .map(|line| {
+                if line.starts_with('#') {
+                    Line::styled(line.to_string(), Style::default().fg(Color::DarkGray))
+                } else {
+                    Line::from(line.to_string())
+                }
+            })
+            .collect();
+        let body_height = chunks[1].height.saturating_sub(2) as usize;
+        let body_scroll = body_lines.len().saturating_sub(body_height) as u16;
+        let body = Paragraph::new(body_lines)
+            .wrap(Wrap { trim: false })
+            .scroll((body_scroll, 0))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(field_style(ComposerField::Body))
+                    .title("Body"),
+            );
+        frame.render_widget(body, chunks[1]);
+
+        let file_lines: Vec<Line> = if composer.staged_files.is_empty() {
+            vec![Line::styled(
+                if composer.amend {
+                    "Nothing staged - amending only changes the message".to_string()
+                } else {
+                    "Nothing staged - the commit would be empty".to_string()
+                },
+                Style::default().fg(Color::Yellow),
+            )]
+        } else {
+            composer
+                .staged_files
+                .iter()
+                .map(|file| {
+                    Line::from(vec![
+                        Span::styled(
+                            format!("{:<9}", file.status),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                        Span::raw(file.path.display().to_string()),
+                        Span::styled(
+                            format!(" +{}", file.insertions),
+                            Style::default().fg(Color::Green),
+                        ),
+                        Span::styled(
+                            format!(" -{}", file.deletions),
+                            Style::default().fg(Color::Red),
+                        ),
+                    ])
+                })
+                .collect()
+        };
+        let files = Paragraph::new(file_lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Staged files ({})", composer.staged_files.len())),
+        );
+        frame.render_widget(files, chunks[2]);
+
+        let toggle = |label: &str, on: bool| {
+            Span::styled(
+                format!("[{}] {}  ", if on { "x" } else { " " }, label),
+                if on {
+                    Style::default().fg(Color::Green)
+                } else {
+                    Style::default().fg(Color::Gray)
+                },
+            )
+        };
+        let mut options = vec![
+            toggle("Amend (^A)", composer.amend),
+            toggle("Sign-off (^O)", composer.signoff),
+            Span::styled(
+                format!(
+                    "Template (^T): {}  ",
+                    composer.template_name().unwrap_or("none")
+                ),
+                Style::default().fg(Color::Gray),
+            ),
+        ];
+        if composer.awaiting_empty_confirmation() {
+            options.push(Span::styled(
+                "Index is empty - press ^S again to commit anyway",
+                Style::default().fg(Color::Yellow),
+            ));
+        } else {
+            options.push(Span::styled(
+                "^S: Commit  Tab: Switch field  Esc: Cancel",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if !composer.has_signoff_identity() {
+            options.push(Span::styled(
+                "  (sign-off needs user.name and user.email)",
+                Style::default().fg(Color::Red),
+            ));
+        }
+        let options = Paragraph::new(Line::from(options)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(if composer.amend {
+                    "Amend commit"
+                } else {
+                    "New commit"
+                }),
+        );
+        frame.render_widget(options, chunks[3]);
+    }
+
+    fn draw_stash_panel(&self, frame: &mut Frame, area: Rect) {
+        let cursor = self.app.stash_cursor();
+        let now = SystemTime::now();
+
+        let items: Vec<ListItem> = self
+            .app
+            .stashes()
+            .iter()
+            .enumerate()
+            .map(|(idx, stash)| {
+                let is_selected = idx == cursor;
+                let style = if is_selected {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let age = now
+                    .duration_since(stash.time)
+                    .map(format_age)
+                    .unwrap_or_else(|_| "just now".to_string());
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("stash@{{{}}} ", stash.index),
+                        Style::default().fg(if is_selected {
+                            Color::Cyan
+                        } else {
+                            Color::DarkGray
+                        }),
+                    ),
+                    Span::styled(&stash.message, style),
+                    Span::styled(
+                        format!(
+                            " ({}, {} file{})",
+                            age,
+                            stash.file_count,
+                            if stash.file_count == 1 { "" } else { "s" }
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta))
+                .title("Stashes (Enter preview, a apply, p pop, d drop, Esc close)"),
+        );
+
+        let mut state = ratatui::widgets::ListState::default();
+        state.select(Some(cursor));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn draw_activity_panel(&self, frame: &mut Frame, area: Rect) {
+        let activity = self.app.activity();
+        let cursor = self.app.activity_cursor();
+        let now = SystemTime::now();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Min(0)])
+            .split(area);
+
+        // Every file's events together, one bar per bucket across the panel
+        let overall_width = chunks[0].width.saturating_sub(2) as usize;
+        let overall: Vec<u64> = activity
+            .counts(now, overall_width)
+            .into_iter()
+            .map(u64::from)
+            .collect();
+        let total: usize = activity.files().iter().map(|(_, file)| file.total()).sum();
+        let overall_sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Magenta))
+                    .title(format!(
+                        "Activity: {} event{} in {} file{}, {}s per bar",
+                        total,
+                        if total == 1 { "" } else { "s" },
+                        activity.len(),
+                        if activity.len() == 1 { "" } else { "s" },
+                        BUCKET_WIDTH.as_secs()
+                    )),
+            )
+            .data(&overall)
+            .style(Style::default().fg(Color::Cyan));
+        frame.render_widget(overall_sparkline, chunks[0]);
+
+        // Per-file timelines share one scale so hot spots stand out
+        let files = activity.files();
+        let timeline_width = BUCKET_COUNT.min(chunks[1].width as usize / 3);
+        let timelines: Vec<Vec<u32>> = files
+            .iter()
+            .map(|(_, file)| file.counts(now, timeline_width))
+            .collect();
+        let peak = timelines.iter().flatten().copied().max().unwrap_or(0);
+
+        let items: Vec<ListItem> = files
+            .iter()
+            .zip(&timelines)
+            .enumerate()
+            .map(|(idx, ((path, file), timeline))| {
+                let is_selected = idx == cursor;
+                let style = if is_selected {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let age = now
+                    .duration_since(file.last_seen)
+                    .map(format_age)
+                    .unwrap_or_else(|_| "just now".to_string());
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(sparkline(timeline, peak), Style::default().fg(Color::Cyan)),
+                    Span::styled(format!(" {:>4} ", file.total()), style),
+                    Span::styled(path.display().to_string(), style),
+                    Span::styled(
+                        format!(
+                            " (+{} ~{} -{}, {})",
+                            file.created, file.modified, file.removed, age
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta))
+                .title("Files by activity (Enter show diff, Esc close)"),
+        );
+
+        let mut state = ratatui::widgets::ListState::default();
+        state.select(Some(cursor));
+        frame.render_stateful_widget(list, chunks[1], &mut state);
+    }
+
+    fn draw_timeline(&self, frame: &mut Frame, area: Rect, cursor: usize) {
+        let history = self.app.history();
+        let Some(selected) = history.get(cursor) else {
+            return;
+        };
+
+        // One column per snapshot, scrolled to keep the selected one in view
+        let width = (area.width.saturating_sub(2) as usize).max(1);
+        let start = cursor
+            .saturating_sub(width / 2)
+            .min(history.len().saturating_sub(width));
+        let sizes: Vec<u32> = history
+            .iter()
+            .skip(start)
+            .take(width)
+            .map(|snapshot| {
+                let (added, removed) = snapshot.line_counts();
+                (added + removed) as u32
+            })
+            .collect();
+        let peak = sizes.iter().copied().max().unwrap_or(0);
+        let bars: Vec<Span> = sparkline(&sizes, peak)
+            .chars()
+            .zip(&sizes)
+            .enumerate()
+            .map(|(idx, (bar, &size))| {
+                // A clean tree still gets a mark
+                let bar = if size == 0 { '·' } else { bar };
+                let color = if start + idx == cursor {
+                    Color::Yellow
+                } else {
+                    Color::Cyan
+                };
+                Span::styled(bar.to_string(), Style::default().fg(color))
+            })
+            .collect();
+        // The selected point, and the marked one to compare it with
+        let marker: String = (start..start + sizes.len())
+            .map(|idx| match idx {
+                idx if idx == cursor => '▲',
+                idx if Some(idx) == self.app.timeline_mark() => '◆',
+                _ => ' ',
+            })
+            .collect();
+
+        let age = SystemTime::now()
+            .duration_since(selected.timestamp)
+            .map(format_elapsed)
+            .unwrap_or_else(|_| "just now".to_string());
+        let (added, removed) = selected.line_counts();
+        let title = format!(
+            "Timeline {}/{}: {}, {} file{}, +{} -{} (←/→ step, Enter mark, D compare, Esc live)",
+            cursor + 1,
+            history.len(),
+            age,
+            selected.files.len(),
+            if selected.files.len() == 1 { "" } else { "s" },
+            added,
+            removed
+        );
+
+        let timeline = Paragraph::new(vec![
+            Line::from(bars),
+            Line::styled(marker, Style::default().fg(Color::Yellow)),
+        ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta))
+                .title(title),
+        );
+        frame.render_widget(timeline, area);
+    }
+
+    fn draw_conflict_view(&self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(25), Constraint::Min(0)])
+            .split(area);
+
+        let file_cursor = self.app.conflict_file_cursor();
+        let items: Vec<ListItem> = self
+            .app
+            .conflicts()
+            .iter()
+            .enumerate()
+            .map(|(idx, file)| {
+                let style = if idx == file_cursor {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let detail = match (file.has_ours, file.has_theirs) {
+                    (false, _) => " (deleted by us)".to_string(),
+                    (_, false) => " (deleted by them)".to_string(),
+                    _ => format!(" ({})", file.regions.len()),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(file.path.display().to_string(), style),
+                    Span::styled(detail, Style::default().fg(Color::DarkGray)),
+                ]))
+            })
+            .collect();
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .title(format!("Conflicts ({})", self.app.conflicts().len())),
+        );
+        let mut state = ratatui::widgets::ListState::default();
+        state.select(Some(file_cursor));
+        frame.render_stateful_widget(list, chunks[0], &mut state);
+
+        let Some(file) = self.app.conflicts().get(file_cursor) else {
+            return;
+        };
+        self.draw_conflict_file(frame, chunks[1], file);
+    }
+
+    /// Three-way view of the selected region: ours, base and theirs side by side
+    fn draw_conflict_file(&self, frame: &mut Frame, area: Rect, file: &ConflictedFile) {
+        let keys = "o ours, t theirs, b both, Enter mark resolved, j/k region, n/p file, Esc close";
+        let region_cursor = self.app.conflict_region_cursor();
+        let Some(region) = file.regions.get(region_cursor) else {
+            let message = match (file.has_ours, file.has_theirs) {
+                (false, _) => "Deleted on our side and changed on theirs. o keeps the deletion, t keeps their version.",
+                (_, false) => "Changed on our side and deleted on theirs. o keeps our version, t keeps the deletion.",
+                _ => "No conflict markers left. Press Enter to mark the file resolved.",
+            };
+            let paragraph = Paragraph::new(message).wrap(Wrap { trim: false }).block(
+                Block::default().borders(Borders::ALL).title(format!(
+                    "{} ({})",
+                    file.path.display(),
+                    keys
+                )),
+            );
+            frame.render_widget(paragraph, area);
+            return;
+        };
+
+        let outer = Block::default().borders(Borders::ALL).title(format!(
+            "{} - conflict {}/{} ({})",
+            file.path.display(),
+            region_cursor + 1,
+            file.regions.len(),
+            keys
+        ));
+        let inner = outer.inner(area);
+        frame.render_widget(outer, area);
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+            ])
+            .split(inner);
+
+        let side = |title: String, lines: Option<&Vec<String>>, color: Color| {
+            let text: Vec<Line> = match lines {
+                Some(lines) => lines
+                    .iter()
+                    .map(|line| {
+                        Line::styled(
+                            line.trim_end_matches(['\n', '\r']).to_string(),
+                            Style::default().fg(color),
+                        )
+                    })
+                    .collect(),
+                None => vec![Line::styled(
+                    "(unknown)",
+                    Style::default().fg(Color::DarkGray),
+                )],
+            };
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color))
+                    .title(title),
+            )
+        };
+
+        frame.render_widget(
+            side(
+                format!("Ours ({})", region.ours_label),
+                Some(&region.ours),
+                Color::Green,
+            ),
+            columns[0],
+        );
+        frame.render_widget(
+            side("Base".to_string(), region.base.as_ref(), Color::Gray),
+            columns[1],
+        );
+        frame.render_widget(
+            side(
+                format!("Theirs ({})", region.theirs_label),
+                Some(&region.theirs),
+                Color::Blue,
+            ),
+            columns[2],
+        );
+    }
+
     fn draw_commit_picker(&self, frame: &mut Frame, area: Rect) {
         let commits = self.app.review_commits();
         let cursor = self.app.review_commit_cursor();
@@ -778,179 +1484,7 @@ impl<'a> UI<'a> {
         // Return viewport height for clamping
         let viewport_height = area.height.saturating_sub(2); // Subtract borders
 
-        let help_content = vec![
-            Line::from(Span::styled(
-                "HUNKY - Extended Help",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from(Span::styled(
-                "═══════════════════════════════════════════════════════════",
-                Style::default().fg(Color::DarkGray),
-            )),
-            Line::from(""),
-            Line::from(Span::styled(
-                "OVERVIEW",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from("Hunky is a terminal UI for reviewing and staging git changes at the hunk"),
-            Line::from("or line level. It provides two main modes for different workflows:"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "MODES",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled(
-                    "View Mode",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Span::raw(" - Browse all current changes"),
-            ]),
-            Line::from("  • Shows all changes from HEAD to working directory"),
-            Line::from("  • Full navigation with Space (next) and Shift+Space (previous)"),
-            Line::from("  • Ideal for reviewing existing changes before committing"),
-            Line::from("  • Default mode when starting Hunky"),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled(
-                    "Streaming Mode",
-                    Style::default()
-                        .fg(Color::Magenta)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Span::raw(" - Watch new changes as they appear"),
-            ]),
-            Line::from("  • Only shows hunks that appear after entering this mode"),
-            Line::from("  • Two sub-modes:"),
-            Line::from("    - Buffered: Manual advance with Space key"),
-            Line::from("    - Auto (Fast/Medium/Slow): Automatic advancement with timing"),
-            Line::from("  • Perfect for TDD workflows or watching build output changes"),
-            Line::from("  • Press M to cycle through streaming options"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "NAVIGATION",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("  Space           Next hunk (all modes)"),
-            Line::from("  B               Previous hunk (View & Buffered modes)"),
-            Line::from("  J/K or ↓/↑      Scroll hunk view or navigate in line mode"),
-            Line::from("  N/P             Next/Previous file"),
-            Line::from("  Tab             Cycle focus forward (File → Hunk → Help)"),
-            Line::from("  Shift+Tab       Cycle focus backward"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "STAGING",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("  S               Smart stage/unstage toggle"),
-            Line::from("  L               Toggle Line Mode for line-level staging"),
-            Line::from(""),
-            Line::from("Smart Toggle Behavior (Hunk Mode):"),
-            Line::from("  • Unstaged → Press S → Fully staged"),
-            Line::from("  • Partially staged → Press S → Fully staged"),
-            Line::from("  • Fully staged → Press S → Fully unstaged"),
-            Line::from(""),
-            Line::from("In Line Mode:"),
-            Line::from("  • Use J/K to navigate between changed lines (+ or -)"),
-            Line::from("  • Press S to toggle staging for the selected line"),
-            Line::from("  • Staged lines show a ✓ indicator"),
-            Line::from("  • External changes (e.g., git add -p) are detected automatically"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "DISPLAY OPTIONS",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("  H               Toggle help sidebar"),
-            Line::from("  Shift+H         Toggle this extended help view"),
-            Line::from("  F               Toggle filenames-only mode (hide diffs)"),
-            Line::from("  W               Toggle line wrapping"),
-            Line::from("  Y               Toggle syntax highlighting"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "MODE SWITCHING",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("  M               Cycle through modes:"),
-            Line::from("                    View → Streaming (Buffered) → Streaming (Auto Fast)"),
-            Line::from(
-                "                    → Streaming (Auto Medium) → Streaming (Auto Slow) → View",
-            ),
-            Line::from(""),
-            Line::from("When switching to Streaming mode, Hunky captures the current state and"),
-            Line::from("will only show new hunks that appear after the switch."),
-            Line::from(""),
-            Line::from(Span::styled(
-                "RESET TO DEFAULTS",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("  ESC             Reset everything to defaults:"),
-            Line::from("                    • Exit extended help view"),
-            Line::from("                    • Set mode to View"),
-            Line::from("                    • Exit line mode"),
-            Line::from("                    • Focus hunk view"),
-            Line::from("                    • Hide help sidebar"),
-            Line::from(""),
-            Line::from(Span::styled(
-                "WORKFLOWS",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled("Code Review", Style::default().fg(Color::Green)),
-                Span::raw(" - Use View mode to browse all changes, stage what you want"),
-            ]),
-            Line::from("to commit, then press C to open your configured git editor."),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled("TDD Workflow", Style::default().fg(Color::Magenta)),
-                Span::raw(" - Switch to Streaming (Auto) mode, run tests in"),
-            ]),
-            Line::from("another terminal, and watch test changes flow through Hunky as you"),
-            Line::from("iterate on your code."),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled("Partial Staging", Style::default().fg(Color::Cyan)),
-                Span::raw(" - Enable Line Mode (L) to stage specific lines"),
-            ]),
-            Line::from(
-                "within a hunk. Great for separating formatting changes from logic changes.",
-            ),
-            Line::from(""),
-            Line::from(Span::styled(
-                "═══════════════════════════════════════════════════════════",
-                Style::default().fg(Color::DarkGray),
-            )),
-            Line::from(""),
-            Line::from("Press ESC to exit this help view and return to normal operation."),
-            Line::from("Press J/K to scroll through this help."),
-        ];
+        let help_content = extended_help_lines();
 
         let help = Paragraph::new(help_content)
             .block(
@@ -968,6 +1502,308 @@ impl<'a> UI<'a> {
     }
 }
 
+/// The key summary in the help sidebar, one entry per row
+pub fn help_lines() -> Vec<Line<'static>> {
+    vec![
+        Line::from(Span::styled(
+            "Navigation",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("Q: Quit"),
+        Line::from("Tab/Shift+Tab: Focus"),
+        Line::from("Space: Next Hunk"),
+        Line::from("B: Prev Hunk"),
+        Line::from("J/K: Scroll/Nav"),
+        Line::from("N/P: Next/Prev File"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Modes",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("M: Cycle Mode"),
+        Line::from("  View → Streaming"),
+        Line::from("  (Buffered/Auto)"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Display",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("W: Toggle Wrap"),
+        Line::from("Shift+W: File Activity"),
+        Line::from("Shift+T: Snapshot Timeline"),
+        Line::from("Shift+D: Changes Since Last Look"),
+        Line::from("Shift+E: Save Snapshots"),
+        Line::from("Y: Toggle Syntax"),
+        Line::from("F: Filenames Only"),
+        Line::from("H: Toggle Help"),
+        Line::from("Shift+H: Extended Help"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Staging",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("L: Line Mode"),
+        Line::from("V: Select Range"),
+        Line::from("S: Stage/Unstage"),
+        Line::from("D: Discard"),
+        Line::from("X: Split Hunk"),
+        Line::from("Shift+X: Unsplit"),
+        Line::from("E: Edit Hunk"),
+        Line::from("T: Mark for Stash"),
+        Line::from("Shift+Z: Stash Hunks"),
+        Line::from("Z: Stash List"),
+        Line::from("Shift+M: Resolve Conflicts"),
+        Line::from("U: Untracked Show/Collapse/Hide"),
+        Line::from("Shift+O: Submodule Changes"),
+        Line::from("I: Ignore (Shift: Exclude)"),
+        Line::from("Shift+N: Intent to Add"),
+        Line::from("[ / ]: Switch Repository"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Review",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("R: Review Commit"),
+        Line::from("G: Review New Commit"),
+        Line::from("S: Accept (in review)"),
+        Line::from("Shift+F: Fixup Commit"),
+        Line::from("Shift+A: Amend HEAD"),
+        Line::from("Shift+Z: Autosquash"),
+        Line::from("ESC: Exit Review"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Other",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("C: Commit Panel"),
+        Line::from("Shift+C: Commit (Editor)"),
+        Line::from("ESC: Reset to Defaults"),
+    ]
+}
+
+/// The extended help view, before wrapping
+pub fn extended_help_lines() -> Vec<Line<'static>> {
+    vec![
+        Line::from(Span::styled(
+            "HUNKY - Extended Help",
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "═══════════════════════════════════════════════════════════",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "OVERVIEW",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("Hunky is a terminal UI for reviewing and staging git changes at the hunk"),
+        Line::from("or line level. It provides two main modes for different workflows:"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "MODES",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(
+                "View Mode",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" - Browse all current changes"),
+        ]),
+        Line::from("  • Shows all changes from HEAD to working directory"),
+        Line::from("  • Full navigation with Space (next) and Shift+Space (previous)"),
+        Line::from("  • Ideal for reviewing existing changes before committing"),
+        Line::from("  • Default mode when starting Hunky"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(
+                "Streaming Mode",
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" - Watch new changes as they appear"),
+        ]),
+        Line::from("  • Only shows hunks that appear after entering this mode"),
+        Line::from("  • Two sub-modes:"),
+        Line::from("    - Buffered: Manual advance with Space key"),
+        Line::from("    - Auto (Fast/Medium/Slow): Automatic advancement with timing"),
+        Line::from("  • Perfect for TDD workflows or watching build output changes"),
+        Line::from("  • Press M to cycle through streaming options"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(
+                "Review Mode",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" - Step through a recent commit"),
+        ]),
+        Line::from("  • Press R to pick a commit, S to accept hunks, Esc to leave"),
+        Line::from("  • Press G after a \"new commit\" notice to review that commit"),
+        Line::from("  • Shift+F commits the staged changes as a fixup! of that commit"),
+        Line::from("  • Shift+A amends HEAD with the staged changes"),
+        Line::from("  • Shift+Z twice autosquashes pending fixups into that commit"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "NAVIGATION",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("  Space           Next hunk (all modes)"),
+        Line::from("  B               Previous hunk (View & Buffered modes)"),
+        Line::from("  J/K or ↓/↑      Scroll hunk view or navigate in line mode"),
+        Line::from("  N/P             Next/Previous file"),
+        Line::from("  Tab             Cycle focus forward (File → Hunk → Help)"),
+        Line::from("  Shift+Tab       Cycle focus backward"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "STAGING",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("  S               Smart stage/unstage toggle"),
+        Line::from("  L               Toggle Line Mode for line-level staging"),
+        Line::from("  V               Start/stop a range selection in Line Mode"),
+        Line::from("  D               Discard selected lines or the hunk (press twice)"),
+        Line::from(
+            "  X               Split hunk at context gaps (at the selected line in Line Mode)",
+        ),
+        Line::from("  Shift+X         Join a split hunk back together"),
+        Line::from("  E               Edit the hunk in $EDITOR and stage the result"),
+        Line::from("  C               Open the commit panel (Shift+C: git commit in $EDITOR)"),
+        Line::from("  T               Mark/unmark the hunk for stashing"),
+        Line::from("  Shift+Z         Stash marked hunks (or the current hunk)"),
+        Line::from("  Z               Stash list: preview, apply, pop or drop stashes"),
+        Line::from("  Shift+M         Conflict view: pick ours/theirs/both, Enter marks resolved"),
+        Line::from("  Shift+N         Intent to add: track a new file so its lines can be staged"),
+        Line::from("  Shift+O         Show changes inside submodules, staged in their own index"),
+        Line::from("  [ / ]           Switch between watched repositories and worktrees"),
+        Line::from(""),
+        Line::from("Smart Toggle Behavior (Hunk Mode):"),
+        Line::from("  • Unstaged → Press S → Fully staged"),
+        Line::from("  • Partially staged → Press S → Fully staged"),
+        Line::from("  • Fully staged → Press S → Fully unstaged"),
+        Line::from(""),
+        Line::from("In Line Mode:"),
+        Line::from("  • Use J/K to navigate between changed lines (+ or -)"),
+        Line::from("  • Press S to toggle staging for the selected line"),
+        Line::from("  • Staged lines show a ✓ indicator"),
+        Line::from("  • Press V to anchor a range, extend it with J/K, then S or D"),
+        Line::from("  • External changes (e.g., git add -p) are detected automatically"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "DISPLAY OPTIONS",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("  H               Toggle help sidebar"),
+        Line::from("  Shift+H         Toggle this extended help view"),
+        Line::from("  F               Toggle filenames-only mode (hide diffs)"),
+        Line::from("  W               Toggle line wrapping"),
+        Line::from("  Shift+W         File activity: event counts and a timeline per file"),
+        Line::from("  Shift+T         Snapshot timeline: ←/→ step back through past diffs"),
+        Line::from("  Shift+D         What changed since you last looked (in the timeline:"),
+        Line::from("                  from the Enter-marked or previous snapshot)"),
+        Line::from("  Shift+E         Save the history (JSON) and this diff (patch) under"),
+        Line::from("                  .git/hunky; reopen the JSON with hunky --open"),
+        Line::from("  Y               Toggle syntax highlighting"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "MODE SWITCHING",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("  M               Cycle through modes:"),
+        Line::from("                    View → Streaming (Buffered) → Streaming (Auto Fast)"),
+        Line::from("                    → Streaming (Auto Medium) → Streaming (Auto Slow) → View"),
+        Line::from(""),
+        Line::from("When switching to Streaming mode, Hunky captures the current state and"),
+        Line::from("will only show new hunks that appear after the switch."),
+        Line::from(""),
+        Line::from(Span::styled(
+            "RESET TO DEFAULTS",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from("  ESC             Reset everything to defaults:"),
+        Line::from("                    • Exit extended help view"),
+        Line::from("                    • Set mode to View"),
+        Line::from("                    • Exit line mode"),
+        Line::from("                    • Focus hunk view"),
+        Line::from("                    • Hide help sidebar"),
+        Line::from(""),
+        Line::from(Span::styled(
+            "WORKFLOWS",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Code Review", Style::default().fg(Color::Green)),
+            Span::raw(" - Use View mode to browse all changes, stage what you want"),
+        ]),
+        Line::from("to commit, then press C to write the message in the commit panel"),
+        Line::from("(Ctrl+A amend, Ctrl+O sign-off, Ctrl+T template, Ctrl+S commit)."),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("TDD Workflow", Style::default().fg(Color::Magenta)),
+            Span::raw(" - Switch to Streaming (Auto) mode, run tests in"),
+        ]),
+        Line::from("another terminal, and watch test changes flow through Hunky as you"),
+        Line::from("iterate on your code."),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Partial Staging", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Enable Line Mode (L) to stage specific lines"),
+        ]),
+        Line::from("within a hunk. Great for separating formatting changes from logic changes."),
+        Line::from(""),
+        Line::from(Span::styled(
+            "═══════════════════════════════════════════════════════════",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(""),
+        Line::from("Press ESC to exit this help view and return to normal operation."),
+        Line::from("Press J/K to scroll through this help."),
+    ]
+}
+
 #[cfg(test)]
 #[path = "../tests/ui.rs"]
 mod tests;
@@ -1,10 +1,14 @@
 use anyhow::Result;
 use git2::Repository;
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher};
-use std::path::Path;
-use tokio::sync::mpsc;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 
-use crate::diff::DiffSnapshot;
+use crate::activity::{FileTouch, TouchKind};
+use crate::diff::{CleanReason, DiffSnapshot, HeadMove, HeadMoveKind, HeadState, UntrackedMode};
 use crate::git::GitRepo;
 
 // Debug logging helper
@@ -12,73 +16,709 @@ fn debug_log(msg: String) {
     crate::logger::debug(msg);
 }
 
+/// What the watcher reports to the app
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// The working tree, index or refs changed; a fresh diff of the working tree
+    Snapshot(DiffSnapshot),
+    /// HEAD moved to another commit or branch. Sent before the snapshot taken in the same
+    /// pass, but a snapshot for an earlier ref write, like the new branch of `checkout -b`,
+    /// can arrive first; that one carries no changes from the move.
+    HeadMoved(HeadMove),
+    /// The working tree went from having changes to having none. Sent after the HEAD move
+    /// that caused it, if any, and before the empty snapshot.
+    TreeClean(CleanReason),
+    /// Something the user should know about the watcher itself, like falling back to polling
+    Warning(String),
+    /// Working tree files a file event just touched, sent as events arrive rather than
+    /// when the burst settles
+    Activity(Vec<FileTouch>),
+}
+
+/// How the watcher learns about file changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum WatchBackend {
+    /// Native file events, falling back to polling when they don't arrive
+    #[default]
+    Auto,
+    /// Native file events only (inotify, FSEvents, ReadDirectoryChangesW)
+    Native,
+    /// Compare file modification times every poll interval; works on NFS, bind mounts
+    /// and WSL paths into Windows drives
+    Poll,
+}
+
+/// How the watcher turns bursts of file events into snapshots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchConfig {
+    /// Quiet time after the last event of a burst before the snapshot is taken
+    pub quiet_period: Duration,
+    /// Longest the first event of a burst waits for a snapshot while events keep coming
+    pub max_latency: Duration,
+    pub backend: WatchBackend,
+    /// How often the poll backend looks for changed files
+    pub poll_interval: Duration,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            quiet_period: Duration::from_millis(250),
+            max_latency: Duration::from_millis(1000),
+            backend: WatchBackend::Auto,
+            poll_interval: Duration::from_millis(1000),
+        }
+    }
+}
+
+/// How long the auto backend waits for a native event about its probe file
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A settled burst of events
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Burst {
+    /// Whether it touched HEAD, refs or operation state
+    pub git_metadata: bool,
+    /// Working tree paths it touched, relative to the repository, or None when something
+    /// else changed too (like the index) and the whole snapshot must be rebuilt
+    pub paths: Option<Vec<PathBuf>>,
+}
+
+/// Trailing-edge debouncer: events are coalesced until the quiet period passes without
+/// a new one, or until the max latency since the first pending event is reached, so the
+/// last write of a burst always ends up in a snapshot.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    config: WatchConfig,
+    first_pending: Option<Instant>,
+    last_event: Option<Instant>,
+    // Whether the pending burst touched HEAD, refs or operation state
+    git_metadata: bool,
+    paths: BTreeSet<PathBuf>,
+    needs_full_snapshot: bool,
+}
+
+impl Debouncer {
+    pub fn new(config: WatchConfig) -> Self {
+        Self {
+            config,
+            first_pending: None,
+            last_event: None,
+            git_metadata: false,
+            paths: BTreeSet::new(),
+            needs_full_snapshot: false,
+        }
+    }
+
+    pub fn event(&mut self, now: Instant, git_metadata: bool) {
+        self.first_pending.get_or_insert(now);
+        self.last_event = Some(now);
+        self.git_metadata |= git_metadata;
+    }
+
+    /// Record a path the pending burst touched; only working tree paths can be re-diffed
+    /// on their own
+    pub fn touched(&mut self, rel_path: &Path) {
+        let in_git_dir = rel_path
+            .components()
+            .next()
+            .is_some_and(|c| c.as_os_str() == ".git");
+        if in_git_dir {
+            self.needs_full_snapshot = true;
+        } else {
+            self.paths.insert(rel_path.to_path_buf());
+        }
+    }
+
+    /// Something changed that can affect any file, like the ignore rules
+    pub fn rules_changed(&mut self, now: Instant) {
+        self.event(now, false);
+        self.needs_full_snapshot = true;
+    }
+
+    /// When the pending burst is due, or None when nothing is pending
+    pub fn deadline(&self) -> Option<Instant> {
+        let first = self.first_pending?;
+        let last = self.last_event?;
+        Some((last + self.config.quiet_period).min(first + self.config.max_latency))
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.deadline().is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Clear the pending burst and return what it touched
+    pub fn take(&mut self) -> Burst {
+        self.first_pending = None;
+        self.last_event = None;
+        let paths = std::mem::take(&mut self.paths);
+        let needs_full_snapshot = std::mem::take(&mut self.needs_full_snapshot);
+        Burst {
+            git_metadata: std::mem::take(&mut self.git_metadata),
+            paths: (!needs_full_snapshot && !paths.is_empty()).then(|| paths.into_iter().collect()),
+        }
+    }
+}
+
+/// Watches a repository and sends `WatchEvent`s. File events arrive on an async channel,
+/// snapshots are computed on the blocking pool, and the task stops when the watcher is
+/// shut down or dropped.
 pub struct FileWatcher {
-    _watcher: RecommendedWatcher,
+    // None when polling from the start
+    _watcher: Option<RecommendedWatcher>,
+    cancel: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
 }
 
 impl FileWatcher {
-    pub fn new(
+    #[cfg(test)]
+    pub fn new(git_repo: GitRepo, event_sender: mpsc::UnboundedSender<WatchEvent>) -> Result<Self> {
+        Self::with_config(git_repo, event_sender, WatchConfig::default())
+    }
+
+    pub fn with_config(
         git_repo: GitRepo,
-        snapshot_sender: mpsc::UnboundedSender<DiffSnapshot>,
+        event_sender: mpsc::UnboundedSender<WatchEvent>,
+        config: WatchConfig,
     ) -> Result<Self> {
         let repo_path = git_repo.repo_path().to_path_buf();
+        // A linked worktree keeps its HEAD and index under the main repository's .git
+        let external_git_dirs = git_repo.external_git_dirs().unwrap_or_default();
+
+        // notify calls back on its own thread; forward into the async task
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut startup_warning = None;
+        let watcher = match config.backend {
+            WatchBackend::Poll => None,
+            WatchBackend::Native => Some(start_native(&git_repo, &external_git_dirs, tx.clone())?),
+            WatchBackend::Auto => match start_native(&git_repo, &external_git_dirs, tx.clone()) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    startup_warning = Some(format!(
+                        "Native file watching failed for {} ({}); polling instead",
+                        repo_path.display(),
+                        e
+                    ));
+                    None
+                }
+            },
+        };
+
+        let last_head = git_repo.head_state().unwrap_or_default();
+        let (cancel, cancelled) = oneshot::channel();
+
+        let source = EventSource {
+            sender: tx,
+            native: watcher.is_some(),
+            startup_warning,
+        };
+        let task = tokio::spawn(watch_loop(
+            git_repo,
+            event_sender,
+            config,
+            rx,
+            source,
+            cancelled,
+            external_git_dirs,
+            last_head,
+        ));
+
+        Ok(Self {
+            _watcher: watcher,
+            cancel: Some(cancel),
+            task: Some(task),
+        })
+    }
+
+    /// Stop the watcher and wait for its task to finish
+    #[allow(dead_code)]
+    pub async fn shutdown(mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            let _ = cancel.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
 
-        let (tx, rx) = std::sync::mpsc::channel();
-
-        let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-
-        watcher.watch(repo_path.as_ref(), RecursiveMode::Recursive)?;
-
-        // Spawn a task to handle file system events
-        tokio::spawn(async move {
-            let mut last_snapshot_time = std::time::Instant::now();
-            let debounce_duration = std::time::Duration::from_millis(500);
-
-            debug_log(format!("File watcher started for {:?}", repo_path));
-
-            loop {
-                match rx.recv() {
-                    Ok(Ok(event)) => {
-                        // Only process events for git-tracked files
-                        if should_process_event(&event, &repo_path) {
-                            debug_log(format!("Received event: {:?}", event));
-                            debug_log("Processing event for snapshot".to_string());
-                            // Debounce: only create a new snapshot if enough time has passed
-                            let now = std::time::Instant::now();
-                            if now.duration_since(last_snapshot_time) >= debounce_duration {
-                                if let Ok(snapshot) = git_repo.get_diff_snapshot() {
-                                    debug_log(format!(
-                                        "Created snapshot with {} files",
-                                        snapshot.files.len()
-                                    ));
-                                    // Only send if there are actual changes
-                                    if !snapshot.files.is_empty() {
-                                        let _ = snapshot_sender.send(snapshot);
-                                        last_snapshot_time = now;
-                                    } else {
-                                        debug_log("Snapshot was empty, not sending".to_string());
-                                    }
-                                }
-                            } else {
-                                debug_log("Debouncing, too soon since last snapshot".to_string());
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        // The task notices on its next poll and exits; nothing to wait for here
+        if let Some(cancel) = self.cancel.take() {
+            let _ = cancel.send(());
+        }
+    }
+}
+
+type RawEventSender = mpsc::UnboundedSender<notify::Result<Event>>;
+
+/// Start native watches on the working tree and any git directories outside it
+fn start_native(
+    git_repo: &GitRepo,
+    external_git_dirs: &[PathBuf],
+    tx: RawEventSender,
+) -> Result<RecommendedWatcher> {
+    let repo_path = git_repo.repo_path();
+    let mut watcher = RecommendedWatcher::new(
+        move |result: notify::Result<Event>| {
+            let _ = tx.send(result);
+        },
+        Config::default(),
+    )?;
+
+    watcher.watch(repo_path, RecursiveMode::Recursive)?;
+
+    for git_dir in external_git_dirs {
+        let nested = external_git_dirs
+            .iter()
+            .any(|other| other != git_dir && git_dir.starts_with(other));
+        if !nested {
+            watcher.watch(git_dir, RecursiveMode::Recursive)?;
+        }
+    }
+
+    // The global excludes file usually lives outside the repository
+    let excludes_dir = IgnoreMatcher::new(git_repo)
+        .excludes_file()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf);
+    if let Some(dir) = excludes_dir.filter(|dir| dir.is_dir() && !dir.starts_with(repo_path)) {
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            debug_log(format!("Failed to watch {:?}: {}", dir, e));
+        }
+    }
+
+    Ok(watcher)
+}
+
+/// Where the watch loop's raw events come from
+struct EventSource {
+    // Lets the loop start a poller feeding the same channel
+    sender: RawEventSender,
+    native: bool,
+    startup_warning: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn watch_loop(
+    git_repo: GitRepo,
+    event_sender: mpsc::UnboundedSender<WatchEvent>,
+    config: WatchConfig,
+    mut rx: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    source: EventSource,
+    mut cancelled: oneshot::Receiver<()>,
+    external_git_dirs: Vec<PathBuf>,
+    mut last_head: HeadState,
+) {
+    let repo_path = git_repo.repo_path().to_path_buf();
+    let mut debouncer = Debouncer::new(config);
+    let mut ignore = IgnoreMatcher::new(&git_repo);
+    // The last snapshot taken and the settings it was taken with; a burst of working
+    // tree edits updates it in place
+    let mut previous: Option<(DiffSnapshot, SnapshotSettings)> = None;
+
+    debug_log(format!("File watcher started for {:?}", repo_path));
+
+    // Start from the tree as it is, so the first burst can be incremental and a tree that
+    // goes clean is noticed
+    let mut had_changes = true;
+    let repo = git_repo.clone();
+    let settings = SnapshotSettings::of(&git_repo);
+    tokio::select! {
+        _ = &mut cancelled => return,
+        result = tokio::task::spawn_blocking(move || repo.get_diff_snapshot()) => {
+            if let Ok(Ok(snapshot)) = result {
+                had_changes = !snapshot.files.is_empty();
+                previous = Some((snapshot, settings));
+            }
+        }
+    }
+
+    let git_dirs = if external_git_dirs.is_empty() {
+        vec![repo_path.join(".git")]
+    } else {
+        external_git_dirs.clone()
+    };
+    // Stops its thread when the loop ends
+    let mut poller = None;
+    let mut probe = None;
+    if let Some(warning) = source.startup_warning {
+        debug_log(warning.clone());
+        let _ = event_sender.send(WatchEvent::Warning(warning));
+    }
+    match (config.backend, source.native) {
+        (WatchBackend::Native, true) => {}
+        (WatchBackend::Auto, true) => probe = Probe::start(&git_dirs[0]),
+        _ => {
+            poller = Some(Poller::start(
+                git_repo.clone(),
+                git_dirs.clone(),
+                config.poll_interval,
+                source.sender.clone(),
+            ))
+        }
+    }
+
+    loop {
+        // Wait for the next event, until the pending burst is due, or until the native
+        // watcher has had long enough to report the probe
+        let deadline = match (
+            debouncer.deadline(),
+            probe.as_ref().map(|p: &Probe| p.deadline),
+        ) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        tokio::select! {
+            _ = &mut cancelled => break,
+            received = rx.recv() => match received {
+                Some(Ok(event)) => {
+                    if probe.as_ref().is_some_and(|probe| event.paths.contains(&probe.path)) {
+                        debug_log("Native file events confirmed".to_string());
+                        probe = None;
+                        continue;
+                    }
+                    let event = map_external_git_dirs(event, &repo_path, &external_git_dirs);
+                    // New ignore rules can list or hide any untracked file
+                    if ignore.refresh_for(&event) {
+                        debouncer.rules_changed(Instant::now());
+                    }
+                    // Only process events for git-tracked files
+                    if should_process_event(&event, &repo_path, &mut ignore) {
+                        debug_log(format!("Received event: {:?}", event));
+                        // HEAD and ref moves are rare and must reach the app even when
+                        // the working tree ends up clean, so they are remembered.
+                        let git_metadata = is_git_metadata_event(&event, &repo_path);
+                        debouncer.event(Instant::now(), git_metadata);
+                        for path in &event.paths {
+                            if let Ok(rel_path) = path.strip_prefix(&repo_path) {
+                                debouncer.touched(rel_path);
                             }
-                        } else if crate::logger::filtered_events_enabled() {
-                            crate::logger::trace(format!("Filtered event: {:?}", event));
                         }
+                        let touches =
+                            worktree_touches(&event, &repo_path, &mut ignore, SystemTime::now());
+                        if !touches.is_empty() {
+                            let _ = event_sender.send(WatchEvent::Activity(touches));
+                        }
+                    } else if crate::logger::filtered_events_enabled() {
+                        crate::logger::trace(format!("Filtered event: {:?}", event));
                     }
-                    Ok(Err(e)) => {
-                        debug_log(format!("Watch error: {:?}", e));
+                }
+                Some(Err(e)) => debug_log(format!("Watch error: {:?}", e)),
+                None => break,
+            },
+            _ = sleep_until(deadline) => {}
+        }
+
+        if probe
+            .as_ref()
+            .is_some_and(|probe| Instant::now() >= probe.deadline)
+        {
+            probe = None;
+            let warning = format!(
+                "File events are not arriving for {}; polling every {}ms instead",
+                repo_path.display(),
+                config.poll_interval.as_millis()
+            );
+            debug_log(warning.clone());
+            let _ = event_sender.send(WatchEvent::Warning(warning));
+            poller = Some(Poller::start(
+                git_repo.clone(),
+                git_dirs.clone(),
+                config.poll_interval,
+                source.sender.clone(),
+            ));
+        }
+
+        if !debouncer.is_due(Instant::now()) {
+            continue;
+        }
+        let burst = debouncer.take();
+        let git_metadata = burst.git_metadata;
+        debug_log(format!(
+            "Burst settled, taking snapshot of {}",
+            match &burst.paths {
+                Some(paths) => format!("{} paths", paths.len()),
+                None => "the whole tree".to_string(),
+            }
+        ));
+
+        let repo = git_repo.clone();
+        let previous_head = last_head.clone();
+        let settings = SnapshotSettings::of(&git_repo);
+        let previous_snapshot = previous
+            .take()
+            .filter(|(_, taken_with)| *taken_with == settings)
+            .map(|(snapshot, _)| snapshot);
+        let snapshot_task = tokio::task::spawn_blocking(move || {
+            take_snapshot(&repo, &previous_head, previous_snapshot, burst.paths)
+        });
+        let (head, head_move, snapshot) = tokio::select! {
+            _ = &mut cancelled => break,
+            result = snapshot_task => match result {
+                Ok(result) => result,
+                Err(e) => {
+                    debug_log(format!("Snapshot task failed: {}", e));
+                    continue;
+                }
+            },
+        };
+
+        if let Some(head) = head {
+            last_head = head;
+        }
+        let clean_reason = match head_move.as_ref().map(|head_move| head_move.kind) {
+            None => CleanReason::Cleared,
+            Some(HeadMoveKind::Commit) => CleanReason::Committed,
+            Some(HeadMoveKind::Checkout | HeadMoveKind::Reset) => CleanReason::Reset,
+        };
+        if let Some(head_move) = head_move {
+            debug_log(format!("HEAD moved: {:?}", head_move));
+            let _ = event_sender.send(WatchEvent::HeadMoved(head_move));
+        }
+        if let Some(snapshot) = snapshot {
+            previous = Some((snapshot.clone(), settings));
+            debug_log(format!(
+                "Created snapshot with {} files",
+                snapshot.files.len()
+            ));
+            let has_changes = !snapshot.files.is_empty();
+            if had_changes && !has_changes {
+                debug_log(format!("Working tree is clean: {:?}", clean_reason));
+                let _ = event_sender.send(WatchEvent::TreeClean(clean_reason));
+            }
+            // A clean tree is sent once, and again only when HEAD moves
+            if has_changes || had_changes || git_metadata {
+                let _ = event_sender.send(WatchEvent::Snapshot(snapshot));
+            } else {
+                debug_log("Snapshot is still empty, not sending".to_string());
+            }
+            had_changes = has_changes;
+        }
+    }
+
+    drop(poller);
+    debug_log(format!("File watcher stopped for {:?}", repo_path));
+}
+
+/// A file the auto backend writes into the git directory to see whether native events
+/// arrive; removed again when dropped
+struct Probe {
+    path: PathBuf,
+    deadline: Instant,
+}
+
+impl Probe {
+    fn start(git_dir: &Path) -> Option<Self> {
+        let path = git_dir.join(format!("hunky-watch-probe-{}", std::process::id()));
+        // Without a probe there is nothing to go on; trust the native watcher
+        if let Err(e) = std::fs::write(&path, b"") {
+            debug_log(format!("Failed to write watch probe {:?}: {}", path, e));
+            return None;
+        }
+        Some(Self {
+            path,
+            deadline: Instant::now() + PROBE_TIMEOUT,
+        })
+    }
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Modification time and size of a file as last seen by the poller
+type Fingerprint = (Option<SystemTime>, u64);
+
+/// Files of a git directory whose changes matter, besides everything under `refs`
+const POLLED_GIT_FILES: &[&str] = &[
+    "index",
+    "HEAD",
+    "ORIG_HEAD",
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+    "BISECT_LOG",
+    "packed-refs",
+    "config",
+    "info/exclude",
+];
+
+/// Watches by comparing modification times and sizes on a dedicated thread, for
+/// filesystems that never deliver native events. Changes are reported as notify events
+/// so they go through the same filtering and debouncing.
+struct Poller {
+    stop: Option<std::sync::mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Poller {
+    fn start(
+        git_repo: GitRepo,
+        git_dirs: Vec<PathBuf>,
+        interval: Duration,
+        tx: RawEventSender,
+    ) -> Self {
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut last = poll_scan(&git_repo, &git_dirs);
+            // Sleeps between scans; dropping the sender ends the thread
+            while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(interval)
+            {
+                let next = poll_scan(&git_repo, &git_dirs);
+                for event in poll_events(&last, &next) {
+                    if tx.send(Ok(event)).is_err() {
+                        return;
                     }
-                    Err(_) => break,
                 }
+                last = next;
             }
         });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Fingerprints of the tracked and unignored untracked files, the git directories' state
+/// files and refs, and submodule indexes
+fn poll_scan(git_repo: &GitRepo, git_dirs: &[PathBuf]) -> HashMap<PathBuf, Fingerprint> {
+    let mut files = HashMap::new();
+    let repo_path = git_repo.repo_path();
+    for rel_path in git_repo.watched_paths().unwrap_or_default() {
+        fingerprint(repo_path.join(rel_path), &mut files);
+    }
+    for git_dir in git_dirs {
+        for name in POLLED_GIT_FILES {
+            fingerprint(git_dir.join(name), &mut files);
+        }
+        for dir in ["refs", "rebase-merge", "rebase-apply"] {
+            fingerprint_tree(&git_dir.join(dir), &|_| true, &mut files);
+        }
+        fingerprint_tree(
+            &git_dir.join("modules"),
+            &|path| path.file_name().is_some_and(|name| name == "index"),
+            &mut files,
+        );
+    }
+    files
+}
+
+fn fingerprint(path: PathBuf, files: &mut HashMap<PathBuf, Fingerprint>) {
+    if let Ok(meta) = std::fs::metadata(&path) {
+        if meta.is_file() {
+            files.insert(path, (meta.modified().ok(), meta.len()));
+        }
+    }
+}
+
+fn fingerprint_tree(
+    dir: &Path,
+    keep: &dyn Fn(&Path) -> bool,
+    files: &mut HashMap<PathBuf, Fingerprint>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            fingerprint_tree(&path, keep, files);
+        } else if keep(&path) {
+            fingerprint(path, files);
+        }
+    }
+}
+
+/// Events for files that appeared, changed or went away between two scans
+fn poll_events(
+    last: &HashMap<PathBuf, Fingerprint>,
+    next: &HashMap<PathBuf, Fingerprint>,
+) -> Vec<Event> {
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+    use notify::EventKind;
+
+    let mut events = Vec::new();
+    for (path, fingerprint) in next {
+        let kind = match last.get(path) {
+            None => EventKind::Create(CreateKind::File),
+            Some(previous) if previous != fingerprint => EventKind::Modify(ModifyKind::Any),
+            Some(_) => continue,
+        };
+        events.push(Event::new(kind).add_path(path.clone()));
+    }
+    for path in last.keys().filter(|path| !next.contains_key(*path)) {
+        events.push(Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.clone()));
+    }
+    events
+}
+
+/// Settings that change what a snapshot lists, so one taken under other settings can't
+/// be updated incrementally
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SnapshotSettings {
+    untracked_mode: UntrackedMode,
+    recurse_submodules: bool,
+}
+
+impl SnapshotSettings {
+    fn of(git_repo: &GitRepo) -> Self {
+        Self {
+            untracked_mode: git_repo.untracked_mode(),
+            recurse_submodules: git_repo.recurses_submodules(),
+        }
+    }
+}
+
+/// Sleep until the deadline, or forever when nothing is pending
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
 
-        Ok(Self { _watcher: watcher })
+/// Runs on the blocking pool. HEAD is checked before the snapshot, so a move seen in this
+/// pass is reported before the snapshot taken with it. A move whose ref writes land in
+/// separate bursts is only seen once HEAD itself changes, after the earlier bursts'
+/// snapshots. With a previous snapshot and the paths of a working-tree-only burst, only
+/// those paths are re-diffed.
+fn take_snapshot(
+    git_repo: &GitRepo,
+    last_head: &HeadState,
+    previous: Option<DiffSnapshot>,
+    changed_paths: Option<Vec<PathBuf>>,
+) -> (Option<HeadState>, Option<HeadMove>, Option<DiffSnapshot>) {
+    let mut head_move = None;
+    let head = git_repo.head_state().ok();
+    if let Some(head) = &head {
+        match git_repo.describe_head_move(last_head, head) {
+            Ok(head_moved) => head_move = head_moved,
+            Err(e) => debug_log(format!("Failed to describe HEAD move: {}", e)),
+        }
     }
+    let snapshot = match (previous, changed_paths) {
+        (Some(previous), Some(paths)) => git_repo.update_snapshot(&previous, &paths),
+        _ => git_repo.get_diff_snapshot(),
+    };
+    (head, head_move, snapshot.ok())
 }
 
-fn should_process_event(event: &Event, repo_path: &Path) -> bool {
+fn should_process_event(event: &Event, repo_path: &Path, ignore: &mut IgnoreMatcher) -> bool {
     use notify::EventKind;
 
     // Filter out events we don't care about
@@ -86,10 +726,22 @@ fn should_process_event(event: &Event, repo_path: &Path) -> bool {
         EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => {
             // Check if any of the paths are:
             // 1. Not in .git directory (working directory changes), OR
-            // 2. The .git/index file specifically (staging changes)
+            // 2. The .git/index file specifically (staging changes), OR
+            // 3. HEAD, refs or operation state (branch/HEAD changes for the header)
+            if is_git_metadata_event(event, repo_path) {
+                return true;
+            }
+
             event.paths.iter().any(|path| {
                 // Check if it's the git index file
-                if path.ends_with(".git/index") {
+                if *path == repo_path.join(".git/index") {
+                    return true;
+                }
+
+                // Or a submodule's index, kept under .git/modules/<name>/
+                if path.file_name().is_some_and(|name| name == "index")
+                    && path.starts_with(repo_path.join(".git/modules"))
+                {
                     return true;
                 }
 
@@ -109,18 +761,230 @@ fn should_process_event(event: &Event, repo_path: &Path) -> bool {
                 }
 
                 // Ignore files excluded by gitignore/excludes.
-                !is_git_ignored(repo_path, rel_path)
+                !ignore.is_ignored(rel_path)
             })
         }
         _ => false,
     }
 }
 
-fn is_git_ignored(repo_path: &Path, rel_path: &Path) -> bool {
-    match Repository::open(repo_path) {
-        Ok(repo) => repo.status_should_ignore(rel_path).unwrap_or(false),
-        Err(_) => false,
+/// The event's paths that are working tree files rather than git metadata or ignored
+fn worktree_touches(
+    event: &Event,
+    repo_path: &Path,
+    ignore: &mut IgnoreMatcher,
+    at: SystemTime,
+) -> Vec<FileTouch> {
+    let Some(kind) = TouchKind::from_event(&event.kind) else {
+        return Vec::new();
+    };
+    event
+        .paths
+        .iter()
+        .filter_map(|path| path.strip_prefix(repo_path).ok())
+        .filter(|rel_path| {
+            !rel_path.as_os_str().is_empty()
+                && !rel_path.starts_with(".git")
+                && !ignore.is_ignored(rel_path)
+        })
+        .map(|rel_path| FileTouch {
+            path: rel_path.to_path_buf(),
+            kind,
+            at,
+        })
+        .collect()
+}
+
+/// Whether the event touches where HEAD or the refs point, or the state files of an
+/// in-progress rebase, merge, cherry-pick, revert or bisect.
+fn is_git_metadata_event(event: &Event, repo_path: &Path) -> bool {
+    let git_dir = repo_path.join(".git");
+    event.paths.iter().any(|path| {
+        let Ok(rel_path) = path.strip_prefix(&git_dir) else {
+            return false;
+        };
+        if rel_path.extension().is_some_and(|ext| ext == "lock") {
+            return false;
+        }
+
+        let Some(first) = rel_path.components().next() else {
+            return false;
+        };
+        matches!(
+            first.as_os_str().to_str(),
+            Some(
+                "HEAD"
+                    | "ORIG_HEAD"
+                    | "MERGE_HEAD"
+                    | "CHERRY_PICK_HEAD"
+                    | "REVERT_HEAD"
+                    | "BISECT_LOG"
+                    | "packed-refs"
+                    | "refs"
+                    | "rebase-merge"
+                    | "rebase-apply"
+            )
+        )
+    })
+}
+
+/// What a linked worktree shares with the main repository's git directory. Everything
+/// else there, like the main worktree's HEAD and index, belongs to another worktree.
+const SHARED_GIT_PATHS: &[&str] = &["refs", "packed-refs", "info/exclude"];
+
+/// Rewrite paths under a git directory outside the working tree as if they were under
+/// `<repo>/.git`, so worktree HEAD, index and ref changes are filtered like in-tree ones.
+/// The most specific directory wins: a worktree's own git directory sits inside the
+/// main repository's, of which only the shared refs are mapped.
+fn map_external_git_dirs(mut event: Event, repo_path: &Path, git_dirs: &[PathBuf]) -> Event {
+    if git_dirs.is_empty() {
+        return event;
+    }
+    for path in &mut event.paths {
+        let mapped = git_dirs
+            .iter()
+            .filter_map(|git_dir| Some((git_dir, path.strip_prefix(git_dir).ok()?)))
+            .min_by_key(|(_, rel_path)| rel_path.components().count())
+            .filter(|(git_dir, rel_path)| {
+                let common = git_dirs
+                    .iter()
+                    .any(|other| other != *git_dir && other.starts_with(git_dir));
+                !common
+                    || SHARED_GIT_PATHS
+                        .iter()
+                        .any(|shared| rel_path.starts_with(shared))
+            })
+            .map(|(_, rel_path)| repo_path.join(".git").join(rel_path));
+        if let Some(mapped) = mapped {
+            *path = mapped;
+        }
+    }
+    event
+}
+
+/// Directories that build tools fill with thousands of files. Once one is known to be
+/// ignored, everything under it is skipped without asking git again.
+const HEAVY_DIRS: &[&str] = &[
+    "target",
+    "node_modules",
+    "build",
+    "dist",
+    ".venv",
+    "__pycache__",
+    ".gradle",
+];
+
+/// Cached answers kept before the cache starts over
+const IGNORE_CACHE_LIMIT: usize = 10_000;
+
+/// Answers whether working tree paths are gitignored. The repository and its ignore rules
+/// are loaded once and reloaded when a `.gitignore`, `.git/info/exclude`, the
+/// `core.excludesFile` or the repository config changes.
+pub struct IgnoreMatcher {
+    repo_path: PathBuf,
+    // Opens the repository wherever its git directory lives; without one nothing is ignored
+    git_repo: Option<GitRepo>,
+    repo: Option<Repository>,
+    excludes_file: Option<PathBuf>,
+    // (path, is directory) → ignored
+    cache: HashMap<(PathBuf, bool), bool>,
+}
+
+impl IgnoreMatcher {
+    pub fn new(git_repo: &GitRepo) -> Self {
+        let mut matcher = Self {
+            repo_path: git_repo.repo_path().to_path_buf(),
+            git_repo: Some(git_repo.clone()),
+            repo: None,
+            excludes_file: None,
+            cache: HashMap::new(),
+        };
+        matcher.reload();
+        matcher
+    }
+
+    fn reload(&mut self) {
+        self.repo = self
+            .git_repo
+            .as_ref()
+            .and_then(|git_repo| git_repo.open().ok());
+        self.excludes_file = self.repo.as_ref().and_then(excludes_file);
+        self.cache.clear();
+    }
+
+    /// The global excludes file in effect, which lives outside the working tree
+    pub fn excludes_file(&self) -> Option<&Path> {
+        self.excludes_file.as_deref()
+    }
+
+    /// Reload the rules if the event touches one of their sources; returns whether it did
+    pub fn refresh_for(&mut self, event: &Event) -> bool {
+        let git_dir = self.repo_path.join(".git");
+        let touches_rules = event.paths.iter().any(|path| {
+            (path.file_name().is_some_and(|name| name == ".gitignore")
+                && path.starts_with(&self.repo_path)
+                && !path.starts_with(&git_dir))
+                || *path == git_dir.join("info/exclude")
+                || *path == git_dir.join("config")
+                || self.excludes_file.as_deref() == Some(path.as_path())
+        });
+        if touches_rules {
+            debug_log("Ignore rules changed, reloading".to_string());
+            self.reload();
+        }
+        touches_rules
+    }
+
+    pub fn is_ignored(&mut self, rel_path: &Path) -> bool {
+        let mut dir = PathBuf::new();
+        for component in rel_path.components() {
+            dir.push(component);
+            let heavy = component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|name| HEAVY_DIRS.contains(&name));
+            if heavy && self.lookup(&dir, true) {
+                return true;
+            }
+        }
+        self.lookup(rel_path, false)
+    }
+
+    fn lookup(&mut self, rel_path: &Path, is_dir: bool) -> bool {
+        let key = (rel_path.to_path_buf(), is_dir);
+        if let Some(&ignored) = self.cache.get(&key) {
+            return ignored;
+        }
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        // libgit2 only applies directory patterns like `target/` to paths ending in a slash
+        let query = if is_dir {
+            PathBuf::from(format!("{}/", rel_path.display()))
+        } else {
+            rel_path.to_path_buf()
+        };
+        let ignored = repo.status_should_ignore(&query).unwrap_or(false);
+        if self.cache.len() >= IGNORE_CACHE_LIMIT {
+            self.cache.clear();
+        }
+        self.cache.insert(key, ignored);
+        ignored
+    }
+}
+
+/// `core.excludesFile`, or git's default of `$XDG_CONFIG_HOME/git/ignore`
+fn excludes_file(repo: &Repository) -> Option<PathBuf> {
+    if let Ok(path) = repo
+        .config()
+        .and_then(|config| config.get_path("core.excludesFile"))
+    {
+        return Some(path);
     }
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("git/ignore"))
 }
 
 #[cfg(test)]
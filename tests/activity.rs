@@ -0,0 +1,101 @@
+use super::*;
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn touch(path: &str, kind: TouchKind, secs: u64) -> FileTouch {
+    FileTouch {
+        path: PathBuf::from(path),
+        kind,
+        at: at(secs),
+    }
+}
+
+#[test]
+fn records_counts_per_kind_and_first_and_last_seen() {
+    let mut stats = ActivityStats::new();
+    stats.record(touch("src/lib.rs", TouchKind::Created, 1000));
+    stats.record(touch("src/lib.rs", TouchKind::Modified, 1005));
+    stats.record(touch("src/lib.rs", TouchKind::Modified, 1030));
+    stats.record(touch("old.rs", TouchKind::Removed, 1010));
+
+    let lib = stats.get(Path::new("src/lib.rs")).unwrap();
+    assert_eq!((lib.created, lib.modified, lib.removed), (1, 2, 0));
+    assert_eq!(lib.total(), 3);
+    assert_eq!(lib.first_seen, at(1000));
+    assert_eq!(lib.last_seen, at(1030));
+    assert_eq!(stats.len(), 2);
+}
+
+#[test]
+fn files_are_ordered_hottest_first() {
+    let mut stats = ActivityStats::new();
+    stats.record(touch("a.rs", TouchKind::Modified, 1000));
+    stats.record(touch("b.rs", TouchKind::Modified, 1000));
+    stats.record(touch("b.rs", TouchKind::Modified, 1001));
+    stats.record(touch("c.rs", TouchKind::Modified, 1002));
+
+    let order: Vec<_> = stats.files().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(
+        order,
+        vec![Path::new("b.rs"), Path::new("c.rs"), Path::new("a.rs")]
+    );
+}
+
+#[test]
+fn counts_bucket_events_ending_at_now_and_drop_old_history() {
+    let mut stats = ActivityStats::new();
+    stats.record(touch("a.rs", TouchKind::Modified, 1000));
+    stats.record(touch("a.rs", TouchKind::Modified, 1005));
+    stats.record(touch("a.rs", TouchKind::Modified, 1025));
+
+    let a = stats.get(Path::new("a.rs")).unwrap();
+    // Buckets 100, 101 and 102 end with the one holding 1025s
+    assert_eq!(a.counts(at(1025), 3), vec![2, 0, 1]);
+    assert_eq!(a.counts(at(1045), 3), vec![1, 0, 0]);
+
+    let later = 1000 + BUCKET_WIDTH.as_secs() * BUCKET_COUNT as u64;
+    stats.record(touch("a.rs", TouchKind::Modified, later));
+    let a = stats.get(Path::new("a.rs")).unwrap();
+    let history = a.counts(at(later), BUCKET_COUNT + 10);
+    assert_eq!(history.iter().sum::<u32>(), 2);
+    assert_eq!(a.total(), 4);
+}
+
+#[test]
+fn overall_counts_add_up_every_file() {
+    let mut stats = ActivityStats::new();
+    stats.record(touch("a.rs", TouchKind::Modified, 1000));
+    stats.record(touch("b.rs", TouchKind::Created, 1001));
+    stats.record(touch("b.rs", TouchKind::Modified, 1011));
+
+    assert_eq!(stats.counts(at(1011), 2), vec![2, 1]);
+}
+
+#[test]
+fn sparkline_scales_to_the_peak_and_leaves_idle_buckets_blank() {
+    assert_eq!(sparkline(&[0, 1, 4, 8], 8), " ▁▄█");
+    assert_eq!(sparkline(&[0, 0], 0), "  ");
+    assert_eq!(sparkline(&[20], 8), "█");
+}
+
+#[test]
+fn maps_notify_event_kinds() {
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+    use notify::EventKind;
+
+    assert_eq!(
+        TouchKind::from_event(&EventKind::Create(CreateKind::File)),
+        Some(TouchKind::Created)
+    );
+    assert_eq!(
+        TouchKind::from_event(&EventKind::Modify(ModifyKind::Any)),
+        Some(TouchKind::Modified)
+    );
+    assert_eq!(
+        TouchKind::from_event(&EventKind::Remove(RemoveKind::File)),
+        Some(TouchKind::Removed)
+    );
+    assert_eq!(TouchKind::from_event(&EventKind::Any), None);
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::activity::{FileTouch, TouchKind};
 use crate::diff::Hunk;
 use crate::ui::UI;
 use ratatui::{backend::TestBackend, Terminal};
@@ -88,6 +89,8 @@ fn sample_snapshot() -> DiffSnapshot {
     let file2 = PathBuf::from("b.txt");
     DiffSnapshot {
         timestamp: SystemTime::now(),
+        head: None,
+        untracked_overflow: 0,
         files: vec![
             FileChange {
                 path: file1.clone(),
@@ -318,12 +321,18 @@ async fn navigation_and_scroll_helpers_cover_core_branches() {
     app.scroll_offset = 50;
     app.clamp_scroll_offset(20);
     assert_eq!(app.scroll_offset, 0);
-    app.help_scroll_offset = 50;
+    app.help_scroll_offset = u16::MAX;
     app.clamp_help_scroll_offset(10);
-    assert_eq!(app.help_scroll_offset, 22);
-    app.extended_help_scroll_offset = 500;
+    assert_eq!(
+        app.help_scroll_offset as usize,
+        app.help_content_height() - 10
+    );
+    app.extended_help_scroll_offset = u16::MAX;
     app.clamp_extended_help_scroll_offset(20);
-    assert_eq!(app.extended_help_scroll_offset, 88);
+    assert_eq!(
+        app.extended_help_scroll_offset as usize,
+        app.extended_help_content_height() - 20
+    );
 }
 
 #[tokio::test]
@@ -377,6 +386,8 @@ async fn ui_draw_clears_previous_hunk_text_when_advancing() {
     let path = PathBuf::from("garble.txt");
     let snapshot = DiffSnapshot {
         timestamp: SystemTime::now(),
+        head: None,
+        untracked_overflow: 0,
         files: vec![FileChange {
             path: path.clone(),
             status: "Modified".to_string(),
@@ -472,7 +483,141 @@ async fn stage_current_selection_handles_line_hunk_and_file_modes() {
 }
 
 #[tokio::test]
-#[ignore = "Known flaky hunk restage path; run explicitly during debugging"]
+async fn range_selection_stages_and_unstages_whole_range() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\nfour\nfive\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nfour-B\nfive\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+
+    let lines = app.snapshots[0].files[0].hunks[0].lines.clone();
+    let first = lines
+        .iter()
+        .position(|line| line.trim_end() == "-two")
+        .expect("expected -two line");
+    let last = lines
+        .iter()
+        .position(|line| line.trim_end() == "+two-A")
+        .expect("expected +two-A line");
+
+    // Anchoring enters line mode on the first change line; extend down to the addition
+    app.toggle_range_selection();
+    assert!(app.line_selection_mode);
+    assert_eq!(app.selection_anchor, Some(first));
+    app.next_change_line();
+    assert_eq!(app.selected_line_index, last);
+    assert_eq!(app.selected_line_range(), Some((first, last)));
+
+    app.stage_current_selection();
+    assert_eq!(app.selection_anchor, None);
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A\nthree\nfour\nfive\n");
+
+    // Selecting the same, now fully staged, range again unstages it
+    app.selection_anchor = Some(first);
+    app.selected_line_index = last;
+    app.stage_current_selection();
+    let cached = run_git(&repo.path, &["diff", "--cached", "--name-only"]);
+    assert!(cached.trim().is_empty());
+}
+
+#[tokio::test]
+async fn range_selection_on_stale_hunk_reports_error() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\nfour\nfive\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nfour-B\nfive\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+
+    // Rewrite the file after the snapshot was taken so the hunk no longer applies
+    repo.write_file("example.txt", "uno\ndos\ntres\n");
+
+    app.toggle_range_selection();
+    app.next_change_line();
+    app.stage_current_selection();
+
+    let status = app.status_message().expect("expected status message");
+    assert!(status.is_error);
+    assert!(status.text.starts_with("Failed to stage selected lines"));
+    let cached = run_git(&repo.path, &["diff", "--cached", "--name-only"]);
+    assert!(cached.trim().is_empty());
+}
+
+#[tokio::test]
+async fn single_line_staging_failure_reports_error() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+    app.line_selection_mode = true;
+    app.selected_line_index = app.snapshots[0].files[0].hunks[0]
+        .lines
+        .iter()
+        .position(|line| line.trim_end() == "+two-A")
+        .expect("expected +two-A line");
+
+    // A held index lock makes writing the index fail
+    fs::write(repo.path.join(".git/index.lock"), "").expect("failed to lock index");
+    app.stage_current_selection();
+
+    let status = app.status_message().expect("expected status message");
+    assert!(status.is_error);
+    assert!(status.text.starts_with("Failed to stage line"));
+}
+
+#[tokio::test]
+async fn discard_current_selection_reverts_selected_lines_only() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\nfour\nfive\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nfour-B\nfive\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+    app.line_selection_mode = true;
+
+    let lines = app.snapshots[0].files[0].hunks[0].lines.clone();
+    app.selection_anchor = lines.iter().position(|line| line.trim_end() == "-four");
+    app.selected_line_index = lines
+        .iter()
+        .position(|line| line.trim_end() == "+four-B")
+        .expect("expected +four-B line");
+
+    // The first press only asks for confirmation
+    app.discard_current_selection(None);
+    let content = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(content, "one\ntwo-A\nthree\nfour-B\nfive\n");
+    let pending = app.pending_confirmation.take();
+    assert!(pending.is_some());
+    app.discard_current_selection(pending);
+    let content = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(content, "one\ntwo-A\nthree\nfour\nfive\n");
+
+    // Outside line mode the whole hunk is discarded
+    app.line_selection_mode = false;
+    app.discard_current_selection(None);
+    let pending = app.pending_confirmation.take();
+    app.discard_current_selection(pending);
+    let content = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(content, "one\ntwo\nthree\nfour\nfive\n");
+}
+
+#[tokio::test]
 async fn hunk_toggle_can_restage_after_unstage_on_simple_file() {
     let repo = TestRepo::new();
     repo.write_file("example.txt", "line 1\nline 2\nline 3\n");
@@ -698,6 +843,8 @@ async fn navigation_handles_empty_and_boundary_states() {
 
     app.snapshots = vec![DiffSnapshot {
         timestamp: SystemTime::now(),
+        head: None,
+        untracked_overflow: 0,
         files: vec![],
     }];
     app.current_snapshot_index = 0;
@@ -1077,3 +1224,1008 @@ async fn review_mode_advance_hunk_wraps() {
     // Should have wrapped back
     assert!(app.current_file_index < file_count);
 }
+
+#[tokio::test]
+async fn split_hunk_pieces_stage_independently_and_unsplit() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\nfour\nfive\nsix\nseven\n");
+    repo.commit_all("initial");
+    repo.write_file(
+        "example.txt",
+        "one\ntwo-A\nthree\nfour\nfive\nsix-B\nseven\n",
+    );
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+    assert_eq!(app.snapshots[0].files[0].hunks.len(), 1);
+
+    app.split_current_hunk();
+    let hunks = &app.snapshots[app.current_snapshot_index].files[0].hunks;
+    assert_eq!(hunks.len(), 2);
+    assert!(hunks.iter().all(|hunk| hunk.split_from.is_some()));
+
+    // Staging the first piece leaves the second change in the worktree only
+    app.stage_current_selection();
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A\nthree\nfour\nfive\nsix\nseven\n");
+    let hunks = &app.snapshots[app.current_snapshot_index].files[0].hunks;
+    assert!(hunks[0].staged);
+    assert!(!hunks[1].staged);
+
+    app.current_hunk_index = 1;
+    app.unsplit_current_hunk();
+    let hunks = &app.snapshots[app.current_snapshot_index].files[0].hunks;
+    assert_eq!(hunks.len(), 1);
+    assert!(hunks[0].split_from.is_none());
+    assert_eq!(app.current_hunk_index, 0);
+}
+
+#[tokio::test]
+async fn edited_hunk_errors_are_reported_in_header() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    let file_path = app.snapshots[0].files[0].path.clone();
+    let hunk = app.snapshots[0].files[0].hunks[0].clone();
+    let patch = GitRepo::hunk_edit_patch(&hunk, &file_path, "Modified");
+
+    app.apply_edited_hunk(&hunk, &file_path, &patch.replace(" one\n", " uno\n"));
+    let status = app.status_message().expect("expected status message");
+    assert!(status.is_error);
+
+    let ui = UI::new(&app);
+    let backend = TestBackend::new(160, 30);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            ui.draw(frame);
+        })
+        .expect("failed to draw ui");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("Edited hunk does not apply"));
+
+    app.apply_edited_hunk(&hunk, &file_path, &patch);
+    assert!(!app.status_message().expect("expected status").is_error);
+    assert!(app.snapshots[app.current_snapshot_index].files[0].hunks[0].staged);
+}
+
+#[tokio::test]
+async fn commit_composer_creates_commit_from_staged_changes() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo\n");
+    run_git(&repo.path, &["add", "example.txt"]);
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.open_commit_composer();
+    let composer = app.commit_composer.as_mut().expect("composer should open");
+    assert_eq!(composer.staged_files.len(), 1);
+    for ch in "Add two".chars() {
+        composer.insert_char(ch);
+    }
+
+    let ui = UI::new(&app);
+    let backend = TestBackend::new(120, 30);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            ui.draw(frame);
+        })
+        .expect("failed to draw ui");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("Add two"));
+    assert!(rendered.contains("Staged files (1)"));
+
+    app.submit_commit_composer();
+    assert!(app.commit_composer.is_none());
+    assert!(!app.status_message().expect("expected status").is_error);
+    let log = run_git(&repo.path, &["log", "--format=%s", "-1"]);
+    assert_eq!(log.trim(), "Add two");
+}
+
+#[tokio::test]
+async fn commit_composer_warns_before_committing_empty_index() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.open_commit_composer();
+    app.commit_composer
+        .as_mut()
+        .expect("composer should open")
+        .summary = "Empty".to_string();
+
+    app.submit_commit_composer();
+    let composer = app.commit_composer.as_ref().expect("composer stays open");
+    assert!(composer.awaiting_empty_confirmation());
+    let count = run_git(&repo.path, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "1");
+
+    app.submit_commit_composer();
+    assert!(app.commit_composer.is_none());
+    let count = run_git(&repo.path, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "2");
+}
+
+#[tokio::test]
+async fn review_fixup_and_confirmed_autosquash() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.enter_review_mode();
+    app.select_review_commit();
+
+    // Nothing staged yet
+    app.create_fixup_for_reviewed_commit();
+    assert!(app.status_message().expect("expected status").is_error);
+
+    repo.write_file("a.txt", "a fixed\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    app.create_fixup_for_reviewed_commit();
+    assert!(!app.status_message().expect("expected status").is_error);
+    assert_eq!(app.review_commits.len(), 2);
+    assert_eq!(
+        app.review_commits[app.review_commit_cursor].summary,
+        "Add a"
+    );
+
+    // The first press only asks for confirmation
+    app.autosquash_reviewed_commit(None);
+    assert!(app.pending_confirmation.is_some());
+    let count = run_git(&repo.path, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "2");
+
+    let pending = app.pending_confirmation.take();
+    app.autosquash_reviewed_commit(pending);
+    assert_eq!(app.mode, Mode::View);
+    let log = run_git(&repo.path, &["log", "--format=%s"]);
+    assert_eq!(log, "Add a\n");
+    assert_eq!(run_git(&repo.path, &["show", "HEAD:a.txt"]), "a fixed\n");
+}
+
+#[tokio::test]
+async fn review_amend_follows_rewritten_head() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.enter_review_mode();
+    app.select_review_commit();
+    let reviewed = app.review_commits[0].sha.clone();
+
+    repo.write_file("a.txt", "a amended\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    app.amend_head_from_review();
+
+    let head = run_git(&repo.path, &["rev-parse", "HEAD"])
+        .trim()
+        .to_string();
+    assert_ne!(head, reviewed);
+    assert_eq!(app.review_commits[app.review_commit_cursor].sha, head);
+    assert!(app.review_snapshot.is_some());
+}
+
+#[tokio::test]
+async fn stash_panel_previews_and_drops_with_confirmation() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.toggle_stash_mark();
+    let hunk = app.snapshots[0].files[0].hunks[0].clone();
+    assert!(app.is_marked_for_stash(&hunk));
+
+    app.stash_marked_hunks();
+    assert!(app.stash_marks.is_empty());
+    assert!(app.snapshots[app.current_snapshot_index].files.is_empty());
+
+    app.open_stash_panel();
+    assert!(app.stash_panel_open());
+    assert_eq!(app.stashes().len(), 1);
+
+    app.preview_selected_stash();
+    assert!(!app.stash_panel_open());
+    assert!(app.read_only_view());
+    assert_eq!(app.current_file().expect("preview file").hunks.len(), 1);
+    assert_eq!(app.stash_preview_label().as_deref(), Some("stash@{0}"));
+
+    let ui = UI::new(&app);
+    let backend = TestBackend::new(120, 30);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            ui.draw(frame);
+        })
+        .expect("failed to draw ui");
+    assert!(render_buffer_to_string(&terminal).contains("stash@{0} (read-only)"));
+
+    app.close_stash_preview();
+    assert!(app.stash_panel_open());
+
+    app.drop_selected_stash(None);
+    assert_eq!(app.stashes().len(), 1);
+    let pending = app.pending_confirmation.take();
+    app.drop_selected_stash(pending);
+    assert!(app.stashes().is_empty());
+    assert!(!app.stash_panel_open());
+}
+
+#[tokio::test]
+async fn ui_header_shows_branch_and_in_progress_merge() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "one\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "topic\n");
+    repo.commit_all("topic change");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    repo.write_file("a.txt", "main\n");
+    repo.commit_all("main change");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("main"), "got:\n{}", rendered);
+    assert!(!rendered.contains("MERGING"), "got:\n{}", rendered);
+
+    let _ = Command::new("git")
+        .args(["merge", "topic"])
+        .current_dir(&repo.path)
+        .output();
+    app.refresh_repo_status();
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("MERGING"), "got:\n{}", rendered);
+}
+
+#[tokio::test]
+async fn head_moves_outside_hunky_are_announced_and_reviewable() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    let before = app
+        .repo()
+        .expect("repository")
+        .head_state()
+        .expect("head state");
+
+    repo.write_file("a.txt", "a\nb\n");
+    run_git(&repo.path, &["add", "."]);
+    run_git(
+        &repo.path,
+        &[
+            "-c",
+            "user.name=agent",
+            "commit",
+            "-q",
+            "-m",
+            "Agent change",
+        ],
+    );
+    let after = app
+        .repo()
+        .expect("repository")
+        .head_state()
+        .expect("head state");
+    let head_move = app
+        .repo()
+        .expect("repository")
+        .describe_head_move(&before, &after)
+        .expect("describe")
+        .expect("HEAD moved");
+    let short_sha = head_move.commit.short_sha.clone();
+
+    app.announce_head_move(head_move.clone());
+    let status = app.status_message().expect("expected a notice");
+    assert!(!status.is_error);
+    assert!(
+        status
+            .text
+            .starts_with(&format!("new commit {} by agent", short_sha)),
+        "{}",
+        status.text
+    );
+
+    app.review_announced_commit();
+    assert_eq!(app.mode, Mode::Review);
+    assert!(!app.review_selecting_commit);
+    assert_eq!(
+        app.review_commits[app.review_commit_cursor].summary,
+        "Agent change"
+    );
+    assert!(app.announced_commit.is_none());
+
+    // Once the header knows about the new HEAD, the same move is not announced again
+    app.exit_review_mode();
+    app.status_message = None;
+    app.announce_head_move(head_move);
+    assert!(app.status_message().is_none());
+}
+
+#[tokio::test]
+async fn conflict_view_resolves_regions_and_marks_file_resolved() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "1\n2\n3\n4\n5\n6\n7\n8\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "1\ntopic 2\n3\n4\n5\n6\ntopic 7\n8\n");
+    repo.commit_all("topic change");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    repo.write_file("a.txt", "1\nmain 2\n3\n4\n5\n6\nmain 7\n8\n");
+    repo.commit_all("main change");
+    let _ = Command::new("git")
+        .args(["merge", "topic"])
+        .current_dir(&repo.path)
+        .output();
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    assert_eq!(app.conflicts().len(), 1);
+    assert!(app.current_snapshot().expect("snapshot").files.is_empty());
+
+    app.open_conflict_view();
+    assert!(app.conflict_view_open());
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    for expected in [
+        "1 conflicted",
+        "Conflicts (1)",
+        "Ours (HEAD)",
+        "Base",
+        "Theirs (topic)",
+        "main 2",
+        "topic 2",
+    ] {
+        assert!(
+            rendered.contains(expected),
+            "missing {:?} in:\n{}",
+            expected,
+            rendered
+        );
+    }
+
+    app.move_conflict_region(1);
+    app.resolve_current_conflict(ConflictChoice::Theirs);
+    assert_eq!(app.conflict_region_cursor(), 0);
+    app.mark_current_conflict_resolved();
+    assert!(app.status_message().expect("status").is_error);
+
+    app.resolve_current_conflict(ConflictChoice::Ours);
+    assert!(app
+        .status_message()
+        .expect("status")
+        .text
+        .contains("no conflicts left"));
+    app.mark_current_conflict_resolved();
+
+    assert!(app.conflicts().is_empty());
+    assert!(!app.conflict_view_open());
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).expect("read"),
+        "1\nmain 2\n3\n4\n5\n6\ntopic 7\n8\n"
+    );
+    assert_eq!(app.current_snapshot().expect("snapshot").files.len(), 1);
+}
+
+#[tokio::test]
+async fn untracked_toggle_and_ignore_from_file_list() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    fs::create_dir_all(repo.path.join("dist")).expect("failed to create dir");
+    repo.write_file("dist/bundle.js", "bundle\n");
+    repo.write_file("dist/bundle.map", "map\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    assert_eq!(app.current_snapshot().expect("snapshot").files.len(), 2);
+
+    app.cycle_untracked_mode();
+    assert_eq!(app.untracked_mode(), UntrackedMode::Collapse);
+    let snapshot = app.current_snapshot().expect("snapshot");
+    assert_eq!(snapshot.files.len(), 1);
+    assert_eq!(snapshot.files[0].path, PathBuf::from("dist/"));
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 20)).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("dist/ (untracked)"), "{}", rendered);
+    assert!(rendered.contains("untracked: collapse"), "{}", rendered);
+
+    app.ignore_current_file(IgnoreTarget::InfoExclude);
+    assert!(!app.status_message().expect("status").is_error);
+    assert!(app.current_snapshot().expect("snapshot").files.is_empty());
+
+    repo.write_file("a.txt", "a\nb\n");
+    app.refresh_current_snapshot_from_git();
+    app.ignore_current_file(IgnoreTarget::GitIgnore);
+    assert!(app.status_message().expect("status").is_error);
+    assert!(!repo.path.join(".gitignore").exists());
+}
+
+#[tokio::test]
+async fn intent_to_add_marks_the_selected_untracked_file() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    repo.write_file("new.txt", "one\ntwo\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    assert_eq!(app.current_file().expect("file").status, "Untracked");
+
+    app.intent_to_add_current_file();
+    assert!(!app.status_message().expect("status").is_error);
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains(" A new.txt"), "{}", status);
+    assert_ne!(app.current_file().expect("file").status, "Untracked");
+
+    app.intent_to_add_current_file();
+    assert!(app.status_message().expect("status").is_error);
+}
+
+#[tokio::test]
+async fn switching_repositories_keeps_each_position_and_state() {
+    let first = TestRepo::new();
+    first.write_file("a.txt", "a\n");
+    first.write_file("b.txt", "b\n");
+    first.commit_all("initial");
+    first.write_file("a.txt", "a\nchanged\n");
+    first.write_file("b.txt", "b\nchanged\n");
+    let second = TestRepo::new();
+    second.write_file("other.txt", "other\n");
+    second.commit_all("initial");
+
+    let paths = vec![
+        first.path.to_string_lossy().into_owned(),
+        second.path.to_string_lossy().into_owned(),
+        // The same repository twice is watched once
+        first.path.join(".").to_string_lossy().into_owned(),
+    ];
+    let mut app = App::with_repos(&paths, WatchConfig::default())
+        .await
+        .expect("failed to create app");
+    assert_eq!(app.repo_sessions.len(), 2);
+    let first_name = first
+        .path
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    assert_eq!(app.repo_label(), Some(format!("{} [1/2]", first_name)));
+    app.next_file();
+    assert_eq!(app.current_file_index, 1);
+
+    second.write_file("other.txt", "other\nchanged\n");
+    app.switch_repo(1);
+    let second_name = second
+        .path
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    assert_eq!(app.repo_label(), Some(format!("{} [2/2]", second_name)));
+    assert_eq!(app.current_file_index, 0);
+    let snapshot = app.current_snapshot().expect("snapshot");
+    assert_eq!(snapshot.files.len(), 1);
+    assert_eq!(snapshot.files[0].path, PathBuf::from("other.txt"));
+
+    // Wrapping around brings back the first repository where it was left
+    app.switch_repo(1);
+    assert_eq!(app.repo_label(), Some(format!("{} [1/2]", first_name)));
+    assert_eq!(app.current_file_index, 1);
+    assert_eq!(app.current_snapshot().expect("snapshot").files.len(), 2);
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 20)).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(
+        rendered.contains(&format!("{} [1/2]", first_name)),
+        "{}",
+        rendered
+    );
+}
+
+#[tokio::test]
+async fn review_mode_explains_an_unborn_branch_instead_of_doing_nothing() {
+    let repo = TestRepo::new();
+    repo.write_file("first.txt", "one\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app on an empty repo");
+    assert_eq!(app.current_snapshot().expect("snapshot").files.len(), 1);
+
+    app.enter_review_mode();
+    assert_eq!(app.mode, Mode::View);
+    let status = app.status_message().expect("status");
+    assert!(!status.is_error);
+    assert!(
+        status.text.starts_with("No commits to review"),
+        "{}",
+        status.text
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropping_the_app_stops_every_watcher_task() {
+    let first = TestRepo::new();
+    first.write_file("a.txt", "a\n");
+    first.commit_all("initial");
+    let second = TestRepo::new();
+    second.write_file("b.txt", "b\n");
+    second.commit_all("initial");
+
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let baseline = metrics.num_alive_tasks();
+    let paths = vec![
+        first.path.to_string_lossy().into_owned(),
+        second.path.to_string_lossy().into_owned(),
+    ];
+    let app = App::with_repos(&paths, WatchConfig::default())
+        .await
+        .expect("failed to create app");
+    assert_eq!(metrics.num_alive_tasks(), baseline + 2);
+
+    drop(app);
+    let stopped = tokio::time::timeout(Duration::from_secs(3), async {
+        while metrics.num_alive_tasks() > baseline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert!(stopped.is_ok(), "watcher tasks outlived the app");
+}
+
+#[tokio::test]
+async fn view_mode_replaces_stale_hunks_with_the_clean_state() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    assert_eq!(app.snapshots[0].files.len(), 1);
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.watch_receiver = rx;
+    repo.commit_all("second");
+    tx.send(WatchEvent::TreeClean(CleanReason::Committed))
+        .expect("send");
+    tx.send(WatchEvent::Snapshot(
+        app.repo()
+            .expect("repository")
+            .get_diff_snapshot()
+            .expect("snapshot"),
+    ))
+    .expect("send");
+
+    app.take_watch_events();
+    assert!(app.snapshots[0].files.is_empty());
+    assert_eq!(app.current_file_index, 0);
+    assert_eq!(app.clean_reason(), Some(CleanReason::Committed));
+    let backend = TestBackend::new(120, 20);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("Working tree clean"));
+    assert!(rendered.contains("All changes were committed"));
+    assert!(!rendered.contains("line 2"));
+
+    // Changes coming back clear the reason
+    repo.write_file("tracked.txt", "line 1\nline 3\n");
+    tx.send(WatchEvent::Snapshot(
+        app.repo()
+            .expect("repository")
+            .get_diff_snapshot()
+            .expect("snapshot"),
+    ))
+    .expect("send");
+    app.take_watch_events();
+    assert_eq!(app.clean_reason(), None);
+    assert_eq!(app.snapshots[0].files.len(), 1);
+}
+
+#[tokio::test]
+async fn activity_panel_lists_touched_files_and_jumps_to_their_diff() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.write_file("b.txt", "b\n");
+    repo.commit_all("initial");
+    repo.write_file("a.txt", "a\nmore\n");
+    repo.write_file("b.txt", "b\nmore\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.open_activity_panel();
+    assert!(!app.activity_panel_open());
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.watch_receiver = rx;
+    let now = SystemTime::now();
+    let touch = |path: &str, kind| FileTouch {
+        path: PathBuf::from(path),
+        kind,
+        at: now,
+    };
+    tx.send(WatchEvent::Activity(vec![
+        touch("b.txt", TouchKind::Modified),
+        touch("b.txt", TouchKind::Modified),
+        touch("gone.txt", TouchKind::Removed),
+    ]))
+    .expect("send");
+    app.take_watch_events();
+    assert_eq!(app.activity().len(), 2);
+
+    app.open_activity_panel();
+    assert!(app.activity_panel_open());
+    let backend = TestBackend::new(120, 20);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("3 events in 2 files"));
+    assert!(rendered.contains("b.txt (+0 ~2 -0, just now)"));
+
+    // The hottest file comes first and its changes are shown on Enter
+    app.jump_to_activity_file();
+    assert!(!app.activity_panel_open());
+    assert_eq!(
+        app.snapshots[0].files[app.current_file_index].path,
+        PathBuf::from("b.txt")
+    );
+
+    app.open_activity_panel();
+    app.move_activity_cursor(5);
+    assert_eq!(app.activity_cursor(), 1);
+    app.jump_to_activity_file();
+    assert_eq!(
+        app.status_message
+            .as_ref()
+            .map(|status| status.text.as_str()),
+        Some("gone.txt has no changes")
+    );
+}
+
+#[tokio::test]
+async fn timeline_steps_back_through_earlier_working_tree_states() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.watch_receiver = rx;
+    let send_snapshot = |app: &App| {
+        tx.send(WatchEvent::Snapshot(
+            app.repo()
+                .expect("repository")
+                .get_diff_snapshot()
+                .expect("snapshot"),
+        ))
+        .expect("send");
+    };
+
+    repo.write_file("tracked.txt", "line 1\nline 2\nline 3\n");
+    send_snapshot(&app);
+    // Unchanged snapshots are kept once
+    send_snapshot(&app);
+    app.take_watch_events();
+    assert_eq!(app.history().len(), 2);
+
+    app.open_timeline();
+    assert_eq!(app.timeline_cursor(), Some(1));
+    assert!(app.read_only_view());
+    app.step_timeline(-1);
+    let lines = &app.current_file().expect("file").hunks[0].lines;
+    assert!(lines.iter().any(|line| line == "+line 2\n"));
+    assert!(!lines.iter().any(|line| line == "+line 3\n"));
+
+    let backend = TestBackend::new(120, 20);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let rendered = render_buffer_to_string(&terminal);
+    assert!(rendered.contains("Timeline 1/2"));
+    assert!(rendered.contains("snapshot 1/2 (read-only)"));
+
+    // New states join the history without moving the timeline
+    repo.write_file("tracked.txt", "line 1\nline 4\n");
+    send_snapshot(&app);
+    app.take_watch_events();
+    assert_eq!(app.history().len(), 3);
+    assert_eq!(app.timeline_cursor(), Some(0));
+
+    app.step_timeline(isize::MAX);
+    assert_eq!(app.timeline_cursor(), Some(2));
+    app.close_timeline();
+    assert_eq!(app.timeline_cursor(), None);
+    assert!(app.current_file().expect("file").hunks[0]
+        .lines
+        .iter()
+        .any(|line| line == "+line 4\n"));
+}
+
+#[test]
+fn streamed_snapshots_are_bounded_and_keep_their_positions() {
+    let mut snapshots = vec![sample_snapshot(); crate::history::HISTORY_LIMIT + 2];
+
+    let mut current = 5;
+    let mut start = Some(3);
+    assert!(!trim_streamed_snapshots(
+        &mut snapshots,
+        &mut current,
+        &mut start
+    ));
+    assert_eq!(snapshots.len(), crate::history::HISTORY_LIMIT);
+    assert_eq!((current, start), (3, Some(1)));
+
+    snapshots.extend([sample_snapshot(), sample_snapshot()]);
+    let mut current = 1;
+    let mut start = Some(0);
+    assert!(trim_streamed_snapshots(
+        &mut snapshots,
+        &mut current,
+        &mut start
+    ));
+    assert_eq!((current, start), (0, None));
+}
+
+#[tokio::test]
+async fn delta_view_shows_what_changed_since_the_last_look_and_between_marked_points() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.watch_receiver = rx;
+    let step = |app: &mut App, content: &str| {
+        repo.write_file("tracked.txt", content);
+        tx.send(WatchEvent::Snapshot(
+            app.repo()
+                .expect("repository")
+                .get_diff_snapshot()
+                .expect("snapshot"),
+        ))
+        .expect("send");
+        app.take_watch_events();
+    };
+
+    app.open_delta_view();
+    assert_eq!(app.delta_label(), None);
+    assert_eq!(
+        app.status_message
+            .as_ref()
+            .map(|status| status.text.as_str()),
+        Some("No changes since you last looked")
+    );
+
+    // Rewriting an added line shows up as that edit alone, not as the whole change
+    step(&mut app, "line 1\nline two\n");
+    app.open_delta_view();
+    assert_eq!(app.delta_label(), Some("changes since you last looked"));
+    assert!(app.read_only_view());
+    assert_eq!(
+        app.current_file().expect("file").hunks[0].lines,
+        vec![" line 1\n", "-line 2\n", "+line two\n"]
+    );
+    app.close_delta_view();
+    app.open_delta_view();
+    assert_eq!(app.delta_label(), None);
+
+    step(&mut app, "line 1\nline two\nline 3\n");
+    app.open_timeline();
+    app.step_timeline(isize::MIN);
+    app.toggle_timeline_mark();
+    assert_eq!(app.timeline_mark(), Some(0));
+    app.step_timeline(isize::MAX);
+    app.open_delta_view();
+    assert_eq!(app.delta_label(), Some("snapshot 1 → 3"));
+    assert_eq!(
+        app.current_file().expect("file").hunks[0].lines,
+        vec![" line 1\n", "-line 2\n", "+line two\n", "+line 3\n"]
+    );
+
+    let backend = TestBackend::new(120, 20);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    assert!(render_buffer_to_string(&terminal).contains("snapshot 1 → 3 (read-only)"));
+
+    // Stepping waits until the comparison is closed; closing the timeline closes both
+    app.step_timeline(-1);
+    assert_eq!(app.timeline_cursor(), Some(2));
+    app.close_timeline();
+    assert_eq!(app.delta_label(), None);
+    assert_eq!(app.timeline_mark(), None);
+}
+
+#[tokio::test]
+async fn saved_snapshots_reopen_read_only_in_the_timeline() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    let (tx, rx) = mpsc::unbounded_channel();
+    app.watch_receiver = rx;
+    let step = |app: &mut App, content: &str| {
+        repo.write_file("tracked.txt", content);
+        tx.send(WatchEvent::Snapshot(
+            app.repo()
+                .expect("repository")
+                .get_diff_snapshot()
+                .expect("snapshot"),
+        ))
+        .expect("send");
+        app.take_watch_events();
+    };
+    step(&mut app, "line 1\nline two\n");
+
+    app.export_snapshots();
+    let saved: Vec<PathBuf> = fs::read_dir(repo.path.join(".git/hunky"))
+        .expect("export directory")
+        .map(|entry| entry.expect("entry").path())
+        .collect();
+    let json = saved
+        .iter()
+        .find(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .expect("history saved as JSON");
+    let patch = saved
+        .iter()
+        .find(|path| path.extension().is_some_and(|ext| ext == "patch"))
+        .expect("diff saved as a patch");
+    assert!(app
+        .status_message
+        .as_ref()
+        .is_some_and(|status| status.text.contains(&*json.to_string_lossy())));
+    assert!(fs::read_to_string(patch)
+        .expect("patch")
+        .contains("+line two\n"));
+
+    app.open_archive(json, crate::archive::load(json).expect("load"));
+    assert_eq!(app.history().len(), 2);
+    assert_eq!(app.timeline_cursor(), Some(1));
+    assert!(app.read_only_view());
+
+    // The live working tree stays out of the opened history, and there is no leaving it
+    step(&mut app, "line 1\nline 3\n");
+    assert_eq!(app.history().len(), 2);
+    app.close_timeline();
+    assert_eq!(app.timeline_cursor(), Some(1));
+
+    // Saved HEADs let opened snapshots be compared like live ones
+    app.open_delta_view();
+    assert_eq!(
+        app.current_file().expect("file").hunks[0].lines,
+        vec![" line 1\n", "-line 2\n", "+line two\n"]
+    );
+    app.close_delta_view();
+
+    let backend = TestBackend::new(120, 20);
+    let mut terminal = Terminal::new(backend).expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+    let label = format!(
+        "{} 2/2 (read-only)",
+        json.file_name().expect("name").to_string_lossy()
+    );
+    assert!(render_buffer_to_string(&terminal).contains(&label));
+}
+
+#[tokio::test]
+async fn discard_needs_a_second_press_on_the_same_selection() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\n");
+
+    let mut app = App::new(repo.path.to_str().expect("path should be utf-8"))
+        .await
+        .expect("failed to create app");
+    app.focus = FocusPane::HunkView;
+
+    app.discard_current_selection(None);
+    let content = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(content, "one\ntwo-A\nthree\n");
+    assert!(app
+        .status_message
+        .as_ref()
+        .is_some_and(|status| status.text.starts_with("Press d again")));
+
+    // A confirmation for a different selection doesn't count
+    app.pending_confirmation.take();
+    app.discard_current_selection(Some(PendingConfirmation::Discard {
+        hunk: app.snapshots[0].files[0].hunks[0].id.clone(),
+        lines: vec![0],
+    }));
+    let content = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(content, "one\ntwo-A\nthree\n");
+}
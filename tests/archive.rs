@@ -0,0 +1,196 @@
+use super::*;
+use crate::git::GitRepo;
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TEST_DIR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+struct TestRepo {
+    path: PathBuf,
+}
+
+impl TestRepo {
+    fn new() -> Self {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("failed to get system time")
+            .as_nanos();
+        let counter = TEST_DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "hunky-archive-tests-{}-{}-{}",
+            std::process::id(),
+            unique,
+            counter
+        ));
+        fs::create_dir_all(&path).expect("failed to create temp directory");
+        run_git(&path, &["init"]);
+        run_git(&path, &["config", "user.name", "Test User"]);
+        run_git(&path, &["config", "user.email", "test@example.com"]);
+        Self { path }
+    }
+
+    fn write_file(&self, rel_path: &str, content: &str) {
+        fs::write(self.path.join(rel_path), content).expect("failed to write file");
+    }
+
+    fn commit_all(&self, message: &str) {
+        run_git(&self.path, &["add", "."]);
+        run_git(&self.path, &["commit", "-m", message]);
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn run_git(repo_path: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .expect("failed to execute git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Edits covering a modified file, a new file, a deleted file and a missing final newline
+fn changed_repo() -> TestRepo {
+    let repo = TestRepo::new();
+    repo.write_file("kept.txt", "one\ntwo\nthree\n");
+    repo.write_file("gone.txt", "bye\n");
+    repo.write_file("tail.txt", "first\nlast\n");
+    repo.commit_all("initial");
+
+    repo.write_file("kept.txt", "one\n2\nthree\nfour\n");
+    fs::remove_file(repo.path.join("gone.txt")).expect("remove");
+    repo.write_file("tail.txt", "first\nlast");
+    repo.write_file("new.txt", "fresh\n");
+    run_git(&repo.path, &["add", "-N", "new.txt"]);
+    repo
+}
+
+#[test]
+fn json_keeps_what_snapshots_recorded() {
+    let repo = changed_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("repo");
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let mut later = snapshot.clone();
+    later.files.truncate(1);
+    later.untracked_overflow = 3;
+
+    let loaded = from_json(&to_json(&[&snapshot, &later]).expect("json")).expect("parse");
+    assert_eq!(loaded.len(), 2);
+    assert!(loaded[0].same_changes(&snapshot));
+    assert!(loaded[1].same_changes(&later));
+    assert_eq!(loaded[0].head, snapshot.head);
+    assert!(loaded[0].head.is_some());
+    let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_millis();
+    assert_eq!(millis(loaded[0].timestamp), millis(snapshot.timestamp));
+}
+
+#[test]
+fn json_from_a_newer_version_is_refused() {
+    let error = from_json(r#"{"version": 99, "snapshots": []}"#).expect_err("newer version");
+    assert!(error.to_string().contains("newer format"));
+    assert!(from_json("{}").is_err());
+}
+
+#[test]
+fn patches_match_git_diff_and_read_back() {
+    let repo = changed_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("repo");
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+
+    let patch = to_patch(&snapshot);
+    assert!(patch.contains("--- a/gone.txt\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-bye\n"));
+    assert!(patch.contains("-last\n+last\n\\ No newline at end of file\n"));
+    assert!(from_patch(&patch).expect("parse").same_changes(&snapshot));
+
+    // The patch undoes the working tree changes when applied in reverse
+    fs::write(repo.path.join("saved.patch"), &patch).expect("write patch");
+    run_git(&repo.path, &["apply", "--check", "-R", "saved.patch"]);
+
+    // git's own output, with index and mode lines, reads as the same changes
+    let from_git = from_patch(&run_git(&repo.path, &["diff", "HEAD"])).expect("parse");
+    let mut paths: Vec<_> = from_git.files.iter().map(|file| &file.path).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        ["gone.txt", "kept.txt", "new.txt", "tail.txt"]
+            .map(PathBuf::from)
+            .iter()
+            .collect::<Vec<_>>()
+    );
+    for file in &from_git.files {
+        let recorded = snapshot
+            .files
+            .iter()
+            .find(|recorded| recorded.path == file.path)
+            .expect("file in snapshot");
+        assert_eq!(file.status, recorded.status);
+        assert_eq!(
+            file.hunks
+                .iter()
+                .map(|hunk| &hunk.lines)
+                .collect::<Vec<_>>(),
+            recorded
+                .hunks
+                .iter()
+                .map(|hunk| &hunk.lines)
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn truncated_patches_are_refused() {
+    let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-old\n+new\n";
+    assert!(from_patch(patch)
+        .expect_err("truncated")
+        .to_string()
+        .contains("middle of a hunk"));
+    assert!(from_patch("@@ -1 +1 @@\n-old\n+new\n").is_err());
+
+    // Stripped blank context lines still count
+    let snapshot =
+        from_patch("--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n\n-old\n+new\n").expect("parse");
+    assert_eq!(
+        snapshot.files[0].hunks[0].lines,
+        vec![" \n", "-old\n", "+new\n"]
+    );
+}
+
+#[test]
+fn files_load_by_content_and_save_by_extension() {
+    let repo = changed_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("repo");
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+
+    let json_path = repo.path.join("saved.json");
+    let patch_path = repo.path.join("saved.diff");
+    save(&json_path, &[&snapshot, &snapshot]).expect("save json");
+    save(&patch_path, &[&snapshot]).expect("save patch");
+    assert!(fs::read_to_string(&patch_path)
+        .expect("read")
+        .starts_with("diff --git "));
+
+    assert_eq!(load(&json_path).expect("load json").len(), 2);
+    let loaded = load(&patch_path).expect("load patch");
+    assert_eq!(loaded.len(), 1);
+    assert!(loaded[0].same_changes(&snapshot));
+    assert!(loaded[0].head.is_none());
+
+    fs::write(&patch_path, "").expect("write");
+    assert!(load(&patch_path).is_ok_and(|snapshots| snapshots[0].files.is_empty()));
+    assert!(load(&repo.path.join("missing.json")).is_err());
+    fs::write(&patch_path, "not a patch\n").expect("write");
+    assert!(load(&patch_path).is_err());
+}
@@ -0,0 +1,101 @@
+use super::*;
+use std::path::PathBuf;
+
+fn staged(path: &str) -> StagedFile {
+    StagedFile {
+        path: PathBuf::from(path),
+        status: "Modified".to_string(),
+        insertions: 1,
+        deletions: 1,
+    }
+}
+
+#[test]
+fn typing_fills_summary_then_body() {
+    let mut composer = CommitComposer::new(vec![staged("a.txt")], None, None, None);
+
+    for ch in "Add thing".chars() {
+        composer.insert_char(ch);
+    }
+    composer.newline();
+    assert_eq!(composer.field, ComposerField::Body);
+    for ch in "Longer\nexplanation".chars() {
+        if ch == '\n' {
+            composer.newline();
+        } else {
+            composer.insert_char(ch);
+        }
+    }
+    composer.backspace();
+
+    assert_eq!(composer.message(), "Add thing\n\nLonger\nexplanatio\n");
+}
+
+#[test]
+fn signoff_appends_trailer_once() {
+    let trailer = "Signed-off-by: Test User <test@example.com>".to_string();
+    let mut composer = CommitComposer::new(vec![staged("a.txt")], None, None, Some(trailer));
+    composer.summary = "Fix bug".to_string();
+
+    composer.toggle_signoff();
+    assert_eq!(
+        composer.message(),
+        "Fix bug\n\nSigned-off-by: Test User <test@example.com>\n"
+    );
+
+    // Without an identity the toggle stays off
+    let mut anonymous = CommitComposer::new(vec![], None, None, None);
+    anonymous.toggle_signoff();
+    assert!(!anonymous.signoff);
+}
+
+#[test]
+fn amend_swaps_untouched_message_for_head_message() {
+    let head = "Previous summary\n\nPrevious body\n".to_string();
+    let mut composer = CommitComposer::new(vec![], None, Some(head), None);
+
+    composer.toggle_amend();
+    assert!(composer.amend);
+    assert_eq!(composer.summary, "Previous summary");
+    assert_eq!(composer.body, "Previous body");
+    assert!(!composer.index_is_empty());
+
+    composer.toggle_amend();
+    assert!(!composer.amend);
+    assert!(composer.summary.is_empty());
+
+    // An edited message survives toggling amend
+    composer.summary = "Mine".to_string();
+    composer.toggle_amend();
+    assert_eq!(composer.summary, "Mine");
+}
+
+#[test]
+fn templates_start_from_commit_template_and_cycle_while_untouched() {
+    let template = "\n# Explain why\n".to_string();
+    let mut composer = CommitComposer::new(vec![staged("a.txt")], Some(template), None, None);
+    assert_eq!(composer.template_name(), Some("commit.template"));
+    assert_eq!(composer.body, "# Explain why");
+
+    composer.cycle_template();
+    assert_eq!(composer.template_name(), Some("feat"));
+    assert_eq!(composer.summary, "feat: ");
+
+    composer.insert_char('x');
+    composer.cycle_template();
+    assert_eq!(composer.template_name(), Some("feat"));
+    assert_eq!(composer.summary, "feat: x");
+}
+
+#[test]
+fn empty_index_needs_confirmation() {
+    let mut composer = CommitComposer::new(vec![], None, None, None);
+    assert!(composer.index_is_empty());
+
+    assert!(!composer.confirm_commit());
+    assert!(composer.awaiting_empty_confirmation());
+    assert!(composer.confirm_commit());
+
+    let mut with_files = CommitComposer::new(vec![staged("a.txt")], None, None, None);
+    assert!(with_files.confirm_commit());
+}
@@ -77,3 +77,160 @@ fn seen_tracker_default_is_empty() {
     tracker.mark_seen(&hunk_id);
     assert!(tracker.is_seen(&hunk_id));
 }
+
+fn lines(raw: &[&str]) -> Vec<String> {
+    raw.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn context_split_points_divide_shared_context() {
+    let file_path = PathBuf::from("src/main.rs");
+    let hunk = Hunk::new(
+        1,
+        1,
+        lines(&[" a", "-b", "+B", " c", " d", " e", " f", "-g", " h"]),
+        &file_path,
+    );
+
+    // The four context lines between the groups are shared two and two.
+    assert_eq!(hunk.context_split_points(), vec![5]);
+
+    let single_group = Hunk::new(1, 1, lines(&[" a", "-b", "+B", " c"]), &file_path);
+    assert!(single_group.context_split_points().is_empty());
+}
+
+#[test]
+fn split_at_tracks_line_numbers_and_origin() {
+    let file_path = PathBuf::from("src/main.rs");
+    let hunk = Hunk::new(
+        10,
+        20,
+        lines(&[" a", "-b", "+B", "+C", " c", " d", "-e", " f"]),
+        &file_path,
+    );
+
+    let pieces = hunk.split_at(&[5], &file_path);
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(pieces[0].lines, lines(&[" a", "-b", "+B", "+C", " c"]));
+    assert_eq!((pieces[0].old_start, pieces[0].new_start), (10, 20));
+    assert_eq!(pieces[1].lines, lines(&[" d", "-e", " f"]));
+    assert_eq!((pieces[1].old_start, pieces[1].new_start), (13, 24));
+    assert_eq!(pieces[1].split_from, Some((hunk.id.clone(), 5)));
+}
+
+#[test]
+fn split_at_refuses_points_between_additions() {
+    let file_path = PathBuf::from("src/main.rs");
+    let hunk = Hunk::new(1, 1, lines(&[" a", "-b", "+B", "+C", " c"]), &file_path);
+
+    assert!(hunk.can_split_at(2));
+    assert!(!hunk.can_split_at(3));
+    assert!(!hunk.can_split_at(0));
+
+    let pieces = hunk.split_at(&[3], &file_path);
+    assert_eq!(pieces.len(), 1);
+    assert!(pieces[0].split_from.is_none());
+}
+
+#[test]
+fn split_at_uses_line_before_for_empty_sides() {
+    let file_path = PathBuf::from("src/main.rs");
+    let hunk = Hunk::new(5, 5, lines(&[" a", "-b", "+B", " c"]), &file_path);
+
+    let pieces = hunk.split_at(&[2], &file_path);
+    assert_eq!(pieces.len(), 2);
+    // The first piece only removes a line, the second only adds one after line 6.
+    assert_eq!((pieces[0].old_start, pieces[0].new_start), (5, 5));
+    assert_eq!(pieces[1].lines, lines(&["+B", " c"]));
+    assert_eq!((pieces[1].old_start, pieces[1].new_start), (7, 6));
+}
+
+#[test]
+fn parse_conflict_regions_reads_merge_and_diff3_markers() {
+    let content = "top\n\
+        <<<<<<< HEAD\n\
+        ours\n\
+        =======\n\
+        theirs 1\n\
+        theirs 2\n\
+        >>>>>>> topic\n\
+        middle\n\
+        <<<<<<< ours\n\
+        ||||||| base\n\
+        base\n\
+        =======\n\
+        >>>>>>> theirs\n\
+        <<<<<<< unterminated\n\
+        dangling\n";
+
+    let regions = parse_conflict_regions(content);
+    assert_eq!(regions.len(), 2);
+
+    assert_eq!((regions[0].start, regions[0].end), (1, 7));
+    assert_eq!(regions[0].ours_label, "HEAD");
+    assert_eq!(regions[0].theirs_label, "topic");
+    assert_eq!(regions[0].ours, lines(&["ours"]));
+    assert_eq!(regions[0].base, None);
+    assert_eq!(regions[0].theirs, lines(&["theirs 1", "theirs 2"]));
+
+    assert_eq!((regions[1].start, regions[1].end), (8, 13));
+    assert!(regions[1].ours.is_empty());
+    assert_eq!(regions[1].base, Some(lines(&["base"])));
+    assert!(regions[1].theirs.is_empty());
+}
+
+#[test]
+fn conflict_region_resolution_picks_sides() {
+    let region = &parse_conflict_regions("<<<<<<< a\nx\n=======\ny\n>>>>>>> b\n")[0];
+
+    assert_eq!(region.resolution(ConflictChoice::Ours), lines(&["x"]));
+    assert_eq!(region.resolution(ConflictChoice::Theirs), lines(&["y"]));
+    assert_eq!(region.resolution(ConflictChoice::Both), lines(&["x", "y"]));
+}
+
+#[test]
+fn patch_file_header_uses_dev_null_for_new_and_deleted_files() {
+    let path = Path::new("src/lib.rs");
+    assert_eq!(
+        patch_file_header(path, "Modified"),
+        "--- a/src/lib.rs\n+++ b/src/lib.rs\n"
+    );
+    assert_eq!(
+        patch_file_header(path, "Untracked"),
+        "--- /dev/null\n+++ b/src/lib.rs\n"
+    );
+    assert_eq!(
+        patch_file_header(path, "Added"),
+        "--- /dev/null\n+++ b/src/lib.rs\n"
+    );
+    assert_eq!(
+        patch_file_header(path, "Deleted"),
+        "--- a/src/lib.rs\n+++ /dev/null\n"
+    );
+}
+
+#[test]
+fn content_hunks_number_lines_like_git() {
+    let path = PathBuf::from("a.txt");
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let new = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11";
+
+    let hunks = content_hunks(old, new, &path);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!((hunks[0].old_start, hunks[0].new_start), (8, 8));
+    assert_eq!(
+        hunks[0].lines,
+        vec![
+            " 8\n",
+            " 9\n",
+            " 10\n",
+            "+11",
+            ">\n\\ No newline at end of file\n"
+        ]
+    );
+
+    // A new file's hunk starts before its first line on the old side
+    let hunks = content_hunks("", "a\n", &path);
+    assert_eq!((hunks[0].old_start, hunks[0].new_start), (0, 1));
+    assert!(content_hunks(old, old, &path).is_empty());
+}
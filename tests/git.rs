@@ -135,7 +135,7 @@ fn stage_and_unstage_hunk_updates_index() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
     let file_path = Path::new("example.txt");
@@ -172,7 +172,7 @@ fn stage_and_unstage_single_line_tracks_index_changes() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
     let line_index = hunk
@@ -194,7 +194,7 @@ fn stage_and_unstage_single_line_tracks_index_changes() {
     let refreshed_file_change = refreshed_snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in refreshed diff");
     let refreshed_hunk = refreshed_file_change
         .hunks
@@ -237,7 +237,7 @@ fn detect_staged_lines_uses_line_coordinates_not_just_content() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -270,7 +270,7 @@ fn detect_staged_lines_handles_staged_plus_with_unstaged_plus_offset() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
 
     // Find the hunk containing the staged +STAGED line and ensure it is detected staged.
@@ -317,7 +317,7 @@ fn stage_single_line_handles_existing_staged_changes_in_same_file() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -338,7 +338,7 @@ fn stage_single_line_handles_existing_staged_changes_in_same_file() {
     let refreshed_file_change = refreshed_snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in refreshed diff");
     let refreshed_hunk = refreshed_file_change
         .hunks
@@ -379,7 +379,7 @@ fn stage_single_line_targets_selected_duplicate_addition() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -424,7 +424,7 @@ fn toggle_hunk_stages_remaining_when_partially_staged() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -445,7 +445,7 @@ fn toggle_hunk_stages_remaining_when_partially_staged() {
     let refreshed_file_change = refreshed_snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in refreshed diff");
     let refreshed_hunk = refreshed_file_change
         .hunks
@@ -465,6 +465,101 @@ fn toggle_hunk_stages_remaining_when_partially_staged() {
     );
 }
 
+#[test]
+fn set_hunk_staged_lines_applies_selection_in_one_update() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\nfour\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nfour-B\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let file_change = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("example.txt"))
+        .expect("expected file in diff");
+    let hunk = file_change.hunks.first().expect("expected hunk");
+
+    // Stage the "two" replacement (both its - and + lines) but not the "four" one.
+    let selected: HashSet<usize> = hunk
+        .lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| matches!(line.trim_end(), "-two" | "+two-A").then_some(idx))
+        .collect();
+    assert_eq!(selected.len(), 2);
+
+    git_repo
+        .set_hunk_staged_lines(hunk, Path::new("example.txt"), &selected)
+        .expect("failed to stage selected lines");
+
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A\nthree\nfour\n");
+
+    let staged = git_repo
+        .detect_staged_lines(hunk, Path::new("example.txt"))
+        .expect("failed to detect staged lines");
+    assert_eq!(staged, selected);
+}
+
+#[test]
+fn stage_hunk_keeps_staged_insertion_after_a_trailing_deletion() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\n");
+    repo.commit_all("initial");
+
+    // Stage a line the working tree doesn't have, right after the line the hunk deletes
+    repo.write_file("example.txt", "one\ntwo\nstaged\n");
+    run_git(&repo.path, &["add", "example.txt"]);
+    repo.write_file("example.txt", "one\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    assert_eq!(hunk.lines.last().map(String::as_str), Some("-two\n"));
+
+    git_repo
+        .stage_hunk(hunk, Path::new("example.txt"))
+        .expect("failed to stage hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\nstaged\n");
+}
+
+#[test]
+fn discard_hunk_lines_reverts_worktree_and_index() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nextra\n");
+    run_git(&repo.path, &["add", "example.txt"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    let selected: HashSet<usize> = hunk
+        .lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| matches!(line.trim_end(), "-two" | "+two-A").then_some(idx))
+        .collect();
+
+    git_repo
+        .discard_hunk_lines(hunk, Path::new("example.txt"), &selected)
+        .expect("failed to discard lines");
+
+    let worktree = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(worktree, "one\ntwo\nthree\nextra\n");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo\nthree\nextra\n");
+}
+
 #[test]
 fn unstage_single_line_targets_selected_duplicate_addition() {
     let repo = TestRepo::new();
@@ -482,7 +577,7 @@ fn unstage_single_line_targets_selected_duplicate_addition() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("example.txt"))
+        .find(|file| file.path == Path::new("example.txt"))
         .expect("expected file in diff");
     let hunk = file_change.hunks.first().expect("expected hunk");
 
@@ -525,14 +620,150 @@ fn diff_snapshot_reports_file_status() {
     let file = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("status.txt"))
+        .find(|f| f.path == Path::new("status.txt"))
         .expect("expected changed file");
     assert_eq!(file.status, "Modified");
     assert!(!file.hunks.is_empty());
 }
 
+/// Paths, statuses and hunk lines, for comparing snapshots built different ways
+fn snapshot_summary(snapshot: &DiffSnapshot) -> Vec<(PathBuf, String, Vec<Vec<String>>)> {
+    snapshot
+        .files
+        .iter()
+        .map(|file| {
+            let hunks = file.hunks.iter().map(|hunk| hunk.lines.clone()).collect();
+            (file.path.clone(), file.status.clone(), hunks)
+        })
+        .collect()
+}
+
+#[test]
+fn update_snapshot_rediffs_only_the_changed_paths() {
+    let repo = TestRepo::new();
+    fs::create_dir_all(repo.path.join("dir")).expect("failed to create dir");
+    repo.write_file("kept.txt", "kept\n");
+    repo.write_file("edited.txt", "one\n");
+    repo.write_file("dir/gone.txt", "gone\n");
+    repo.commit_all("initial");
+    repo.write_file("kept.txt", "kept\nchanged\n");
+    repo.write_file("edited.txt", "one\ntwo\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let mut previous = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    // Marks on files outside the burst survive, which shows they were not re-diffed
+    previous.files[1].hunks[0].seen = true;
+    assert_eq!(previous.files[1].path, Path::new("kept.txt"));
+
+    repo.write_file("edited.txt", "one\ntwo\nthree\n");
+    repo.write_file("new.txt", "new\n");
+    fs::remove_dir_all(repo.path.join("dir")).expect("failed to remove dir");
+    let changed = vec![
+        PathBuf::from("edited.txt"),
+        PathBuf::from("new.txt"),
+        PathBuf::from("dir"),
+    ];
+    let updated = git_repo
+        .update_snapshot(&previous, &changed)
+        .expect("failed to update snapshot");
+    let full = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+
+    assert_eq!(snapshot_summary(&updated), snapshot_summary(&full));
+    let kept = updated
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("kept.txt"))
+        .expect("kept.txt should stay listed");
+    assert!(kept.hunks[0].seen);
+    assert!(updated
+        .files
+        .iter()
+        .any(|file| file.path == Path::new("dir/gone.txt") && file.status == "Deleted"));
+
+    // Reverting a file drops it from the snapshot
+    repo.write_file("edited.txt", "one\n");
+    let reverted = git_repo
+        .update_snapshot(&updated, &[PathBuf::from("edited.txt")])
+        .expect("failed to update snapshot");
+    assert!(!reverted
+        .files
+        .iter()
+        .any(|file| file.path == Path::new("edited.txt")));
+}
+
+#[test]
+fn update_snapshot_falls_back_to_a_full_snapshot_when_collapsing_untracked_dirs() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    git_repo.set_untracked_mode(UntrackedMode::Collapse);
+    let previous = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+
+    fs::create_dir_all(repo.path.join("build")).expect("failed to create dir");
+    repo.write_file("build/out.txt", "out\n");
+    let updated = git_repo
+        .update_snapshot(&previous, &[PathBuf::from("build/out.txt")])
+        .expect("failed to update snapshot");
+    assert_eq!(
+        snapshot_summary(&updated),
+        snapshot_summary(&git_repo.get_diff_snapshot().expect("snapshot"))
+    );
+    assert!(updated
+        .files
+        .iter()
+        .all(|file| file.path != Path::new("build/out.txt")));
+}
+
+#[test]
+fn staged_changes_match_per_hunk_detection() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a1\na2\na3\n");
+    repo.write_file("b.txt", "b1\nb2\n");
+    repo.commit_all("initial");
+    repo.write_file("a.txt", "a1\nstaged\na2\na3\nunstaged\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    repo.write_file("a.txt", "a1\nstaged\na2\na3\nunstaged\nlater\n");
+    repo.write_file("b.txt", "b2\n");
+    run_git(&repo.path, &["add", "b.txt"]);
+    repo.write_file("c.txt", "new\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let paths: Vec<&Path> = snapshot.files.iter().map(|f| f.path.as_path()).collect();
+    let staged = git_repo
+        .staged_changes(&paths)
+        .expect("failed to read staged changes");
+
+    let mut any_staged = false;
+    for file in &snapshot.files {
+        for hunk in &file.hunks {
+            let batched = staged
+                .staged_lines(hunk, &file.path)
+                .expect("every snapshot path should be covered");
+            let single = git_repo
+                .detect_staged_lines(hunk, &file.path)
+                .expect("failed to detect staged lines");
+            assert_eq!(batched, single, "{}", file.path.display());
+            any_staged |= !batched.is_empty();
+        }
+    }
+    assert!(any_staged);
+    assert!(staged
+        .staged_lines(&snapshot.files[0].hunks[0], Path::new("other.txt"))
+        .is_none());
+}
+
 #[test]
-#[ignore = "TDD regression: expected to fail until flake.lock stage_hunk behavior is fixed"]
 fn regression_flake_lock_stage_hunk_from_partial_index_state() {
     let repo = TestRepo::new();
 
@@ -779,7 +1010,7 @@ fn regression_flake_lock_stage_hunk_from_partial_index_state() {
     let file_change = snapshot
         .files
         .iter()
-        .find(|file| file.path == PathBuf::from("flake.lock"))
+        .find(|file| file.path == Path::new("flake.lock"))
         .expect("expected flake.lock in diff");
     let hunk = file_change
         .hunks
@@ -851,7 +1082,7 @@ fn get_commit_diff_returns_snapshot_for_commit() {
     let file = snapshot
         .files
         .iter()
-        .find(|f| f.path == PathBuf::from("example.txt"))
+        .find(|f| f.path == Path::new("example.txt"))
         .expect("expected example.txt in commit diff");
     assert!(!file.hunks.is_empty());
 
@@ -886,3 +1117,1467 @@ fn get_commit_diff_handles_initial_commit() {
 
     assert!(!snapshot.files.is_empty());
 }
+
+#[test]
+fn apply_edited_hunk_stages_edited_new_side() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\nextra\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    let file_path = Path::new("example.txt");
+
+    // Keep "two" as context, reword the addition and drop the trailing one
+    let patch = GitRepo::hunk_edit_patch(hunk, file_path, "Modified");
+    assert!(patch.contains("@@ -1,3 +1,4 @@\n"));
+    let edited = patch
+        .replace("-two\n", " two\n")
+        .replace("+two-A\n", "+two-B\n")
+        .replace("+extra\n", "");
+
+    git_repo
+        .apply_edited_hunk(hunk, file_path, &edited)
+        .expect("failed to apply edited hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo\ntwo-B\nthree\n");
+
+    // The unedited patch stages the hunk exactly like `stage_hunk`
+    git_repo
+        .apply_edited_hunk(hunk, file_path, &patch)
+        .expect("failed to apply unedited hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A\nthree\nextra\n");
+}
+
+#[test]
+fn apply_edited_hunk_keeps_final_newline_unless_marked() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    let file_path = Path::new("example.txt");
+
+    // Saved by an editor that drops the newline after the last line
+    let patch = GitRepo::hunk_edit_patch(hunk, file_path, "Modified");
+    let edited: String = patch
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(edited.ends_with("+two-A"));
+    git_repo
+        .apply_edited_hunk(hunk, file_path, &edited)
+        .expect("failed to apply edited hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A\n");
+
+    let marked = format!("{}\n\\ No newline at end of file\n", edited);
+    git_repo
+        .apply_edited_hunk(hunk, file_path, &marked)
+        .expect("failed to apply marked hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A");
+}
+
+#[test]
+fn apply_edited_hunk_rejects_changed_context_and_keeps_index() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A\nthree\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    let file_path = Path::new("example.txt");
+    let patch = GitRepo::hunk_edit_patch(hunk, file_path, "Modified");
+
+    let err = git_repo
+        .apply_edited_hunk(hunk, file_path, &patch.replace(" one\n", " uno\n"))
+        .expect_err("changed context should not apply");
+    assert!(err.to_string().contains("does not apply"));
+
+    let err = git_repo
+        .apply_edited_hunk(hunk, file_path, &patch.replace("+two-A\n", "*two-A\n"))
+        .expect_err("bad prefix should not apply");
+    assert!(err.to_string().contains("Body line"));
+
+    let cached = run_git(&repo.path, &["diff", "--cached", "--name-only"]);
+    assert!(cached.trim().is_empty());
+}
+
+#[test]
+fn apply_edited_hunk_preserves_missing_newline_at_eof() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\ntwo");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo-A");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let hunk = snapshot.files[0].hunks.first().expect("expected hunk");
+    let file_path = Path::new("example.txt");
+    let patch = GitRepo::hunk_edit_patch(hunk, file_path, "Modified");
+    assert!(patch.contains("+two-A\n\\ No newline at end of file\n"));
+
+    git_repo
+        .apply_edited_hunk(hunk, file_path, &patch)
+        .expect("failed to apply edited hunk");
+    let index_content = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(index_content, "one\ntwo-A");
+}
+
+#[test]
+fn create_commit_commits_index_and_amends_head() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo\n");
+    repo.write_file("other.txt", "unstaged\n");
+    run_git(&repo.path, &["add", "example.txt"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let staged = git_repo
+        .staged_files()
+        .expect("failed to list staged files");
+    assert_eq!(staged.len(), 1);
+    assert_eq!(staged[0].path, Path::new("example.txt"));
+    assert_eq!((staged[0].insertions, staged[0].deletions), (1, 0));
+
+    git_repo
+        .create_commit("Add two\n\n# stripped comment\n", false)
+        .expect("failed to commit");
+    let log = run_git(&repo.path, &["log", "--format=%B", "-1"]);
+    assert_eq!(log.trim_end(), "Add two");
+    let files = run_git(&repo.path, &["show", "--name-only", "--format=", "HEAD"]);
+    assert_eq!(files.trim(), "example.txt");
+    assert!(git_repo.staged_files().expect("staged files").is_empty());
+
+    git_repo
+        .create_commit("Add two, reworded\n", true)
+        .expect("failed to amend");
+    let count = run_git(&repo.path, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "2");
+    assert_eq!(
+        git_repo.head_commit_message().expect("head message"),
+        Some("Add two, reworded\n".to_string())
+    );
+
+    let err = git_repo
+        .create_commit("# only a comment\n", false)
+        .expect_err("empty message should be rejected");
+    assert!(err.to_string().contains("empty commit message"));
+}
+
+#[test]
+fn create_commit_handles_unborn_head() {
+    let repo = TestRepo::new();
+    repo.write_file("first.txt", "hello\n");
+    run_git(&repo.path, &["add", "first.txt"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    assert_eq!(git_repo.head_commit_message().expect("head message"), None);
+    git_repo
+        .create_commit("Root commit\n", false)
+        .expect("failed to create root commit");
+
+    let count = run_git(&repo.path, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count.trim(), "1");
+}
+
+#[test]
+fn create_commit_finishes_a_merge_in_progress() {
+    let repo = conflicting_merge_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let err = git_repo
+        .create_commit("Merge topic\n", false)
+        .expect_err("conflicts are unresolved");
+    assert!(err.to_string().contains("Resolve the conflicted files"));
+
+    repo.write_file("a.txt", "1\nboth 2\n3\n4\n5\n6\nboth 7\n8\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    let err = git_repo
+        .create_commit("Merge topic\n", true)
+        .expect_err("amending mid-merge");
+    assert!(err.to_string().contains("merge is in progress"));
+
+    git_repo
+        .create_commit("Merge topic\n", false)
+        .expect("failed to create merge commit");
+    let parents = run_git(&repo.path, &["rev-list", "--parents", "-n", "1", "HEAD"]);
+    assert_eq!(parents.split_whitespace().count(), 3);
+    assert!(!repo.path.join(".git/MERGE_HEAD").exists());
+    assert_eq!(git_repo.repo_status().expect("status").operation, None);
+}
+
+#[test]
+fn commit_template_and_signoff_come_from_config() {
+    let repo = TestRepo::new();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    assert_eq!(git_repo.commit_template().expect("template"), None);
+
+    repo.write_file("template.txt", "Summary\n\n# Why?\n");
+    run_git(&repo.path, &["config", "commit.template", "template.txt"]);
+    assert_eq!(
+        git_repo.commit_template().expect("template"),
+        Some("Summary\n\n# Why?\n".to_string())
+    );
+
+    let trailer = git_repo.signoff_trailer().expect("signoff trailer");
+    assert!(trailer.starts_with("Signed-off-by: "));
+}
+
+#[test]
+fn fixup_commit_autosquashes_into_target() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+    repo.write_file("b.txt", "b\n");
+    repo.commit_all("Add b");
+    let target = run_git(&repo.path, &["rev-parse", "HEAD~1"])
+        .trim()
+        .to_string();
+
+    repo.write_file("a.txt", "a fixed\n");
+    repo.write_file("b.txt", "b local\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    git_repo
+        .create_fixup_commit(&target)
+        .expect("failed to create fixup commit");
+    let subject = run_git(&repo.path, &["log", "--format=%s", "-1"]);
+    assert_eq!(subject.trim(), "fixup! Add a");
+
+    git_repo
+        .autosquash_onto(&target)
+        .expect("failed to autosquash");
+    let subjects = run_git(&repo.path, &["log", "--format=%s"]);
+    assert_eq!(subjects, "Add b\nAdd a\n");
+    let first = run_git(&repo.path, &["show", "HEAD~1:a.txt"]);
+    assert_eq!(first, "a fixed\n");
+    // Unstaged work survives the rebase
+    let worktree = fs::read_to_string(repo.path.join("b.txt")).expect("failed to read");
+    assert_eq!(worktree, "b local\n");
+}
+
+#[test]
+fn fixup_and_amend_require_staged_changes() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+    let head = run_git(&repo.path, &["rev-parse", "HEAD"])
+        .trim()
+        .to_string();
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    assert!(git_repo.create_fixup_commit(&head).is_err());
+    assert!(git_repo.amend_head_with_index().is_err());
+
+    repo.write_file("a.txt", "a amended\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    git_repo.amend_head_with_index().expect("failed to amend");
+    let log = run_git(&repo.path, &["log", "--format=%s"]);
+    assert_eq!(log, "Add a\n");
+    assert_eq!(run_git(&repo.path, &["show", "HEAD:a.txt"]), "a amended\n");
+}
+
+#[test]
+fn autosquash_aborts_cleanly_on_conflict() {
+    let repo = TestRepo::new();
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+    let target = run_git(&repo.path, &["rev-parse", "HEAD"])
+        .trim()
+        .to_string();
+    repo.write_file("a.txt", "a two\n");
+    repo.commit_all("Change a");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    repo.write_file("a.txt", "a three\n");
+    run_git(&repo.path, &["add", "a.txt"]);
+    git_repo
+        .create_fixup_commit(&target)
+        .expect("failed to create fixup commit");
+    let before = run_git(&repo.path, &["rev-parse", "HEAD"]);
+
+    let err = git_repo
+        .autosquash_onto(&target)
+        .expect_err("conflicting fixup should abort");
+    assert!(err.to_string().contains("Autosquash aborted"));
+    assert_eq!(run_git(&repo.path, &["rev-parse", "HEAD"]), before);
+    assert!(!repo.path.join(".git/rebase-merge").exists());
+}
+
+#[test]
+fn autosquash_leaves_a_rebase_already_in_progress_alone() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("Add a");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "topic\n");
+    repo.commit_all("Topic change");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    repo.write_file("a.txt", "main\n");
+    repo.commit_all("Main change");
+    run_git(&repo.path, &["checkout", "-q", "topic"]);
+    let output = Command::new("git")
+        .args(["rebase", "main"])
+        .current_dir(&repo.path)
+        .output()
+        .expect("failed to run git rebase");
+    assert!(!output.status.success(), "rebase should conflict");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let target = run_git(&repo.path, &["rev-parse", "main"]);
+    let err = git_repo
+        .autosquash_onto(target.trim())
+        .expect_err("a rebase is already in progress");
+    assert!(err.to_string().contains("REBASING"));
+    assert!(repo.path.join(".git/rebase-merge").exists());
+}
+
+#[test]
+fn stash_hunks_moves_only_selected_hunks_into_a_stash() {
+    let repo = TestRepo::new();
+    let base: String = (1..=12).map(|i| format!("line {}\n", i)).collect();
+    repo.write_file("example.txt", &base);
+    repo.write_file("other.txt", "other\n");
+    repo.commit_all("initial");
+    let modified = base
+        .replace("line 2\n", "line 2 changed\n")
+        .replace("line 11\n", "line 11 changed\n");
+    repo.write_file("example.txt", &modified);
+    repo.write_file("other.txt", "other changed\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let example = snapshot
+        .files
+        .iter()
+        .find(|f| f.path == Path::new("example.txt"))
+        .expect("expected example.txt");
+    assert_eq!(example.hunks.len(), 2);
+    let selected = vec![(example.path.clone(), example.hunks[1].clone())];
+
+    git_repo
+        .stash_hunks(&selected, "second hunk")
+        .expect("failed to stash hunks");
+
+    let worktree = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(worktree, base.replace("line 2\n", "line 2 changed\n"));
+    let other = fs::read_to_string(repo.path.join("other.txt")).expect("failed to read");
+    assert_eq!(other, "other changed\n");
+
+    let stashes = git_repo.list_stashes().expect("failed to list stashes");
+    assert_eq!(stashes.len(), 1);
+    assert!(stashes[0].message.ends_with("second hunk"));
+    assert_eq!(stashes[0].file_count, 1);
+    let stash_diff = git_repo
+        .get_commit_diff(&stashes[0].sha)
+        .expect("failed to load stash diff");
+    assert_eq!(stash_diff.files.len(), 1);
+    assert_eq!(stash_diff.files[0].hunks.len(), 1);
+
+    // Like `git stash pop`, local changes to the same file block the pop
+    assert!(git_repo.pop_stash(0).is_err());
+    assert_eq!(git_repo.list_stashes().expect("list").len(), 1);
+
+    run_git(&repo.path, &["checkout", "--", "example.txt"]);
+    git_repo.pop_stash(0).expect("failed to pop stash");
+    let worktree = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(
+        worktree,
+        base.replace(
+            "line 11
+",
+            "line 11 changed
+"
+        )
+    );
+    assert!(git_repo.list_stashes().expect("list").is_empty());
+}
+
+#[test]
+fn apply_and_drop_stash_entries() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo\n");
+    run_git(&repo.path, &["stash", "push", "-m", "saved work"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let stashes = git_repo.list_stashes().expect("failed to list stashes");
+    assert_eq!(stashes.len(), 1);
+    assert!(stashes[0].message.contains("saved work"));
+
+    git_repo.apply_stash(0).expect("failed to apply stash");
+    let worktree = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(worktree, "one\ntwo\n");
+    assert_eq!(git_repo.list_stashes().expect("list").len(), 1);
+
+    git_repo.drop_stash(0).expect("failed to drop stash");
+    assert!(git_repo.list_stashes().expect("list").is_empty());
+}
+
+#[test]
+fn stash_hunks_puts_changes_back_when_the_entry_cannot_be_stored() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo\n");
+    run_git(&repo.path, &["add", "example.txt"]);
+    repo.write_file("example.txt", "one\ntwo\nthree\n");
+    // A ref below refs/stash leaves no room for the stash ref itself
+    let head = run_git(&repo.path, &["rev-parse", "HEAD"]);
+    run_git(
+        &repo.path,
+        &["update-ref", "refs/stash/blocked", head.trim()],
+    );
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let snapshot = git_repo
+        .get_diff_snapshot()
+        .expect("failed to get diff snapshot");
+    let file = &snapshot.files[0];
+    let selected = vec![(file.path.clone(), file.hunks[0].clone())];
+
+    assert!(git_repo.stash_hunks(&selected, "blocked").is_err());
+    let worktree = fs::read_to_string(repo.path.join("example.txt")).expect("failed to read");
+    assert_eq!(worktree, "one\ntwo\nthree\n");
+    let staged = run_git(&repo.path, &["show", ":example.txt"]);
+    assert_eq!(staged, "one\ntwo\n");
+}
+
+#[test]
+fn stash_diff_lists_untracked_files_of_the_entry() {
+    let repo = TestRepo::new();
+    repo.write_file("example.txt", "one\n");
+    repo.commit_all("initial");
+    repo.write_file("example.txt", "one\ntwo\n");
+    repo.write_file("new.txt", "new\n");
+    run_git(&repo.path, &["stash", "push", "-u", "-m", "with untracked"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let stashes = git_repo.list_stashes().expect("failed to list stashes");
+    let stash_diff = git_repo
+        .get_stash_diff(&stashes[0].sha)
+        .expect("failed to load stash diff");
+    let statuses: Vec<(&Path, &str)> = stash_diff
+        .files
+        .iter()
+        .map(|file| (file.path.as_path(), file.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (Path::new("example.txt"), "Modified"),
+            (Path::new("new.txt"), "Untracked"),
+        ]
+    );
+    assert_eq!(stash_diff.files[1].hunks.len(), 1);
+}
+
+#[test]
+fn repo_status_reports_branch_detached_and_unborn_head() {
+    let repo = TestRepo::new();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+    let status = git_repo.repo_status().expect("status");
+    assert!(status.unborn);
+    assert_eq!(status.branch.as_deref(), Some("main"));
+    assert_eq!(status.head_short_sha, None);
+
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    let status = git_repo.repo_status().expect("status");
+    assert!(!status.unborn && !status.detached);
+    assert_eq!(status.branch.as_deref(), Some("main"));
+    assert_eq!(status.operation, None);
+
+    run_git(&repo.path, &["checkout", "-q", "--detach"]);
+    let status = git_repo.repo_status().expect("status");
+    assert!(status.detached);
+    assert_eq!(status.branch, None);
+    let sha = run_git(&repo.path, &["rev-parse", "--short=7", "HEAD"]);
+    assert_eq!(status.head_short_sha.as_deref(), Some(sha.trim()));
+}
+
+#[test]
+fn repo_status_counts_ahead_and_behind_upstream() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["branch", "base"]);
+    run_git(&repo.path, &["config", "branch.main.remote", "."]);
+    run_git(
+        &repo.path,
+        &["config", "branch.main.merge", "refs/heads/base"],
+    );
+
+    repo.write_file("a.txt", "a\nb\n");
+    repo.commit_all("ahead one");
+    repo.write_file("a.txt", "a\nb\nc\n");
+    repo.commit_all("ahead two");
+    run_git(&repo.path, &["checkout", "-q", "base"]);
+    repo.write_file("base.txt", "base\n");
+    repo.commit_all("behind one");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let status = git_repo.repo_status().expect("status");
+    assert_eq!(status.upstream.as_deref(), Some("base"));
+    assert_eq!((status.ahead, status.behind), (2, 1));
+}
+
+#[test]
+fn repo_status_reports_in_progress_operations() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "topic\n");
+    repo.commit_all("topic change");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    repo.write_file("a.txt", "main\n");
+    repo.commit_all("main change");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let _ = Command::new("git")
+        .args(["merge", "topic"])
+        .current_dir(&repo.path)
+        .output();
+    assert_eq!(
+        git_repo.repo_status().expect("status").operation,
+        Some(RepoOperation::Merge)
+    );
+    run_git(&repo.path, &["merge", "--abort"]);
+
+    run_git(&repo.path, &["checkout", "-q", "topic"]);
+    let _ = Command::new("git")
+        .args(["rebase", "main"])
+        .current_dir(&repo.path)
+        .output();
+    let status = git_repo.repo_status().expect("status");
+    assert_eq!(status.operation, Some(RepoOperation::Rebase));
+    assert!(status.detached);
+    assert_eq!(status.branch.as_deref(), Some("topic"));
+    run_git(&repo.path, &["rebase", "--abort"]);
+
+    let _ = Command::new("git")
+        .args(["cherry-pick", "main"])
+        .current_dir(&repo.path)
+        .output();
+    assert_eq!(
+        git_repo.repo_status().expect("status").operation,
+        Some(RepoOperation::CherryPick)
+    );
+    run_git(&repo.path, &["cherry-pick", "--abort"]);
+
+    run_git(&repo.path, &["bisect", "start"]);
+    assert_eq!(
+        git_repo.repo_status().expect("status").operation,
+        Some(RepoOperation::Bisect)
+    );
+    run_git(&repo.path, &["bisect", "reset"]);
+    assert_eq!(git_repo.repo_status().expect("status").operation, None);
+}
+
+#[test]
+fn describe_head_move_classifies_commit_checkout_and_reset() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let unborn = git_repo.head_state().expect("head state");
+    assert_eq!(unborn.branch.as_deref(), Some("main"));
+    assert_eq!(unborn.oid, None);
+
+    repo.write_file("a.txt", "a\n");
+    repo.commit_all("initial");
+    let first = git_repo.head_state().expect("head state");
+    assert_eq!(first.branch.as_deref(), Some("main"));
+    let head_move = git_repo
+        .describe_head_move(&unborn, &first)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Commit);
+    assert_eq!(head_move.commit.summary, "initial");
+    assert_eq!(head_move.commit.author, "Test User");
+
+    assert_eq!(
+        git_repo
+            .describe_head_move(&first, &first)
+            .expect("describe"),
+        None
+    );
+
+    repo.write_file("a.txt", "a\nb\n");
+    repo.commit_all("second");
+    let second = git_repo.head_state().expect("head state");
+    let head_move = git_repo
+        .describe_head_move(&first, &second)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Commit);
+    assert_eq!(head_move.commit.sha, second.oid.clone().expect("oid"));
+
+    run_git(
+        &repo.path,
+        &["commit", "-q", "--amend", "-m", "second, amended"],
+    );
+    let amended = git_repo.head_state().expect("head state");
+    let head_move = git_repo
+        .describe_head_move(&second, &amended)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Reset);
+
+    run_git(&repo.path, &["reset", "-q", "--hard", "HEAD~1"]);
+    let reset = git_repo.head_state().expect("head state");
+    assert_eq!(reset, first);
+    let head_move = git_repo
+        .describe_head_move(&amended, &reset)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Reset);
+
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    let topic = git_repo.head_state().expect("head state");
+    let head_move = git_repo
+        .describe_head_move(&reset, &topic)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Checkout);
+    assert_eq!(head_move.branch.as_deref(), Some("topic"));
+
+    run_git(&repo.path, &["checkout", "-q", "--detach"]);
+    let detached = git_repo.head_state().expect("head state");
+    assert_eq!(detached.branch, None);
+    let head_move = git_repo
+        .describe_head_move(&topic, &detached)
+        .expect("describe")
+        .expect("HEAD moved");
+    assert_eq!(head_move.kind, HeadMoveKind::Checkout);
+}
+
+/// Repo on `main` with a `topic` branch whose merge conflicts in two places of `a.txt`
+fn conflicting_merge_repo() -> TestRepo {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "1\n2\n3\n4\n5\n6\n7\n8\n");
+    repo.write_file("clean.txt", "clean\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "1\ntopic 2\n3\n4\n5\n6\ntopic 7\n8\n");
+    repo.commit_all("topic change");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    repo.write_file("a.txt", "1\nmain 2\n3\n4\n5\n6\nmain 7\n8\n");
+    repo.commit_all("main change");
+
+    let output = Command::new("git")
+        .args(["merge", "topic"])
+        .current_dir(&repo.path)
+        .output()
+        .expect("failed to run git merge");
+    assert!(!output.status.success(), "merge should conflict");
+    repo
+}
+
+#[test]
+fn conflicted_files_reports_regions_with_base_from_index() {
+    let repo = conflicting_merge_repo();
+    repo.write_file("clean.txt", "clean\nedited\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let files = git_repo.conflicted_files().expect("conflicts");
+    assert_eq!(files.len(), 1);
+    let file = &files[0];
+    assert_eq!(file.path, PathBuf::from("a.txt"));
+    assert!(file.has_base && file.has_ours && file.has_theirs);
+    assert_eq!(file.regions.len(), 2);
+    assert_eq!(file.regions[0].ours, vec!["main 2\n".to_string()]);
+    assert_eq!(file.regions[0].theirs, vec!["topic 2\n".to_string()]);
+    assert_eq!(file.regions[0].base, Some(vec!["2\n".to_string()]));
+    assert_eq!(file.regions[0].ours_label, "HEAD");
+    assert_eq!(file.regions[0].theirs_label, "topic");
+    assert_eq!(file.regions[1].base, Some(vec!["7\n".to_string()]));
+    // Recovering the base leaves the index and the git directory as they were
+    let unmerged = run_git(&repo.path, &["ls-files", "--unmerged"]);
+    assert_eq!(unmerged.lines().count(), 3);
+    let scratch = fs::read_dir(repo.path.join(".git"))
+        .expect("failed to read .git")
+        .filter_map(Result::ok)
+        .any(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("HUNKY_MERGE")
+        });
+    assert!(!scratch);
+
+    // The conflicted file is left out of the regular hunks
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let paths: Vec<_> = snapshot
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect();
+    assert_eq!(paths, vec![PathBuf::from("clean.txt")]);
+}
+
+#[test]
+fn resolving_regions_then_marking_the_file_resolved() {
+    let repo = conflicting_merge_repo();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let path = Path::new("a.txt");
+
+    git_repo
+        .resolve_conflict_region(path, 1, ConflictChoice::Both)
+        .expect("resolve second region");
+    let err = git_repo
+        .mark_conflict_resolved(path)
+        .expect_err("markers remain");
+    assert!(err.to_string().contains("1 unresolved conflict"), "{}", err);
+
+    let files = git_repo.conflicted_files().expect("conflicts");
+    assert_eq!(files[0].regions.len(), 1);
+    assert_eq!(files[0].regions[0].base, Some(vec!["2\n".to_string()]));
+
+    git_repo
+        .resolve_conflict_region(path, 0, ConflictChoice::Theirs)
+        .expect("resolve first region");
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).expect("read"),
+        "1\ntopic 2\n3\n4\n5\n6\nmain 7\ntopic 7\n8\n"
+    );
+
+    git_repo
+        .mark_conflict_resolved(path)
+        .expect("mark resolved");
+    assert!(git_repo.conflicted_files().expect("conflicts").is_empty());
+    assert_eq!(
+        run_git(&repo.path, &["show", ":a.txt"]),
+        "1\ntopic 2\n3\n4\n5\n6\nmain 7\ntopic 7\n8\n"
+    );
+}
+
+#[test]
+fn take_conflict_side_handles_modify_delete_conflicts() {
+    let repo = TestRepo::new();
+    run_git(&repo.path, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    repo.write_file("a.txt", "a\n");
+    repo.write_file("b.txt", "b\n");
+    repo.commit_all("initial");
+    run_git(&repo.path, &["checkout", "-q", "-b", "topic"]);
+    repo.write_file("a.txt", "a topic\n");
+    repo.write_file("b.txt", "b topic\n");
+    repo.commit_all("topic edits");
+    run_git(&repo.path, &["checkout", "-q", "main"]);
+    run_git(&repo.path, &["rm", "-q", "a.txt", "b.txt"]);
+    run_git(&repo.path, &["commit", "-q", "-m", "remove files"]);
+    let _ = Command::new("git")
+        .args(["merge", "topic"])
+        .current_dir(&repo.path)
+        .output();
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let files = git_repo.conflicted_files().expect("conflicts");
+    assert_eq!(files.len(), 2);
+    assert!(!files[0].has_ours && files[0].has_theirs);
+    assert!(files[0].regions.is_empty());
+
+    let err = git_repo
+        .take_conflict_side(Path::new("a.txt"), ConflictChoice::Both)
+        .expect_err("nothing to combine");
+    assert!(err.to_string().contains("Pick ours or theirs"), "{}", err);
+
+    git_repo
+        .take_conflict_side(Path::new("a.txt"), ConflictChoice::Theirs)
+        .expect("take theirs");
+    git_repo
+        .take_conflict_side(Path::new("b.txt"), ConflictChoice::Ours)
+        .expect("take ours");
+
+    assert!(git_repo.conflicted_files().expect("conflicts").is_empty());
+    assert_eq!(run_git(&repo.path, &["show", ":a.txt"]), "a topic\n");
+    assert!(!repo.path.join("b.txt").exists());
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(!status.contains("b.txt"), "{}", status);
+}
+
+#[test]
+fn untracked_mode_shows_collapses_or_hides_untracked_files() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+    fs::create_dir_all(repo.path.join("build/out")).expect("failed to create dir");
+    repo.write_file("build/out/a.o", "a\n");
+    repo.write_file("build/b.o", "b\n");
+    repo.write_file("new.txt", "new line\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let paths = |snapshot: &DiffSnapshot| -> Vec<String> {
+        snapshot
+            .files
+            .iter()
+            .map(|file| file.path.to_string_lossy().into_owned())
+            .collect()
+    };
+
+    assert_eq!(git_repo.untracked_mode(), UntrackedMode::Show);
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    assert_eq!(
+        paths(&snapshot),
+        vec!["build/b.o", "build/out/a.o", "new.txt"]
+    );
+    let new_file = &snapshot.files[2];
+    assert_eq!(new_file.status, "Untracked");
+    assert_eq!(new_file.hunks.len(), 1);
+    assert_eq!(new_file.hunks[0].lines, vec!["+new line\n".to_string()]);
+
+    // The mode is shared with clones, like the watcher's
+    let watcher_repo = git_repo.clone();
+    git_repo.set_untracked_mode(UntrackedMode::Collapse);
+    let snapshot = watcher_repo.get_diff_snapshot().expect("snapshot");
+    assert_eq!(paths(&snapshot), vec!["build/", "new.txt"]);
+    assert!(snapshot.files[0].hunks.is_empty());
+
+    git_repo.set_untracked_mode(UntrackedMode::Hide);
+    assert!(git_repo
+        .get_diff_snapshot()
+        .expect("snapshot")
+        .files
+        .is_empty());
+}
+
+#[test]
+fn untracked_files_over_the_caps_are_summarized() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+    fs::create_dir_all(repo.path.join("gen")).expect("failed to create dir");
+    for idx in 0..UNTRACKED_FILE_LIMIT + 5 {
+        repo.write_file(&format!("gen/{:04}.txt", idx), "generated\n");
+    }
+    repo.write_file("tracked.txt", "tracked\nchanged\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    assert_eq!(snapshot.untracked_overflow, 5);
+    assert_eq!(snapshot.files.len(), UNTRACKED_FILE_LIMIT + 1);
+    assert!(snapshot
+        .files
+        .iter()
+        .any(|file| file.path == Path::new("tracked.txt")));
+
+    let large = "x".repeat(UNTRACKED_MAX_FILE_SIZE as usize + 1);
+    fs::remove_dir_all(repo.path.join("gen")).expect("failed to remove dir");
+    repo.write_file("large.bin", &large);
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let large_file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("large.bin"))
+        .expect("large file listed");
+    assert!(large_file.hunks.is_empty());
+    assert_eq!(snapshot.untracked_overflow, 0);
+}
+
+#[test]
+fn ignore_path_appends_anchored_patterns_once() {
+    let repo = TestRepo::new();
+    repo.write_file(".gitignore", "*.log");
+    repo.commit_all("initial");
+    fs::create_dir_all(repo.path.join("node_modules/pkg")).expect("failed to create dir");
+    repo.write_file("node_modules/pkg/index.js", "x\n");
+    repo.write_file("notes.txt", "mine\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    git_repo.set_untracked_mode(UntrackedMode::Collapse);
+
+    git_repo
+        .ignore_path(Path::new("node_modules/"), IgnoreTarget::GitIgnore)
+        .expect("ignore dir");
+    git_repo
+        .ignore_path(Path::new("node_modules/"), IgnoreTarget::GitIgnore)
+        .expect("ignore dir again");
+    assert_eq!(
+        fs::read_to_string(repo.path.join(".gitignore")).expect("read"),
+        "*.log\n/node_modules/\n"
+    );
+
+    git_repo
+        .ignore_path(Path::new("notes.txt"), IgnoreTarget::InfoExclude)
+        .expect("exclude file");
+    let exclude = fs::read_to_string(repo.path.join(".git/info/exclude")).expect("read");
+    assert!(exclude.ends_with("/notes.txt\n"), "{}", exclude);
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let paths: Vec<_> = snapshot
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect();
+    assert_eq!(paths, vec![PathBuf::from(".gitignore")]);
+}
+
+#[test]
+fn ignore_path_excludes_from_a_linked_worktree() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+    let worktree_path = repo.path.with_extension("exclude-wt");
+    run_git(
+        &repo.path,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "topic",
+            worktree_path.to_str().expect("utf-8 path"),
+        ],
+    );
+    fs::write(worktree_path.join("notes.txt"), "mine\n").expect("failed to write file");
+
+    let git_repo = GitRepo::new(&worktree_path).expect("open linked worktree");
+    git_repo
+        .ignore_path(Path::new("notes.txt"), IgnoreTarget::InfoExclude)
+        .expect("exclude file");
+    let exclude = fs::read_to_string(repo.path.join(".git/info/exclude")).expect("read");
+    assert!(exclude.ends_with("/notes.txt\n"), "{}", exclude);
+    let status = run_git(&worktree_path, &["status", "--porcelain"]);
+    assert!(status.is_empty(), "{}", status);
+
+    let _ = fs::remove_dir_all(&worktree_path);
+}
+
+#[test]
+fn intent_to_add_lets_new_files_be_staged_line_by_line() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+    repo.write_file("new.txt", "one\ntwo\nthree\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+    let file_path = Path::new("new.txt");
+
+    git_repo.intent_to_add(file_path).expect("intent to add");
+    assert!(git_repo.intent_to_add(file_path).is_err());
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains(" A new.txt"), "{}", status);
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == file_path)
+        .expect("new file listed");
+    let hunk = file.hunks[0].clone();
+    assert_eq!(
+        GitRepo::hunk_edit_patch(&hunk, file_path, &file.status)
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .take(3)
+            .collect::<Vec<_>>(),
+        vec!["--- /dev/null", "+++ b/new.txt", "@@ -0,0 +1,3 @@"]
+    );
+
+    git_repo
+        .stage_single_line(&hunk, 1, file_path)
+        .expect("stage second line");
+    assert_eq!(run_git(&repo.path, &["show", ":new.txt"]), "two\n");
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains("AM new.txt"), "{}", status);
+
+    // Unstaging the last staged line leaves the file untracked again
+    git_repo
+        .unstage_single_line(&hunk, 1, file_path)
+        .expect("unstage second line");
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains("?? new.txt"), "{}", status);
+}
+
+#[test]
+fn staging_a_deleted_file_removes_it_from_the_index() {
+    let repo = TestRepo::new();
+    repo.write_file("keep.txt", "keep\n");
+    repo.write_file("gone.txt", "one\ntwo\n");
+    repo.write_file("gone-too.txt", "bye\n");
+    repo.commit_all("initial");
+    fs::remove_file(repo.path.join("gone.txt")).expect("remove");
+    fs::remove_file(repo.path.join("gone-too.txt")).expect("remove");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("gone.txt"))
+        .expect("deleted file listed");
+    assert_eq!(file.status, "Deleted");
+    let patch = GitRepo::hunk_edit_patch(&file.hunks[0], &file.path, &file.status);
+    assert!(
+        patch.contains("--- a/gone.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n"),
+        "{}",
+        patch
+    );
+
+    git_repo
+        .stage_hunk(&file.hunks[0], &file.path)
+        .expect("stage deletion hunk");
+    git_repo
+        .stage_file(Path::new("gone-too.txt"))
+        .expect("stage deleted file");
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains("D  gone.txt"), "{}", status);
+    assert!(status.contains("D  gone-too.txt"), "{}", status);
+
+    git_repo
+        .unstage_hunk(&file.hunks[0], &file.path)
+        .expect("unstage deletion hunk");
+    let status = run_git(&repo.path, &["status", "--porcelain"]);
+    assert!(status.contains(" D gone.txt"), "{}", status);
+}
+
+#[test]
+fn worktree_paths_and_external_git_dirs_for_linked_worktrees() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "tracked\n");
+    repo.commit_all("initial");
+    let worktree_path = repo.path.with_extension("wt");
+    run_git(
+        &repo.path,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "topic",
+            worktree_path.to_str().expect("utf-8 path"),
+        ],
+    );
+
+    let main_repo = GitRepo::new(&repo.path).expect("open main worktree");
+    let linked_repo = GitRepo::new(&worktree_path).expect("open linked worktree");
+    assert_eq!(
+        linked_repo.repo_status().expect("status").branch.as_deref(),
+        Some("topic")
+    );
+
+    for git_repo in [&main_repo, &linked_repo] {
+        let paths = git_repo.worktree_paths().expect("worktree paths");
+        assert_eq!(paths.len(), 2, "{:?}", paths);
+        assert!(paths[0].starts_with(&repo.path), "{:?}", paths);
+        assert!(
+            paths[1].ends_with(worktree_path.file_name().unwrap()),
+            "{:?}",
+            paths
+        );
+    }
+
+    // An ordinary .git lives inside the working tree and needs no extra watching
+    assert!(main_repo.external_git_dirs().expect("git dirs").is_empty());
+    let git_dirs = linked_repo.external_git_dirs().expect("git dirs");
+    assert_eq!(git_dirs.len(), 2, "{:?}", git_dirs);
+    assert!(git_dirs[0].join("HEAD").exists());
+    assert!(git_dirs[0].starts_with(&git_dirs[1]), "{:?}", git_dirs);
+    assert!(git_dirs[1].join("refs/heads/topic").exists());
+
+    let _ = fs::remove_dir_all(&worktree_path);
+}
+
+#[test]
+fn recursing_into_submodules_lists_and_stages_their_changes() {
+    let library = TestRepo::new();
+    library.write_file("lib.txt", "one\ntwo\n");
+    library.commit_all("library");
+    let repo = TestRepo::new();
+    repo.write_file("app.txt", "app\n");
+    repo.commit_all("initial");
+    run_git(
+        &repo.path,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            library.path.to_str().expect("utf-8 path"),
+            "vendor/lib",
+        ],
+    );
+    run_git(&repo.path, &["commit", "-qm", "add submodule"]);
+    let submodule_path = repo.path.join("vendor/lib");
+    fs::write(submodule_path.join("lib.txt"), "one\ntwo\nthree\n").expect("write");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    assert!(snapshot
+        .files
+        .iter()
+        .all(|file| file.path != Path::new("vendor/lib/lib.txt")));
+
+    git_repo.set_recurse_submodules(true);
+    assert!(git_repo.clone().recurses_submodules());
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    let file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("vendor/lib/lib.txt"))
+        .expect("submodule change listed under its path");
+    assert_eq!(file.hunks.len(), 1);
+    assert_eq!(
+        file.hunks[0].id.file_path,
+        PathBuf::from("vendor/lib/lib.txt")
+    );
+
+    // Staging goes to the submodule's index, not the superproject's
+    git_repo
+        .stage_hunk(&file.hunks[0], &file.path)
+        .expect("stage submodule hunk");
+    assert_eq!(
+        run_git(&submodule_path, &["diff", "--cached", "--name-only"]),
+        "lib.txt\n"
+    );
+    assert_eq!(
+        run_git(&repo.path, &["diff", "--cached", "--name-only"]),
+        ""
+    );
+    assert_eq!(
+        git_repo
+            .detect_staged_lines(&file.hunks[0], &file.path)
+            .expect("staged lines")
+            .len(),
+        1
+    );
+
+    // A batched read covers superproject and submodule files alike
+    repo.write_file("app.txt", "app\nmore\n");
+    let staged = git_repo
+        .staged_changes(&[Path::new("app.txt"), file.path.as_path()])
+        .expect("staged changes");
+    assert_eq!(
+        staged
+            .staged_lines(&file.hunks[0], &file.path)
+            .expect("submodule file read")
+            .len(),
+        1
+    );
+
+    git_repo
+        .unstage_file(&file.path)
+        .expect("unstage submodule file");
+    assert_eq!(
+        run_git(&submodule_path, &["diff", "--cached", "--name-only"]),
+        ""
+    );
+}
+
+#[test]
+fn empty_repo_has_no_history_and_a_clean_snapshot() {
+    let repo = TestRepo::new();
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open empty repo");
+
+    assert!(git_repo.get_recent_commits(20).expect("commits").is_empty());
+    assert!(git_repo
+        .get_diff_snapshot()
+        .expect("snapshot")
+        .files
+        .is_empty());
+    assert!(git_repo.head_commit_message().expect("message").is_none());
+    assert!(git_repo.staged_files().expect("staged files").is_empty());
+    let head = git_repo.head_state().expect("head state");
+    assert!(head.oid.is_none());
+    assert!(git_repo.repo_status().expect("status").unborn);
+}
+
+#[test]
+fn unborn_branch_stages_and_commits_new_files() {
+    let repo = TestRepo::new();
+    repo.write_file("first.txt", "one\ntwo\n");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open empty repo");
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    assert_eq!(snapshot.files.len(), 1);
+    let file = &snapshot.files[0];
+    git_repo
+        .stage_hunk(&file.hunks[0], &file.path)
+        .expect("stage on unborn branch");
+    assert_eq!(run_git(&repo.path, &["show", ":first.txt"]), "one\ntwo\n");
+    git_repo
+        .unstage_file(&file.path)
+        .expect("unstage on unborn branch");
+    assert!(run_git(&repo.path, &["ls-files"]).is_empty());
+
+    git_repo.stage_file(&file.path).expect("stage file");
+    let oid = git_repo.create_commit("first", false).expect("root commit");
+    let commits = git_repo.get_recent_commits(20).expect("commits");
+    assert_eq!(commits.len(), 1);
+    let diff = git_repo
+        .get_commit_diff(&oid.to_string())
+        .expect("root commit diff");
+    assert_eq!(diff.files[0].status, "Added");
+
+    // An orphan branch in a repository with history is unborn as well
+    run_git(&repo.path, &["checkout", "-q", "--orphan", "fresh"]);
+    assert!(git_repo.get_recent_commits(20).expect("commits").is_empty());
+    assert_eq!(
+        git_repo.repo_status().expect("status").branch.as_deref(),
+        Some("fresh")
+    );
+}
+
+#[test]
+fn shallow_clone_boundary_commit_reports_missing_parent() {
+    let origin = TestRepo::new();
+    origin.write_file("a.txt", "one\n");
+    origin.commit_all("first");
+    origin.write_file("a.txt", "one\ntwo\n");
+    origin.commit_all("second");
+    origin.write_file("a.txt", "one\ntwo\nthree\n");
+    origin.commit_all("third");
+    let clone_path = origin.path.with_extension("shallow");
+    run_git(
+        &origin.path,
+        &[
+            "clone",
+            "-q",
+            "--depth",
+            "2",
+            &format!("file://{}", origin.path.display()),
+            clone_path.to_str().expect("utf-8 path"),
+        ],
+    );
+    let git_repo = GitRepo::new(&clone_path).expect("failed to open shallow clone");
+
+    let commits = git_repo.get_recent_commits(20).expect("commits");
+    let summaries: Vec<_> = commits.iter().map(|c| c.summary.as_str()).collect();
+    assert_eq!(summaries, vec!["third", "second"]);
+
+    // The newest commit still diffs against its fetched parent
+    let diff = git_repo.get_commit_diff(&commits[0].sha).expect("diff");
+    assert_eq!(diff.files[0].status, "Modified");
+    assert_eq!(diff.files[0].hunks[0].count_changes(), 1);
+
+    // The boundary commit would otherwise look like it added every file
+    let err = git_repo
+        .get_commit_diff(&commits[1].sha)
+        .expect_err("boundary commit has no parent to diff against");
+    assert!(err.to_string().contains("shallow clone"), "{}", err);
+
+    let _ = fs::remove_dir_all(&clone_path);
+}
+
+#[test]
+fn separate_git_dir_from_a_gitfile_is_followed() {
+    let repo = TestRepo::new();
+    let work_tree = repo.path.join("work");
+    let git_dir = repo.path.join("meta.git");
+    run_git(
+        &repo.path,
+        &[
+            "init",
+            "-q",
+            "--separate-git-dir",
+            git_dir.to_str().expect("utf-8 path"),
+            work_tree.to_str().expect("utf-8 path"),
+        ],
+    );
+    run_git(&work_tree, &["config", "user.name", "Test User"]);
+    run_git(&work_tree, &["config", "user.email", "test@example.com"]);
+    fs::write(work_tree.join("a.txt"), "one\n").expect("write");
+    run_git(&work_tree, &["add", "."]);
+    run_git(&work_tree, &["commit", "-qm", "initial"]);
+    fs::write(work_tree.join("a.txt"), "one\ntwo\n").expect("write");
+
+    let git_repo = GitRepo::new(&work_tree).expect("failed to open work tree");
+    assert_eq!(git_repo.repo_path(), work_tree.as_path());
+    let git_dirs = git_repo.external_git_dirs().expect("git dirs");
+    assert!(
+        git_dirs.iter().any(|dir| dir.ends_with("meta.git")),
+        "{:?}",
+        git_dirs
+    );
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    git_repo
+        .stage_hunk(&snapshot.files[0].hunks[0], &snapshot.files[0].path)
+        .expect("stage");
+    assert_eq!(
+        run_git(&work_tree, &["diff", "--cached", "--name-only"]),
+        "a.txt\n"
+    );
+}
+
+#[test]
+fn explicit_git_dir_and_work_tree_are_used_together() {
+    let repo = TestRepo::new();
+    let work_tree = repo.path.join("checkout");
+    let git_dir = repo.path.join("store.git");
+    fs::create_dir_all(&work_tree).expect("create work tree");
+    run_git(
+        &repo.path,
+        &[
+            "init",
+            "-q",
+            "--bare",
+            git_dir.to_str().expect("utf-8 path"),
+        ],
+    );
+    let git_args = |args: &[&str]| -> String {
+        let mut full = vec![
+            "--git-dir",
+            git_dir.to_str().expect("utf-8 path"),
+            "--work-tree",
+            work_tree.to_str().expect("utf-8 path"),
+        ];
+        full.extend_from_slice(args);
+        run_git(&repo.path, &full)
+    };
+    git_args(&["config", "user.name", "Test User"]);
+    git_args(&["config", "user.email", "test@example.com"]);
+    fs::write(work_tree.join("a.txt"), "one\n").expect("write");
+    git_args(&["add", "."]);
+    git_args(&["commit", "-qm", "initial"]);
+    fs::write(work_tree.join("a.txt"), "one\ntwo\n").expect("write");
+
+    let git_repo = GitRepo::with_git_dir(&work_tree, &git_dir).expect("open git dir");
+    assert!(git_repo.repo_path().starts_with(&work_tree));
+    assert_eq!(git_repo.get_recent_commits(5).expect("commits").len(), 1);
+
+    let snapshot = git_repo.get_diff_snapshot().expect("snapshot");
+    assert_eq!(snapshot.files.len(), 1);
+    let (path, hunk) = (
+        snapshot.files[0].path.clone(),
+        snapshot.files[0].hunks[0].clone(),
+    );
+    git_repo.stage_hunk(&hunk, &path).expect("stage");
+    assert_eq!(git_args(&["diff", "--cached", "--name-only"]), "a.txt\n");
+    git_repo.unstage_file(&path).expect("unstage");
+
+    // Shelling out to git targets the same git directory and work tree
+    git_repo
+        .stash_hunks(&[(path, hunk)], "separate dir")
+        .expect("stash");
+    assert_eq!(git_repo.list_stashes().expect("stashes").len(), 1);
+    assert_eq!(
+        fs::read_to_string(work_tree.join("a.txt")).expect("read"),
+        "one\n"
+    );
+}
+
+fn numbered_lines(count: usize) -> String {
+    (1..=count).map(|n| format!("{}\n", n)).collect()
+}
+
+#[test]
+fn diff_snapshots_compares_file_contents_between_working_tree_states() {
+    let repo = TestRepo::new();
+    repo.write_file("edited.txt", &numbered_lines(20));
+    repo.write_file("reverted.txt", "kept\n");
+    repo.write_file("removed.txt", "removed\n");
+    repo.commit_all("initial");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    repo.write_file(
+        "edited.txt",
+        &numbered_lines(20).replace("\n5\n", "\nfive\n"),
+    );
+    repo.write_file("reverted.txt", "changed\n");
+    let before = git_repo.get_diff_snapshot().expect("snapshot");
+
+    // Line 5 changes again, so both snapshots have a hunk there
+    repo.write_file(
+        "edited.txt",
+        &numbered_lines(20).replace("\n5\n", "\nFIVE\n"),
+    );
+    repo.write_file("reverted.txt", "kept\n");
+    fs::remove_file(repo.path.join("removed.txt")).expect("failed to remove file");
+    repo.write_file("created.txt", "new\n");
+    let after = git_repo.get_diff_snapshot().expect("snapshot");
+
+    let delta = git_repo.diff_snapshots(&before, &after).expect("delta");
+    let summary = snapshot_summary(&delta);
+    let expected: Vec<(PathBuf, String, Vec<Vec<String>>)> = vec![
+        (
+            PathBuf::from("created.txt"),
+            "Added".to_string(),
+            vec![vec!["+new\n".to_string()]],
+        ),
+        (
+            PathBuf::from("edited.txt"),
+            "Modified".to_string(),
+            vec![["2", "3", "4", "-five", "+FIVE", "6", "7", "8"]
+                .iter()
+                .map(|line| match line.chars().next() {
+                    Some('-' | '+') => format!("{}\n", line),
+                    _ => format!(" {}\n", line),
+                })
+                .collect()],
+        ),
+        (
+            PathBuf::from("removed.txt"),
+            "Deleted".to_string(),
+            vec![vec!["-removed\n".to_string()]],
+        ),
+        (
+            PathBuf::from("reverted.txt"),
+            "Modified".to_string(),
+            vec![vec!["-changed\n".to_string(), "+kept\n".to_string()]],
+        ),
+    ];
+    assert_eq!(summary, expected);
+    assert_eq!(delta.files[1].hunks[0].old_start, 2);
+}
+
+#[test]
+fn diff_snapshots_leaves_out_changes_that_were_committed_in_between() {
+    let repo = TestRepo::new();
+    repo.write_file("committed.txt", "one\n");
+    repo.write_file("later.txt", "one\n");
+    repo.commit_all("initial");
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open test repo");
+
+    repo.write_file("committed.txt", "two\n");
+    let before = git_repo.get_diff_snapshot().expect("snapshot");
+    repo.commit_all("second");
+    repo.write_file("later.txt", "two");
+    let after = git_repo.get_diff_snapshot().expect("snapshot");
+    assert_ne!(before.head, after.head);
+
+    let delta = git_repo.diff_snapshots(&before, &after).expect("delta");
+    assert_eq!(
+        snapshot_summary(&delta),
+        vec![(
+            PathBuf::from("later.txt"),
+            "Modified".to_string(),
+            vec![vec![
+                "-one\n".to_string(),
+                "+two".to_string(),
+                ">\n\\ No newline at end of file\n".to_string(),
+            ]],
+        )]
+    );
+}
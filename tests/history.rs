@@ -0,0 +1,66 @@
+use super::*;
+use crate::diff::{FileChange, Hunk};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+fn snapshot(lines: &[&str]) -> DiffSnapshot {
+    let path = PathBuf::from("a.txt");
+    DiffSnapshot {
+        timestamp: SystemTime::now(),
+        head: None,
+        untracked_overflow: 0,
+        files: vec![FileChange {
+            path: path.clone(),
+            status: "Modified".to_string(),
+            hunks: vec![Hunk::new(
+                1,
+                1,
+                lines.iter().map(|line| line.to_string()).collect(),
+                &path,
+            )],
+        }],
+    }
+}
+
+#[test]
+fn records_snapshots_once_per_change() {
+    let mut history = SnapshotHistory::new();
+    history.record(&snapshot(&["-old\n", "+new\n"]));
+    history.record(&snapshot(&["-old\n", "+new\n"]));
+    history.record(&snapshot(&["-old\n", "+newer\n"]));
+    history.record(&snapshot(&["-old\n", "+new\n"]));
+
+    assert_eq!(history.len(), 3);
+    let added: Vec<_> = history
+        .iter()
+        .map(|snapshot| snapshot.files[0].hunks[0].lines[1].clone())
+        .collect();
+    assert_eq!(added, vec!["+new\n", "+newer\n", "+new\n"]);
+}
+
+#[test]
+fn drops_the_oldest_snapshots_past_the_limit() {
+    let mut history = SnapshotHistory::with_limit(2);
+    assert_eq!(history.record(&snapshot(&["+1\n"])), 0);
+    assert_eq!(history.record(&snapshot(&["+2\n"])), 0);
+    assert_eq!(history.record(&snapshot(&["+3\n"])), 1);
+
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history.get(0).unwrap().files[0].hunks[0].lines,
+        vec!["+2\n"]
+    );
+}
+
+#[test]
+fn line_counts_skip_file_headers() {
+    let snapshot = snapshot(&[
+        "--- a/a.txt\n",
+        "+++ b/a.txt\n",
+        " ctx\n",
+        "-old\n",
+        "+new\n",
+        "+more\n",
+    ]);
+    assert_eq!(snapshot.line_counts(), (2, 1));
+}
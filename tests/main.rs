@@ -4,19 +4,72 @@ use clap::CommandFactory;
 #[test]
 fn parses_default_repo_argument() {
     let args = Args::try_parse_from(["hunky"]).expect("args should parse");
-    assert_eq!(args.repo, ".");
+    assert_eq!(args.repo, vec!["."]);
+    assert!(!args.worktrees);
 }
 
 #[test]
 fn parses_explicit_repo_argument() {
     let args = Args::try_parse_from(["hunky", "--repo", "/tmp/custom"]).expect("args should parse");
-    assert_eq!(args.repo, "/tmp/custom");
+    assert_eq!(args.repo, vec!["/tmp/custom"]);
 }
 
 #[test]
 fn parses_short_repo_argument() {
     let args = Args::try_parse_from(["hunky", "-r", "/tmp/short"]).expect("args should parse");
-    assert_eq!(args.repo, "/tmp/short");
+    assert_eq!(args.repo, vec!["/tmp/short"]);
+}
+
+#[test]
+fn parses_repeated_repo_arguments_and_worktrees_flag() {
+    let args = Args::try_parse_from(["hunky", "-r", "/tmp/a", "--repo", "/tmp/b", "--worktrees"])
+        .expect("args should parse");
+    assert_eq!(args.repo, vec!["/tmp/a", "/tmp/b"]);
+    assert!(args.worktrees);
+    assert!(!args.recurse_submodules);
+
+    let args = Args::try_parse_from(["hunky", "--recurse-submodules"]).expect("args should parse");
+    assert!(args.recurse_submodules);
+}
+
+#[test]
+fn parses_debounce_intervals_into_a_watch_config() {
+    let args = Args::try_parse_from(["hunky"]).expect("args should parse");
+    assert_eq!(args.watch_config(), watcher::WatchConfig::default());
+
+    let args = Args::try_parse_from(["hunky", "--debounce-ms", "50", "--max-latency-ms", "400"])
+        .expect("args should parse");
+    let config = args.watch_config();
+    assert_eq!(config.quiet_period, Duration::from_millis(50));
+    assert_eq!(config.max_latency, Duration::from_millis(400));
+
+    // The latency cap never undercuts the quiet period
+    let args = Args::try_parse_from(["hunky", "--debounce-ms", "800", "--max-latency-ms", "100"])
+        .expect("args should parse");
+    assert_eq!(args.watch_config().max_latency, Duration::from_millis(800));
+}
+
+#[test]
+fn parses_watch_backend_and_poll_interval() {
+    let args = Args::try_parse_from(["hunky"]).expect("args should parse");
+    assert_eq!(args.watch_config().backend, watcher::WatchBackend::Auto);
+
+    let args = Args::try_parse_from([
+        "hunky",
+        "--watch-backend",
+        "poll",
+        "--poll-interval-ms",
+        "250",
+    ])
+    .expect("args should parse");
+    let config = args.watch_config();
+    assert_eq!(config.backend, watcher::WatchBackend::Poll);
+    assert_eq!(config.poll_interval, Duration::from_millis(250));
+
+    let args =
+        Args::try_parse_from(["hunky", "--watch-backend=native"]).expect("args should parse");
+    assert_eq!(args.watch_backend, watcher::WatchBackend::Native);
+    assert!(Args::try_parse_from(["hunky", "--watch-backend", "fsevents"]).is_err());
 }
 
 #[test]
@@ -33,3 +86,104 @@ fn help_text_mentions_tui_description() {
 fn unknown_argument_returns_error() {
     assert!(Args::try_parse_from(["hunky", "--unknown"]).is_err());
 }
+
+#[test]
+fn parses_open_and_export_files() {
+    let args = Args::try_parse_from(["hunky"]).expect("args should parse");
+    assert_eq!((args.open, args.export), (None, None));
+
+    let args = Args::try_parse_from(["hunky", "--open", "saved.json"]).expect("args should parse");
+    assert_eq!(args.open, Some(PathBuf::from("saved.json")));
+
+    let args = Args::try_parse_from(["hunky", "--export", "wip.patch"]).expect("args should parse");
+    assert_eq!(args.export, Some(PathBuf::from("wip.patch")));
+
+    assert!(Args::try_parse_from(["hunky", "--open", "a.json", "--export", "b.json"]).is_err());
+}
+
+#[test]
+fn git_dir_is_only_honoured_with_a_single_repo() {
+    let args = Args::try_parse_from(["hunky"]).expect("args should parse");
+    assert!(args.check_git_dir(true).is_ok());
+
+    let args =
+        Args::try_parse_from(["hunky", "-r", "/tmp/a", "-r", "/tmp/b"]).expect("args should parse");
+    assert!(args.check_git_dir(false).is_ok());
+    let err = args
+        .check_git_dir(true)
+        .expect_err("GIT_DIR with two repos");
+    assert!(err.to_string().contains("GIT_DIR"), "{}", err);
+
+    let args = Args::try_parse_from(["hunky", "--worktrees"]).expect("args should parse");
+    assert!(args.check_git_dir(true).is_err());
+}
+
+/// A fresh directory under the system temp dir, outside any repository
+fn temp_dir(name: &str) -> PathBuf {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("failed to get system time")
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!(
+        "hunky-main-tests-{}-{}-{}",
+        name,
+        std::process::id(),
+        unique
+    ));
+    std::fs::create_dir_all(&path).expect("failed to create temp directory");
+    path
+}
+
+fn run_git(dir: &Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn export_needs_a_single_repo() {
+    let args =
+        Args::try_parse_from(["hunky", "-r", "/tmp/a", "-r", "/tmp/b"]).expect("args should parse");
+    let err = export(&args, Path::new("unused.json")).expect_err("two repos");
+    assert!(err.to_string().contains("single --repo"), "{}", err);
+}
+
+#[test]
+fn exported_snapshots_open_outside_a_repository() {
+    let repo = temp_dir("repo");
+    run_git(&repo, &["init", "-q"]);
+    run_git(&repo, &["config", "user.name", "Test User"]);
+    run_git(&repo, &["config", "user.email", "test@example.com"]);
+    std::fs::write(repo.join("a.txt"), "one\n").expect("failed to write file");
+    run_git(&repo, &["add", "a.txt"]);
+    run_git(&repo, &["commit", "-qm", "initial"]);
+    std::fs::write(repo.join("a.txt"), "one\ntwo\n").expect("failed to write file");
+
+    let elsewhere = temp_dir("elsewhere");
+    let saved = elsewhere.join("wip.json");
+    let args = Args::try_parse_from(["hunky", "-r", repo.to_str().expect("utf-8 path")])
+        .expect("args should parse");
+    assert_eq!(export(&args, &saved).expect("export"), 1);
+    std::fs::remove_dir_all(&repo).expect("failed to remove repo");
+
+    let app = open_saved(&saved).expect("open saved snapshots");
+    assert_eq!(app.archive_label(), Some("wip.json"));
+    assert_eq!(app.history().len(), 1);
+    assert_eq!(
+        app.current_file().expect("file").path,
+        PathBuf::from("a.txt")
+    );
+
+    let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 20))
+        .expect("failed to create terminal");
+    terminal
+        .draw(|frame| {
+            ui::UI::new(&app).draw(frame);
+        })
+        .expect("failed to draw");
+
+    let _ = std::fs::remove_dir_all(&elsewhere);
+}
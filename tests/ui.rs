@@ -71,6 +71,8 @@ async fn draw_renders_header_and_empty_state() {
     let rendered = render_buffer_to_string(&terminal);
     assert!(rendered.contains("Hunky"));
     assert!(rendered.contains("Files"));
+    assert!(rendered.contains("Working tree clean"));
+    assert!(rendered.contains("No changes against HEAD"));
 
     fs::remove_dir_all(repo_path).expect("failed to remove temp repo");
 }
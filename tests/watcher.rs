@@ -1,36 +1,121 @@
 use super::*;
+use crate::diff::{CleanReason, HeadMoveKind};
 use notify::{
     event::{CreateKind, ModifyKind, RemoveKind},
     EventKind,
 };
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const FS_STABILIZATION_DELAY: Duration = Duration::from_millis(700);
 const WATCHER_RETRY_ATTEMPTS: usize = 3;
 const WATCHER_RECV_TIMEOUT: Duration = Duration::from_secs(3);
 
+/// A matcher for a made-up working tree, which has no ignore rules
+fn matcher_without_repo(repo_path: &Path) -> IgnoreMatcher {
+    IgnoreMatcher {
+        repo_path: repo_path.to_path_buf(),
+        git_repo: None,
+        repo: None,
+        excludes_file: None,
+        cache: HashMap::new(),
+    }
+}
+
+fn processes(event: &Event, repo_path: &Path) -> bool {
+    let mut ignore = match GitRepo::new(repo_path) {
+        Ok(git_repo) => IgnoreMatcher::new(&git_repo),
+        Err(_) => matcher_without_repo(repo_path),
+    };
+    should_process_event(event, repo_path, &mut ignore)
+}
+
 #[test]
 fn processes_working_tree_modifications() {
     let repo_path = PathBuf::from("/tmp/repo");
     let event =
         Event::new(EventKind::Modify(ModifyKind::Any)).add_path(repo_path.join("src/main.rs"));
 
-    assert!(should_process_event(&event, &repo_path));
+    assert!(processes(&event, &repo_path));
 }
 
 #[test]
-fn ignores_git_directory_changes_except_index() {
+fn ignores_git_directory_changes_except_index_head_and_refs() {
     let repo_path = PathBuf::from("/tmp/repo");
     let git_object_event = Event::new(EventKind::Create(CreateKind::Any))
         .add_path(repo_path.join(".git/objects/ab/cdef"));
     let index_event =
         Event::new(EventKind::Modify(ModifyKind::Any)).add_path(repo_path.join(".git/index"));
+    let reflog_event =
+        Event::new(EventKind::Modify(ModifyKind::Any)).add_path(repo_path.join(".git/logs/HEAD"));
+
+    let submodule_index_event = Event::new(EventKind::Modify(ModifyKind::Any))
+        .add_path(repo_path.join(".git/modules/vendor/lib/index"));
 
-    assert!(!should_process_event(&git_object_event, &repo_path));
-    assert!(should_process_event(&index_event, &repo_path));
+    assert!(!processes(&git_object_event, &repo_path));
+    assert!(processes(&submodule_index_event, &repo_path));
+    assert!(!processes(&reflog_event, &repo_path));
+    assert!(processes(&index_event, &repo_path));
+}
+
+#[test]
+fn processes_head_ref_and_operation_state_changes() {
+    let repo_path = PathBuf::from("/tmp/repo");
+    for rel_path in [
+        ".git/HEAD",
+        ".git/refs/heads/main",
+        ".git/refs/remotes/origin/main",
+        ".git/packed-refs",
+        ".git/MERGE_HEAD",
+        ".git/rebase-merge/head-name",
+    ] {
+        let event =
+            Event::new(EventKind::Create(CreateKind::Any)).add_path(repo_path.join(rel_path));
+        assert!(processes(&event, &repo_path), "{}", rel_path);
+        assert!(is_git_metadata_event(&event, &repo_path), "{}", rel_path);
+    }
+
+    // Lock files are written before the real update lands
+    let lock_event = Event::new(EventKind::Create(CreateKind::Any))
+        .add_path(repo_path.join(".git/refs/heads/main.lock"));
+    assert!(!processes(&lock_event, &repo_path));
+
+    let worktree_event =
+        Event::new(EventKind::Modify(ModifyKind::Any)).add_path(repo_path.join("src/main.rs"));
+    assert!(!is_git_metadata_event(&worktree_event, &repo_path));
+}
+
+#[test]
+fn maps_linked_worktree_git_dirs_onto_the_worktree() {
+    let worktree_path = PathBuf::from("/tmp/topic");
+    let git_dirs = vec![
+        PathBuf::from("/tmp/repo/.git/worktrees/topic"),
+        PathBuf::from("/tmp/repo/.git"),
+    ];
+    let mapped = |rel_path: &str| {
+        let event = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(PathBuf::from("/tmp/repo/.git").join(rel_path));
+        map_external_git_dirs(event, &worktree_path, &git_dirs)
+    };
+
+    let head_event = mapped("worktrees/topic/HEAD");
+    assert_eq!(head_event.paths, vec![worktree_path.join(".git/HEAD")]);
+    assert!(is_git_metadata_event(&head_event, &worktree_path));
+    assert!(processes(&mapped("worktrees/topic/index"), &worktree_path));
+    assert!(processes(&mapped("refs/heads/topic"), &worktree_path));
+
+    // Other worktrees' state and object writes are not ours, the main worktree's included
+    assert!(!processes(&mapped("worktrees/other/HEAD"), &worktree_path));
+    assert!(!processes(&mapped("HEAD"), &worktree_path));
+    assert!(!processes(&mapped("index"), &worktree_path));
+    assert_eq!(
+        mapped("index").paths,
+        vec![PathBuf::from("/tmp/repo/.git/index")]
+    );
+    assert!(!processes(&mapped("objects/ab/cdef"), &worktree_path));
 }
 
 #[test]
@@ -38,10 +123,10 @@ fn ignores_non_create_modify_remove_events() {
     let repo_path = PathBuf::from("/tmp/repo");
     let event =
         Event::new(EventKind::Remove(RemoveKind::Any)).add_path(repo_path.join("README.md"));
-    assert!(should_process_event(&event, &repo_path));
+    assert!(processes(&event, &repo_path));
 
     let access_event = Event::new(EventKind::Any).add_path(repo_path.join("README.md"));
-    assert!(!should_process_event(&access_event, &repo_path));
+    assert!(!processes(&access_event, &repo_path));
 }
 
 #[test]
@@ -49,7 +134,7 @@ fn ignores_events_for_paths_outside_repo() {
     let repo_path = PathBuf::from("/tmp/repo");
     let event = Event::new(EventKind::Modify(ModifyKind::Any))
         .add_path(PathBuf::from("/tmp/other/file.txt"));
-    assert!(!should_process_event(&event, &repo_path));
+    assert!(!processes(&event, &repo_path));
 }
 
 #[test]
@@ -61,7 +146,287 @@ fn ignores_gitignored_files() {
     let event =
         Event::new(EventKind::Modify(ModifyKind::Any)).add_path(repo.path.join("hunky.log"));
 
-    assert!(!should_process_event(&event, &repo.path));
+    assert!(!processes(&event, &repo.path));
+}
+
+#[test]
+fn activity_covers_worktree_files_but_not_git_metadata_or_ignored_files() {
+    let repo = TestRepo::new();
+    repo.write_file(".gitignore", "hunky.log\n");
+    repo.commit_all("add ignore rule");
+
+    let at = SystemTime::now();
+    let event = Event::new(EventKind::Create(CreateKind::File))
+        .add_path(repo.path.join("src/new.rs"))
+        .add_path(repo.path.join("hunky.log"))
+        .add_path(repo.path.join(".git/index"))
+        .add_path(repo.path.join(".gitignore"));
+    let touches = worktree_touches(
+        &event,
+        &repo.path,
+        &mut IgnoreMatcher::new(&GitRepo::new(&repo.path).expect("failed to open test repo")),
+        at,
+    );
+
+    assert_eq!(
+        touches,
+        vec![
+            FileTouch {
+                path: PathBuf::from("src/new.rs"),
+                kind: TouchKind::Created,
+                at,
+            },
+            FileTouch {
+                path: PathBuf::from(".gitignore"),
+                kind: TouchKind::Created,
+                at,
+            },
+        ]
+    );
+}
+
+fn test_config() -> WatchConfig {
+    WatchConfig {
+        quiet_period: Duration::from_millis(100),
+        max_latency: Duration::from_millis(400),
+        ..WatchConfig::default()
+    }
+}
+
+#[test]
+fn debouncer_waits_for_a_quiet_period_after_the_last_event() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(test_config());
+    assert_eq!(debouncer.deadline(), None);
+    assert!(!debouncer.is_due(start + Duration::from_secs(10)));
+
+    debouncer.event(start, false);
+    debouncer.event(start + Duration::from_millis(60), false);
+    assert!(!debouncer.is_due(start + Duration::from_millis(100)));
+    assert_eq!(
+        debouncer.deadline(),
+        Some(start + Duration::from_millis(160))
+    );
+    assert!(debouncer.is_due(start + Duration::from_millis(160)));
+
+    assert!(!debouncer.take().git_metadata);
+    assert_eq!(debouncer.deadline(), None);
+}
+
+#[test]
+fn debouncer_caps_latency_during_a_continuous_burst() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(test_config());
+    for step in 0..10 {
+        debouncer.event(start + Duration::from_millis(step * 50), false);
+    }
+    // Events never paused for 100ms, but the first one has waited long enough
+    assert_eq!(
+        debouncer.deadline(),
+        Some(start + Duration::from_millis(400))
+    );
+    assert!(debouncer.is_due(start + Duration::from_millis(450)));
+}
+
+#[test]
+fn debouncer_remembers_git_metadata_until_taken() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(test_config());
+    debouncer.event(start, true);
+    debouncer.event(start + Duration::from_millis(10), false);
+    assert!(debouncer.take().git_metadata);
+
+    debouncer.event(start + Duration::from_millis(500), false);
+    assert!(!debouncer.take().git_metadata);
+}
+
+#[test]
+fn debouncer_collects_worktree_paths_until_the_git_dir_changes() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(test_config());
+    debouncer.event(start, false);
+    debouncer.touched(Path::new("src/main.rs"));
+    debouncer.touched(Path::new("README.md"));
+    debouncer.touched(Path::new("src/main.rs"));
+    assert_eq!(
+        debouncer.take().paths,
+        Some(vec![
+            PathBuf::from("README.md"),
+            PathBuf::from("src/main.rs")
+        ])
+    );
+
+    // An index write can change what every file looks like
+    debouncer.event(start, false);
+    debouncer.touched(Path::new("src/main.rs"));
+    debouncer.touched(Path::new(".git/index"));
+    assert_eq!(debouncer.take().paths, None);
+
+    // Paths don't leak into the next burst
+    debouncer.event(start, true);
+    debouncer.touched(Path::new(".git/HEAD"));
+    debouncer.take();
+    debouncer.event(start, false);
+    debouncer.touched(Path::new("README.md"));
+    assert_eq!(
+        debouncer.take().paths,
+        Some(vec![PathBuf::from("README.md")])
+    );
+}
+
+#[test]
+fn ignore_matcher_skips_ignored_heavy_directories_after_one_lookup() {
+    let repo = TestRepo::new();
+    repo.write_file(".gitignore", "target/\n");
+    fs::create_dir_all(repo.path.join("build")).expect("failed to create dir");
+    repo.write_file("build/tracked.rs", "fn main() {}\n");
+    repo.commit_all("initial");
+
+    let mut ignore =
+        IgnoreMatcher::new(&GitRepo::new(&repo.path).expect("failed to open test repo"));
+    assert!(ignore.is_ignored(Path::new("target/debug/deps/libfoo.rlib")));
+    assert!(ignore.is_ignored(Path::new("crates/a/target/debug/out.o")));
+    // A heavy name that isn't ignored here is looked at like any other path
+    assert!(!ignore.is_ignored(Path::new("build/tracked.rs")));
+
+    // Once the directory is known to be ignored, its contents need no more lookups
+    ignore.repo = None;
+    assert!(ignore.is_ignored(Path::new("target/release/build/other.o")));
+}
+
+#[test]
+fn ignore_matcher_reads_rules_through_a_separate_git_dir() {
+    let repo = TestRepo::new();
+    let work_tree = repo.path.join("checkout");
+    let git_dir = repo.path.join("store.git");
+    fs::create_dir_all(&work_tree).expect("failed to create work tree");
+    run_git(
+        &repo.path,
+        &["init", "-q", "--bare", git_dir.to_str().expect("utf-8")],
+    );
+    fs::write(work_tree.join(".gitignore"), "*.log\n").expect("failed to write file");
+
+    let git_repo = GitRepo::with_git_dir(&work_tree, &git_dir).expect("open git dir");
+    let mut ignore = IgnoreMatcher::new(&git_repo);
+    assert!(ignore.is_ignored(Path::new("out.log")));
+    assert!(!ignore.is_ignored(Path::new("notes.txt")));
+}
+
+#[test]
+fn ignore_matcher_reloads_when_ignore_rules_change() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    let excludes = repo.path.join("global-excludes");
+    fs::write(&excludes, "").expect("failed to write excludes");
+    run_git(
+        &repo.path,
+        &[
+            "config",
+            "core.excludesFile",
+            excludes.to_str().expect("utf-8"),
+        ],
+    );
+
+    let mut ignore =
+        IgnoreMatcher::new(&GitRepo::new(&repo.path).expect("failed to open test repo"));
+    assert_eq!(ignore.excludes_file(), Some(excludes.as_path()));
+    let modified = |path: PathBuf| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path);
+
+    assert!(!ignore.is_ignored(Path::new("out.log")));
+    repo.write_file(".gitignore", "*.log\n");
+    // Answers are cached until the rules are reloaded
+    assert!(!ignore.is_ignored(Path::new("out.log")));
+    assert!(!ignore.refresh_for(&modified(repo.path.join("tracked.txt"))));
+    assert!(ignore.refresh_for(&modified(repo.path.join(".gitignore"))));
+    assert!(ignore.is_ignored(Path::new("out.log")));
+
+    assert!(!ignore.is_ignored(Path::new("notes.tmp")));
+    fs::write(repo.path.join(".git/info/exclude"), "*.tmp\n").expect("failed to write exclude");
+    assert!(ignore.refresh_for(&modified(repo.path.join(".git/info/exclude"))));
+    assert!(ignore.is_ignored(Path::new("notes.tmp")));
+
+    assert!(!ignore.is_ignored(Path::new("scratch.bak")));
+    fs::write(&excludes, "*.bak\n").expect("failed to write excludes");
+    assert!(ignore.refresh_for(&modified(excludes.clone())));
+    assert!(ignore.is_ignored(Path::new("scratch.bak")));
+}
+
+#[test]
+fn debouncer_rebuilds_everything_when_rules_change() {
+    let mut debouncer = Debouncer::new(test_config());
+    debouncer.event(Instant::now(), false);
+    debouncer.touched(Path::new(".gitignore"));
+    debouncer.rules_changed(Instant::now());
+    assert_eq!(debouncer.take().paths, None);
+}
+
+#[test]
+fn poll_events_report_created_modified_and_removed_files() {
+    let mtime = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+    let last = HashMap::from([
+        (PathBuf::from("/tmp/repo/kept.txt"), (mtime(1), 10)),
+        (PathBuf::from("/tmp/repo/touched.txt"), (mtime(1), 10)),
+        (PathBuf::from("/tmp/repo/grown.txt"), (mtime(1), 10)),
+        (PathBuf::from("/tmp/repo/gone.txt"), (mtime(1), 10)),
+    ]);
+    let next = HashMap::from([
+        (PathBuf::from("/tmp/repo/kept.txt"), (mtime(1), 10)),
+        (PathBuf::from("/tmp/repo/touched.txt"), (mtime(2), 10)),
+        (PathBuf::from("/tmp/repo/grown.txt"), (mtime(1), 11)),
+        (PathBuf::from("/tmp/repo/new.txt"), (mtime(2), 1)),
+    ]);
+
+    let mut events: Vec<(String, EventKind)> = poll_events(&last, &next)
+        .into_iter()
+        .map(|event| {
+            let name = event.paths[0].file_name().expect("file name");
+            (name.to_string_lossy().into_owned(), event.kind)
+        })
+        .collect();
+    events.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        events,
+        vec![
+            ("gone.txt".to_string(), EventKind::Remove(RemoveKind::File)),
+            ("grown.txt".to_string(), EventKind::Modify(ModifyKind::Any)),
+            ("new.txt".to_string(), EventKind::Create(CreateKind::File)),
+            (
+                "touched.txt".to_string(),
+                EventKind::Modify(ModifyKind::Any)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn poll_scan_covers_worktree_files_and_git_state_but_not_ignored_files() {
+    let repo = TestRepo::new();
+    repo.write_file(".gitignore", "*.log\n");
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("untracked.txt", "new\n");
+    repo.write_file("debug.log", "noise\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let git_dir = repo.path.join(".git");
+    let files = poll_scan(&git_repo, std::slice::from_ref(&git_dir));
+
+    for path in [
+        repo.path.join("tracked.txt"),
+        repo.path.join("untracked.txt"),
+        git_dir.join("index"),
+        git_dir.join("HEAD"),
+    ] {
+        assert!(files.contains_key(&path), "{}", path.display());
+    }
+    assert!(files
+        .keys()
+        .any(|path| path.starts_with(git_dir.join("refs/heads"))));
+    assert!(!files.contains_key(&repo.path.join("debug.log")));
+    assert!(!files
+        .keys()
+        .any(|path| path.starts_with(git_dir.join("objects"))));
 }
 
 struct TestRepo {
@@ -130,16 +495,315 @@ async fn watcher_emits_snapshot_for_tracked_file_changes() {
 
     for attempt in 0..WATCHER_RETRY_ATTEMPTS {
         repo.write_file("tracked.txt", &format!("line 1\nline {}\n", attempt + 2));
-        if let Ok(Some(snapshot)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
-            assert!(!snapshot.files.is_empty());
-            assert!(snapshot
-                .files
-                .iter()
-                .any(|file| file.path.ends_with("tracked.txt")));
-            return;
+        // Activity for the write arrives ahead of its snapshot
+        while let Ok(Some(event)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+            if let WatchEvent::Snapshot(snapshot) = event {
+                assert!(!snapshot.files.is_empty());
+                assert!(snapshot
+                    .files
+                    .iter()
+                    .any(|file| file.path.ends_with("tracked.txt")));
+                return;
+            }
         }
         tokio::time::sleep(FS_STABILIZATION_DELAY).await;
     }
 
     panic!("watcher did not emit a snapshot in time");
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watcher_reports_head_move_on_clean_tree() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher = FileWatcher::new(git_repo, tx).expect("failed to start watcher");
+
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+
+    for attempt in 0..WATCHER_RETRY_ATTEMPTS {
+        run_git(
+            &repo.path,
+            &["checkout", "-q", "-b", &format!("topic-{}", attempt)],
+        );
+        // `checkout -b` writes the new branch ref before HEAD, so a snapshot for the ref
+        // event may come first; it must not carry any changes.
+        while let Ok(Some(event)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+            match event {
+                WatchEvent::HeadMoved(head_move) => {
+                    assert_eq!(head_move.kind, HeadMoveKind::Checkout);
+                    assert_eq!(head_move.branch, Some(format!("topic-{}", attempt)));
+                    return;
+                }
+                WatchEvent::Snapshot(snapshot) => assert!(snapshot.files.is_empty()),
+                WatchEvent::TreeClean(reason) => panic!("tree was already clean: {:?}", reason),
+                WatchEvent::Warning(warning) => panic!("unexpected warning: {}", warning),
+                WatchEvent::Activity(_) => {}
+            }
+        }
+        tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+    }
+
+    panic!("watcher did not report the branch switch");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watcher_reports_commit_then_snapshot() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher = FileWatcher::new(git_repo, tx).expect("failed to start watcher");
+
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+    repo.commit_all("second");
+
+    let mut saw_head_move = false;
+    while let Ok(Some(event)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+        match event {
+            WatchEvent::HeadMoved(head_move) => {
+                assert_eq!(head_move.kind, HeadMoveKind::Commit);
+                assert_eq!(head_move.commit.summary, "second");
+                saw_head_move = true;
+            }
+            WatchEvent::Snapshot(snapshot) if snapshot.files.is_empty() => {
+                assert!(saw_head_move, "clean snapshot arrived before the HEAD move");
+                return;
+            }
+            WatchEvent::Snapshot(_) => {}
+            // Only when the edit got a snapshot of its own before the commit
+            WatchEvent::TreeClean(reason) => {
+                assert!(saw_head_move, "tree went clean before the HEAD move");
+                assert_eq!(reason, CleanReason::Committed);
+            }
+            WatchEvent::Warning(warning) => panic!("unexpected warning: {}", warning),
+            WatchEvent::Activity(_) => {}
+        }
+    }
+
+    panic!("watcher did not report the commit");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watcher_snapshot_after_a_burst_includes_the_last_write() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher =
+        FileWatcher::with_config(git_repo, tx, test_config()).expect("failed to start watcher");
+
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+
+    for step in 0..5 {
+        repo.write_file("tracked.txt", &format!("line 1\nwrite {}\n", step));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // Whatever snapshots arrive during the burst, the last one must hold the final write
+    let mut last_snapshot = None;
+    while let Ok(Some(event)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+        if let WatchEvent::Snapshot(snapshot) = event {
+            last_snapshot = Some(snapshot);
+        }
+    }
+    let snapshot = last_snapshot.expect("watcher did not emit a snapshot");
+    let file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path.ends_with("tracked.txt"))
+        .expect("snapshot should include tracked.txt");
+    assert!(file
+        .hunks
+        .iter()
+        .any(|hunk| hunk.lines.iter().any(|line| line.contains("write 4"))));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_stops_the_watcher_task() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let baseline = metrics.num_alive_tasks();
+
+    let watcher = FileWatcher::new(git_repo, tx).expect("failed to start watcher");
+    assert_eq!(metrics.num_alive_tasks(), baseline + 1);
+
+    tokio::time::timeout(WATCHER_RECV_TIMEOUT, watcher.shutdown())
+        .await
+        .expect("watcher did not shut down");
+    // The joiner is woken just before the runtime releases the task
+    let released = tokio::time::timeout(WATCHER_RECV_TIMEOUT, async {
+        while metrics.num_alive_tasks() > baseline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert!(released.is_ok(), "watcher task is still alive");
+    // The task owned the sender, so the channel is closed once drained
+    while rx.try_recv().is_ok() {}
+    assert!(rx.is_closed());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropping_the_watcher_cancels_its_task() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher =
+        FileWatcher::with_config(git_repo, tx, test_config()).expect("failed to start watcher");
+
+    // Leave a burst pending so the task is mid-debounce when dropped
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+    drop(watcher);
+
+    let closed = tokio::time::timeout(WATCHER_RECV_TIMEOUT, async {
+        while rx.recv().await.is_some() {}
+    })
+    .await;
+    assert!(closed.is_ok(), "watcher task outlived its FileWatcher");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn poll_backend_emits_snapshots_without_native_events() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let config = WatchConfig {
+        backend: WatchBackend::Poll,
+        poll_interval: Duration::from_millis(100),
+        ..test_config()
+    };
+    let watcher = FileWatcher::with_config(git_repo, tx, config).expect("failed to start");
+    assert!(watcher._watcher.is_none());
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    loop {
+        match tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+            Ok(Some(WatchEvent::Snapshot(snapshot))) => {
+                assert!(snapshot
+                    .files
+                    .iter()
+                    .any(|file| file.path.ends_with("tracked.txt")));
+                break;
+            }
+            Ok(Some(WatchEvent::Warning(warning))) => panic!("unexpected warning: {}", warning),
+            Ok(Some(WatchEvent::HeadMoved(_) | WatchEvent::Activity(_))) => {}
+            _ => panic!("poll backend did not emit a snapshot in time"),
+        }
+    }
+    tokio::time::timeout(WATCHER_RECV_TIMEOUT, watcher.shutdown())
+        .await
+        .expect("poller did not stop");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn auto_backend_keeps_native_events_when_the_probe_arrives() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher = FileWatcher::with_config(git_repo, tx, test_config()).expect("failed to start");
+
+    // No fallback warning within the probe timeout, and the probe file is cleaned up
+    let received = tokio::time::timeout(PROBE_TIMEOUT + FS_STABILIZATION_DELAY, async {
+        loop {
+            if let Some(WatchEvent::Warning(warning)) = rx.recv().await {
+                return warning;
+            }
+        }
+    })
+    .await;
+    assert!(received.is_err(), "unexpected warning: {:?}", received);
+    let probe = repo
+        .path
+        .join(format!(".git/hunky-watch-probe-{}", std::process::id()));
+    assert!(!probe.exists());
+}
+
+/// Events until the first empty snapshot, which must arrive in time
+async fn events_until_clean(rx: &mut mpsc::UnboundedReceiver<WatchEvent>) -> Vec<WatchEvent> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = tokio::time::timeout(WATCHER_RECV_TIMEOUT, rx.recv()).await {
+        let clean = matches!(&event, WatchEvent::Snapshot(snapshot) if snapshot.files.is_empty());
+        events.push(event);
+        if clean {
+            return events;
+        }
+    }
+    panic!("watcher did not send an empty snapshot; got {:?}", events);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watcher_sends_empty_snapshot_when_changes_are_reverted() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher = FileWatcher::new(git_repo, tx).expect("failed to start watcher");
+
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+    run_git(&repo.path, &["checkout", "--", "tracked.txt"]);
+
+    let events = events_until_clean(&mut rx).await;
+    assert!(
+        events
+            .iter()
+            .any(|event| matches!(event, WatchEvent::TreeClean(CleanReason::Cleared))),
+        "{:?}",
+        events
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watcher_reports_committed_changes_as_clean() {
+    let repo = TestRepo::new();
+    repo.write_file("tracked.txt", "line 1\n");
+    repo.commit_all("initial");
+    repo.write_file("tracked.txt", "line 1\nline 2\n");
+
+    let git_repo = GitRepo::new(&repo.path).expect("failed to open repo");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _watcher = FileWatcher::new(git_repo, tx).expect("failed to start watcher");
+
+    tokio::time::sleep(FS_STABILIZATION_DELAY).await;
+    repo.commit_all("second");
+
+    let events = events_until_clean(&mut rx).await;
+    let head_move = events
+        .iter()
+        .position(|event| matches!(event, WatchEvent::HeadMoved(_)))
+        .expect("HEAD move should be reported");
+    let clean = events
+        .iter()
+        .position(|event| matches!(event, WatchEvent::TreeClean(CleanReason::Committed)))
+        .expect("tree should be reported clean by the commit");
+    assert!(head_move < clean, "{:?}", events);
+}